@@ -0,0 +1,296 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use dam::simulation::ProgramBuilder;
+
+use super::traffic::{attention_ops, Architecture};
+use crate::{
+    graph::{
+        flashattn::{flash_attn, FlashAttnConfig},
+        multihead::{
+            add_checker, flash_reference, streamed_reference, synthetic_k, synthetic_q, synthetic_v,
+        },
+        streamattn::{streamed_attn, StreamAttnConfig},
+    },
+    memory::{
+        dram::{DramConfig, DramRead},
+        AccessPattern,
+    },
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OpKind {
+    Mul,
+    Add, // also subtractions and comparisons
+    Exp,
+    Div,
+    SramAccess,
+    DramByte,
+}
+
+pub const OP_KINDS: [OpKind; 6] = [
+    OpKind::Mul,
+    OpKind::Add,
+    OpKind::Exp,
+    OpKind::Div,
+    OpKind::SramAccess,
+    OpKind::DramByte,
+];
+
+// Tally shared by all nodes of a graph, nodes add to it as they fire.
+// The default counter is private to its owner, so nodes without one count into the void.
+#[derive(Clone, Default)]
+pub struct OpCounter {
+    counts: Arc<[AtomicU64; 6]>,
+}
+
+impl OpCounter {
+    pub fn add(&self, kind: OpKind, n: u64) {
+        self.counts[kind as usize].fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self, kind: OpKind) -> u64 {
+        self.counts[kind as usize].load(Ordering::Relaxed)
+    }
+
+    // Arithmetic ops, memory accesses excluded
+    pub fn compute_ops(&self) -> u64 {
+        [OpKind::Mul, OpKind::Add, OpKind::Exp, OpKind::Div]
+            .iter()
+            .map(|k| self.get(*k))
+            .sum()
+    }
+}
+
+// Energy per event in pJ
+#[derive(Clone, Debug)]
+pub struct EnergyModel {
+    pub mul_pj: f64,
+    pub add_pj: f64,
+    pub exp_pj: f64,
+    pub div_pj: f64,
+    pub sram_access_pj: f64, // per word
+    pub dram_byte_pj: f64,
+}
+
+impl Default for EnergyModel {
+    // Double-precision units and 64-bit SRAM words in a 45nm-class process
+    fn default() -> Self {
+        EnergyModel {
+            mul_pj: 15.0,
+            add_pj: 3.5,
+            exp_pj: 40.0,
+            div_pj: 45.0,
+            sram_access_pj: 10.0,
+            dram_byte_pj: 20.0,
+        }
+    }
+}
+
+impl EnergyModel {
+    pub fn energy_pj(&self, kind: OpKind) -> f64 {
+        match kind {
+            OpKind::Mul => self.mul_pj,
+            OpKind::Add => self.add_pj,
+            OpKind::Exp => self.exp_pj,
+            OpKind::Div => self.div_pj,
+            OpKind::SramAccess => self.sram_access_pj,
+            OpKind::DramByte => self.dram_byte_pj,
+        }
+    }
+}
+
+// Peak throughput of the target the design is placed against
+#[derive(Clone, Debug)]
+pub struct Roofline {
+    pub peak_ops_per_cycle: f64,
+    pub dram_bytes_per_cycle: f64,
+}
+
+impl Roofline {
+    // Ops per byte where the memory and compute roofs meet
+    pub fn ridge_point(&self) -> f64 {
+        self.peak_ops_per_cycle / self.dram_bytes_per_cycle
+    }
+
+    pub fn attainable_ops_per_cycle(&self, intensity: f64) -> f64 {
+        self.peak_ops_per_cycle
+            .min(intensity * self.dram_bytes_per_cycle)
+    }
+}
+
+pub struct CostReport {
+    pub counts: Vec<(OpKind, u64)>,
+    pub model: EnergyModel,
+    pub roofline: Roofline,
+    pub useful_ops: u64, // ops placed on the roofline (see traffic::attention_ops)
+    pub elapsed_cycles: u64,
+    pub clock_ghz: f64,
+}
+
+impl CostReport {
+    pub fn new(
+        counter: &OpCounter,
+        model: EnergyModel,
+        roofline: Roofline,
+        useful_ops: u64,
+        elapsed_cycles: u64,
+        clock_ghz: f64,
+    ) -> Self {
+        CostReport {
+            counts: OP_KINDS.iter().map(|k| (*k, counter.get(*k))).collect(),
+            model,
+            roofline,
+            useful_ops,
+            elapsed_cycles,
+            clock_ghz,
+        }
+    }
+
+    pub fn count(&self, kind: OpKind) -> u64 {
+        self.counts
+            .iter()
+            .find(|(k, _)| *k == kind)
+            .map(|(_, n)| *n)
+            .unwrap_or(0)
+    }
+
+    pub fn energy_pj(&self, kind: OpKind) -> f64 {
+        (self.count(kind) as f64) * self.model.energy_pj(kind)
+    }
+
+    pub fn total_energy_pj(&self) -> f64 {
+        OP_KINDS.iter().map(|k| self.energy_pj(*k)).sum()
+    }
+
+    // pJ per ns is mW
+    pub fn power_mw(&self) -> f64 {
+        self.total_energy_pj() * self.clock_ghz / (self.elapsed_cycles as f64)
+    }
+
+    pub fn arithmetic_intensity(&self) -> f64 {
+        (self.useful_ops as f64) / (self.count(OpKind::DramByte) as f64)
+    }
+
+    pub fn achieved_ops_per_cycle(&self) -> f64 {
+        (self.useful_ops as f64) / (self.elapsed_cycles as f64)
+    }
+
+    pub fn memory_bound(&self) -> bool {
+        self.arithmetic_intensity() < self.roofline.ridge_point()
+    }
+}
+
+impl fmt::Display for CostReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<12} {:>12} {:>16}", "event", "count", "energy (pJ)")?;
+        for (kind, count) in self.counts.iter() {
+            writeln!(
+                f,
+                "{:<12} {:>12} {:>16.1}",
+                format!("{:?}", kind),
+                count,
+                self.energy_pj(*kind)
+            )?;
+        }
+        writeln!(
+            f,
+            "{} cycles, {:.1} nJ, {:.1} mW at {} GHz",
+            self.elapsed_cycles,
+            self.total_energy_pj() / 1000_f64,
+            self.power_mw(),
+            self.clock_ghz
+        )?;
+        write!(
+            f,
+            "roofline: {:.3} ops/byte ({}-bound, ridge {:.3}), {:.3} of {:.3} attainable ops/cycle",
+            self.arithmetic_intensity(),
+            if self.memory_bound() {
+                "memory"
+            } else {
+                "compute"
+            },
+            self.roofline.ridge_point(),
+            self.achieved_ops_per_cycle(),
+            self.roofline
+                .attainable_ops_per_cycle(self.arithmetic_intensity())
+        )
+    }
+}
+
+// Builds one head of the given architecture with Q, K and V served from DRAM
+// and tallies the energy of every op and access
+pub fn run_attn_cost(
+    architecture: Architecture,
+    seq_len: u64,
+    model: EnergyModel,
+    roofline: Roofline,
+    clock_ghz: f64,
+) -> CostReport {
+    let counter = OpCounter::default();
+    let chan_size = 2;
+
+    let mut ctx = ProgramBuilder::default();
+
+    let dram_config = || DramConfig {
+        bytes_per_cycle: 64,
+        burst_bytes: 64,
+        latency: 100,
+    };
+    let q_data: Vec<f64> = (0..seq_len).map(synthetic_q).collect();
+    let kt_data: Vec<f64> = (0..seq_len).map(synthetic_k).collect();
+    let v_data: Vec<f64> = (0..seq_len).map(synthetic_v).collect();
+    let mut add_dram = |data: Vec<f64>, pattern: AccessPattern| {
+        let (sender, receiver) = ctx.bounded::<f64>(chan_size);
+        ctx.add_child(
+            DramRead::new(data, pattern, sender, dram_config()).with_op_counter(counter.clone()),
+        );
+        receiver
+    };
+    let q = add_dram(q_data, AccessPattern::sequential(seq_len));
+    let kt = add_dram(kt_data, AccessPattern::repeated(seq_len, seq_len));
+    let v = add_dram(v_data, AccessPattern::repeated(seq_len, seq_len));
+
+    let (out, expected) = match architecture {
+        Architecture::Streamed => {
+            let config = StreamAttnConfig {
+                seq_len,
+                op_counter: counter.clone(),
+                ..Default::default()
+            };
+            (
+                streamed_attn(&mut ctx, q, kt, v, &config, seq_len),
+                streamed_reference(0..seq_len, seq_len),
+            )
+        }
+        Architecture::Flash => {
+            let config = FlashAttnConfig {
+                seq_len,
+                op_counter: counter.clone(),
+                ..Default::default()
+            };
+            (
+                flash_attn(&mut ctx, q, kt, v, &config, seq_len),
+                flash_reference(0..seq_len, seq_len),
+            )
+        }
+    };
+    add_checker(&mut ctx, out, expected);
+
+    let initialized = ctx.initialize(Default::default()).unwrap();
+    let summary = initialized.run(Default::default());
+
+    CostReport::new(
+        &counter,
+        model,
+        roofline,
+        attention_ops(seq_len),
+        summary.elapsed_cycles().unwrap(),
+        clock_ghz,
+    )
+}
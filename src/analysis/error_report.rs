@@ -0,0 +1,221 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use dam::context_tools::*;
+
+// Absolute errors are binned by decade: bucket k holds errors in [10^-(k+1), 10^-k)
+// The last bucket also collects anything smaller, the first anything larger.
+pub const ABS_ERR_DECADES: usize = 17;
+// ULP distances are binned by powers of two: bucket k holds distances in [2^(k-1), 2^k)
+// Bucket 0 holds exact matches.
+pub const ULP_BUCKETS: usize = 65;
+
+pub trait ErrorMetric: Copy {
+    fn as_f64(self) -> f64;
+    fn ulp_distance(self, rhs: Self) -> u64;
+}
+
+impl ErrorMetric for f32 {
+    fn as_f64(self) -> f64 {
+        self as f64
+    }
+    fn ulp_distance(self, rhs: f32) -> u64 {
+        // Map the sign-magnitude encoding onto a monotonic integer line
+        let ordered = |x: f32| {
+            let bits = x.to_bits() as i32;
+            if bits < 0 {
+                (i32::MIN - bits) as i64
+            } else {
+                bits as i64
+            }
+        };
+        ordered(self).abs_diff(ordered(rhs))
+    }
+}
+
+impl ErrorMetric for f64 {
+    fn as_f64(self) -> f64 {
+        self
+    }
+    fn ulp_distance(self, rhs: f64) -> u64 {
+        let ordered = |x: f64| {
+            let bits = x.to_bits() as i64;
+            if bits < 0 {
+                (i64::MIN - bits) as i128
+            } else {
+                bits as i128
+            }
+        };
+        ordered(self).abs_diff(ordered(rhs)) as u64
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ErrorReport {
+    pub count: u64,
+    pub exact_matches: u64,
+    pub non_finite: u64, // NaN or infinite errors, kept out of the histograms
+    pub max_abs_err: f64,
+    pub max_rel_err: f64,
+    pub max_ulp: u64,
+    pub sum_abs_err: f64,
+    pub abs_err_histogram: Vec<u64>,
+    pub ulp_histogram: Vec<u64>,
+}
+
+impl Default for ErrorReport {
+    fn default() -> Self {
+        ErrorReport {
+            count: 0,
+            exact_matches: 0,
+            non_finite: 0,
+            max_abs_err: 0_f64,
+            max_rel_err: 0_f64,
+            max_ulp: 0,
+            sum_abs_err: 0_f64,
+            abs_err_histogram: vec![0; ABS_ERR_DECADES],
+            ulp_histogram: vec![0; ULP_BUCKETS],
+        }
+    }
+}
+
+// Max that keeps a NaN once one has been seen
+fn nan_max(lhs: f64, rhs: f64) -> f64 {
+    if lhs.is_nan() || rhs.is_nan() {
+        f64::NAN
+    } else {
+        lhs.max(rhs)
+    }
+}
+
+impl ErrorReport {
+    pub fn record<A: ErrorMetric>(&mut self, actual: A, expected: A) {
+        // Matching infinities are exact, anything else involving NaN or inf is not finite
+        let abs_err = if actual.as_f64() == expected.as_f64() {
+            0_f64
+        } else {
+            (actual.as_f64() - expected.as_f64()).abs()
+        };
+        let rel_err = if expected.as_f64() == 0_f64 {
+            abs_err
+        } else {
+            abs_err / expected.as_f64().abs()
+        };
+
+        self.count += 1;
+        self.sum_abs_err += abs_err;
+        if !abs_err.is_finite() {
+            // f64::max drops NaN, so a NaN error has to be propagated by hand
+            self.non_finite += 1;
+            self.max_abs_err = nan_max(self.max_abs_err, abs_err);
+            self.max_rel_err = nan_max(self.max_rel_err, rel_err);
+            return;
+        }
+        let ulp = actual.ulp_distance(expected);
+        self.max_abs_err = nan_max(self.max_abs_err, abs_err);
+        self.max_rel_err = nan_max(self.max_rel_err, rel_err);
+        self.max_ulp = self.max_ulp.max(ulp);
+
+        if abs_err == 0_f64 {
+            self.exact_matches += 1;
+        } else {
+            let decade = (-abs_err.log10()).floor().max(0_f64) as usize;
+            self.abs_err_histogram[decade.min(ABS_ERR_DECADES - 1)] += 1;
+        }
+        self.ulp_histogram[(u64::BITS - ulp.leading_zeros()) as usize] += 1;
+    }
+
+    pub fn mean_abs_err(&self) -> f64 {
+        if self.count == 0 {
+            0_f64
+        } else {
+            self.sum_abs_err / (self.count as f64)
+        }
+    }
+}
+
+impl fmt::Display for ErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "samples:       {}", self.count)?;
+        writeln!(f, "exact matches: {}", self.exact_matches)?;
+        writeln!(f, "non-finite:    {}", self.non_finite)?;
+        writeln!(f, "max abs err:   {:e}", self.max_abs_err)?;
+        writeln!(f, "mean abs err:  {:e}", self.mean_abs_err())?;
+        writeln!(f, "max rel err:   {:e}", self.max_rel_err)?;
+        writeln!(f, "max ulp:       {}", self.max_ulp)?;
+        writeln!(f, "abs err histogram:")?;
+        for (k, n) in self.abs_err_histogram.iter().enumerate() {
+            if *n > 0 {
+                writeln!(f, "  [1e-{:<2}, 1e-{:<2}): {}", k + 1, k, n)?;
+            }
+        }
+        writeln!(f, "ulp histogram:")?;
+        for (k, n) in self.ulp_histogram.iter().enumerate() {
+            if *n > 0 {
+                match k {
+                    0 => writeln!(f, "  0 ulp: {}", n)?,
+                    _ => writeln!(f, "  [2^{}, 2^{}) ulp: {}", k - 1, k, n)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[context_macro]
+pub struct ErrorAnalysisContext<A: Clone> {
+    // Consumes the whole stream and compares it to a reference
+    // instead of failing on the first mismatch like the checker contexts
+    reference: Vec<A>,
+    pub in_stream: Receiver<A>,
+    report: Arc<Mutex<ErrorReport>>,
+}
+
+impl<A: DAMType> ErrorAnalysisContext<A>
+where
+    ErrorAnalysisContext<A>: Context,
+{
+    pub fn new<FType, IType>(reference: FType, in_stream: Receiver<A>) -> Self
+    where
+        FType: FnOnce() -> IType,
+        IType: IntoIterator<Item = A>,
+    {
+        let analysis = ErrorAnalysisContext {
+            reference: reference().into_iter().collect(),
+            in_stream,
+            report: Default::default(),
+            context_info: Default::default(),
+        };
+        (analysis.in_stream).attach_receiver(&analysis);
+
+        analysis
+    }
+
+    // Handle to read the report once the simulation has finished
+    pub fn report(&self) -> Arc<Mutex<ErrorReport>> {
+        self.report.clone()
+    }
+}
+
+impl<A> Context for ErrorAnalysisContext<A>
+where
+    A: DAMType + ErrorMetric,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        for expected in self.reference.iter() {
+            let in_deq = self.in_stream.dequeue(&self.time);
+            match in_deq {
+                Ok(in_elem) => {
+                    self.report.lock().unwrap().record(in_elem.data, *expected);
+                }
+                _ => {
+                    panic!("Reached unhandled case");
+                }
+            }
+        }
+    }
+}
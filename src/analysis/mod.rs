@@ -0,0 +1,4 @@
+pub mod cost;
+pub mod error_report;
+pub mod resource;
+pub mod traffic;
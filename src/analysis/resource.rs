@@ -0,0 +1,604 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use dam::simulation::ProgramBuilder;
+
+use super::traffic::Architecture;
+use crate::{
+    graph::{
+        flashattn::{flash_attn, FlashAttnConfig},
+        multihead::{add_checker, add_generators, flash_reference, streamed_reference},
+        streamattn::{streamed_attn, StreamAttnConfig},
+    },
+    node::{
+        bias::AlibiBias,
+        broadcast::Interleave,
+        flashattn_binary_op::BinaryOp,
+        flashattn_lse::LogSumExp,
+        flashattn_running_op::{IncrMax, IncrOutP, IncrSum},
+        flashattn_tiled_op::{TiledIncrMax, TiledIncrOutP, TiledIncrSum},
+        normalizer::{LinearAttn, QKTAct},
+        simd_op::{SimdBinary, SimdBinaryOp, SimdIncrMax, SimdIncrOutP, SimdIncrSum, SimdQKTExp},
+        streamattn_binary::Binary,
+        streamattn_matvec::MatVecProd,
+        streamattn_qkt::{QKTExp, QKTExpMultiRow},
+        streamattn_reduce::ReduceOp,
+        tree_reduce::{tree_levels, PartialSumAccum, TreeReduce},
+    },
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnitKind {
+    Multiplier,
+    Adder, // also subtractors and comparators
+    Divider,
+    ExpUnit,
+}
+
+pub const UNIT_KINDS: [UnitKind; 4] = [
+    UnitKind::Multiplier,
+    UnitKind::Adder,
+    UnitKind::Divider,
+    UnitKind::ExpUnit,
+];
+
+// Hardware a node needs to sustain its II, with d = 1
+pub trait Resources {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)>;
+    // Pipeline stages, each holds one result word per lane
+    fn pipeline_depth(&self) -> u64;
+    fn word_bits(&self) -> u64;
+    fn lanes(&self) -> u64 {
+        1
+    }
+}
+
+fn word_bits<A>() -> u64 {
+    (std::mem::size_of::<A>() * 8) as u64
+}
+
+impl<A: Clone> Resources for QKTExp<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        let mut units = vec![(UnitKind::Multiplier, 1), (UnitKind::ExpUnit, 1)];
+        units.extend(self.score_scale.functional_units());
+        if self.bias.is_some() {
+            units.push((UnitKind::Adder, 1));
+        }
+        units
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency + self.exp_unit.latency + self.score_scale.latency + self.bias_latency
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+}
+
+impl<A: Clone> Resources for QKTExpMultiRow<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        let lanes = self.lanes();
+        let mut units = vec![(UnitKind::Multiplier, lanes), (UnitKind::ExpUnit, lanes)];
+        units.extend(
+            self.score_scale
+                .functional_units()
+                .into_iter()
+                .map(|(kind, n)| (kind, n * lanes)),
+        );
+        units
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency + self.exp_unit.latency + self.score_scale.latency
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+    fn lanes(&self) -> u64 {
+        self.out_fifo.len() as u64
+    }
+}
+
+impl<A: Clone> Resources for AlibiBias<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        vec![(UnitKind::Multiplier, 1)]
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+}
+
+impl<A: Clone> Resources for QKTAct<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        let mut units = vec![(UnitKind::Multiplier, 1)];
+        units.extend(self.score_scale.functional_units());
+        units.extend(self.activation.functional_units());
+        units
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency + self.score_scale.latency
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+}
+
+// two feature maps, the S and z updates, the projections with phi(q) and the divide
+impl<A: Clone> Resources for LinearAttn<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        let mut units = vec![
+            (UnitKind::Multiplier, 6),
+            (UnitKind::Adder, 12),
+            (UnitKind::ExpUnit, 4),
+            (UnitKind::Divider, 1),
+        ];
+        units.extend(self.score_scale.functional_units());
+        units
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency + self.score_scale.latency
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+}
+
+impl<A: Clone> Resources for ReduceOp<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        self.op.functional_units()
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+}
+
+// W - 1 combiners in the tree and one accumulator
+impl<A: Clone> Resources for TreeReduce<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        let lanes = self.lanes();
+        self.op
+            .functional_units()
+            .into_iter()
+            .map(|(kind, n)| (kind, n * lanes))
+            .collect()
+    }
+    fn pipeline_depth(&self) -> u64 {
+        (tree_levels(self.lanes()) + 1) * self.add_latency
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+    fn lanes(&self) -> u64 {
+        self.in_lanes.len() as u64
+    }
+}
+
+// One accumulating combiner and one merging the partials, which are held as extra stages
+impl<A: Clone> Resources for PartialSumAccum<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        self.op
+            .functional_units()
+            .into_iter()
+            .map(|(kind, n)| (kind, 2 * n))
+            .collect()
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.add_latency + self.num_partials
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+}
+
+impl<A: Clone> Resources for Binary<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        vec![(self.op.unit_kind(), 1)]
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+}
+
+impl<A: Clone> Resources for BinaryOp<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        vec![(self.op.unit_kind(), 1)]
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+}
+
+impl<A: Clone> Resources for MatVecProd<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        vec![(UnitKind::Multiplier, 1), (UnitKind::Adder, 1)]
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+}
+
+// max, two subtractions and the exponentials of delta and curr
+impl<A: Clone> Resources for IncrMax<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        vec![(UnitKind::Adder, 3), (UnitKind::ExpUnit, 2)]
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency + self.exp_unit.latency
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+}
+
+// Interleaved rows hold one extra accumulator each
+impl<A: Clone> Resources for IncrSum<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        vec![(UnitKind::Multiplier, 1), (UnitKind::Adder, 1)]
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency + self.num_accumulators - 1
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+}
+
+impl<A: Clone> Resources for IncrOutP<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        vec![(UnitKind::Multiplier, 2), (UnitKind::Adder, 1)]
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency + self.num_accumulators - 1
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+}
+
+// A mux, only its output register
+impl<A: Clone> Resources for Interleave<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        vec![]
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+}
+
+// The log is counted on the exp unit, like in the op counter
+impl<A: Clone> Resources for LogSumExp<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        vec![(UnitKind::ExpUnit, 1), (UnitKind::Adder, 1)]
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+}
+
+// The tile is double buffered for the block max, on top of the pipeline
+impl<A: Clone> Resources for TiledIncrMax<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        vec![(UnitKind::Adder, 3), (UnitKind::ExpUnit, 2)]
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency + self.exp_unit.latency + 2 * self.tile_size
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+}
+
+impl<A: Clone> Resources for TiledIncrSum<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        vec![(UnitKind::Multiplier, 1), (UnitKind::Adder, 2)]
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+}
+
+impl<A: Clone> Resources for TiledIncrOutP<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        vec![(UnitKind::Multiplier, 2), (UnitKind::Adder, 2)]
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+}
+
+// Packed nodes replicate their datapath per lane; the running ops keep one scalar recurrence
+// behind a W-input adder tree
+impl<A: Clone> Resources for SimdQKTExp<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        let mut units = vec![
+            (UnitKind::Multiplier, self.lanes),
+            (UnitKind::ExpUnit, self.lanes),
+        ];
+        units.extend(
+            self.score_scale
+                .functional_units()
+                .into_iter()
+                .map(|(kind, n)| (kind, n * self.lanes)),
+        );
+        if self.bias.is_some() {
+            units.push((UnitKind::Adder, self.lanes));
+        }
+        units
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency + self.exp_unit.latency + self.score_scale.latency + self.bias_latency
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+    fn lanes(&self) -> u64 {
+        self.lanes
+    }
+}
+
+impl<A: Clone> Resources for SimdIncrMax<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        vec![
+            (UnitKind::Adder, 2 * self.lanes + 1),
+            (UnitKind::ExpUnit, self.lanes + 1),
+        ]
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.total_latency()
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+    fn lanes(&self) -> u64 {
+        self.lanes
+    }
+}
+
+impl<A: Clone> Resources for SimdIncrSum<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        vec![(UnitKind::Multiplier, 1), (UnitKind::Adder, self.lanes)]
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.total_latency()
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+    fn lanes(&self) -> u64 {
+        self.lanes
+    }
+}
+
+impl<A: Clone> Resources for SimdIncrOutP<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        vec![
+            (UnitKind::Multiplier, self.lanes + 1),
+            (UnitKind::Adder, self.lanes),
+        ]
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.total_latency()
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+    fn lanes(&self) -> u64 {
+        self.lanes
+    }
+}
+
+impl<A: Clone> Resources for SimdBinaryOp<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        vec![(self.op.unit_kind(), self.lanes)]
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+    fn lanes(&self) -> u64 {
+        self.lanes
+    }
+}
+
+impl<A: Clone> Resources for SimdBinary<A> {
+    fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        vec![(self.op.unit_kind(), self.lanes)]
+    }
+    fn pipeline_depth(&self) -> u64 {
+        self.latency
+    }
+    fn word_bits(&self) -> u64 {
+        word_bits::<A>()
+    }
+    fn lanes(&self) -> u64 {
+        self.lanes
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NodeEntry {
+    pub name: String,
+    pub units: Vec<(UnitKind, u64)>,
+    pub pipeline_reg_bits: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct FifoEntry {
+    pub name: String,
+    pub depth: u64,
+    pub word_bits: u64,
+}
+
+impl FifoEntry {
+    pub fn bits(&self) -> u64 {
+        self.depth * self.word_bits
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ResourceSummary {
+    pub nodes: Vec<NodeEntry>,
+    pub fifos: Vec<FifoEntry>,
+}
+
+impl ResourceSummary {
+    pub fn units(&self, kind: UnitKind) -> u64 {
+        self.nodes
+            .iter()
+            .flat_map(|n| n.units.iter())
+            .filter(|(k, _)| *k == kind)
+            .map(|(_, n)| *n)
+            .sum()
+    }
+
+    pub fn fifo_bits(&self) -> u64 {
+        self.fifos.iter().map(|f| f.bits()).sum()
+    }
+
+    pub fn pipeline_reg_bits(&self) -> u64 {
+        self.nodes.iter().map(|n| n.pipeline_reg_bits).sum()
+    }
+
+    pub fn storage_bits(&self) -> u64 {
+        self.fifo_bits() + self.pipeline_reg_bits()
+    }
+}
+
+impl fmt::Display for ResourceSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<16}", "node")?;
+        for kind in UNIT_KINDS.iter() {
+            write!(f, " {:>10}", format!("{:?}", kind))?;
+        }
+        writeln!(f, " {:>12}", "reg bits")?;
+        for node in self.nodes.iter() {
+            write!(f, "{:<16}", node.name)?;
+            for kind in UNIT_KINDS.iter() {
+                let count: u64 = node
+                    .units
+                    .iter()
+                    .filter(|(k, _)| k == kind)
+                    .map(|(_, n)| *n)
+                    .sum();
+                write!(f, " {:>10}", count)?;
+            }
+            writeln!(f, " {:>12}", node.pipeline_reg_bits)?;
+        }
+        writeln!(f, "{:<16} {:>10} {:>12}", "fifo", "depth", "bits")?;
+        for fifo in self.fifos.iter() {
+            writeln!(
+                f,
+                "{:<16} {:>10} {:>12}",
+                fifo.name,
+                fifo.depth,
+                fifo.bits()
+            )?;
+        }
+        write!(f, "{:<16}", "total")?;
+        for kind in UNIT_KINDS.iter() {
+            write!(f, " {:>10}", self.units(*kind))?;
+        }
+        write!(
+            f,
+            "\n{} FIFO bits + {} pipeline register bits = {} bits",
+            self.fifo_bits(),
+            self.pipeline_reg_bits(),
+            self.storage_bits()
+        )
+    }
+}
+
+// Filled in by the graph builders as they wire nodes and FIFOs.
+// Clones share the same table, like OpCounter.
+#[derive(Clone, Default)]
+pub struct ResourceTable {
+    summary: Arc<Mutex<ResourceSummary>>,
+}
+
+impl ResourceTable {
+    pub fn add_node<N: Resources>(&self, name: &str, node: &N) {
+        self.summary.lock().unwrap().nodes.push(NodeEntry {
+            name: name.to_string(),
+            units: node.functional_units(),
+            pipeline_reg_bits: node.pipeline_depth() * node.lanes() * node.word_bits(),
+        });
+    }
+
+    pub fn add_fifo<A>(&self, name: &str, depth: usize) {
+        self.summary.lock().unwrap().fifos.push(FifoEntry {
+            name: name.to_string(),
+            depth: depth as u64,
+            word_bits: word_bits::<A>(),
+        });
+    }
+
+    pub fn summary(&self) -> ResourceSummary {
+        self.summary.lock().unwrap().clone()
+    }
+}
+
+// Wires (without running) one head of the given architecture and collects its resources
+pub fn attn_resources(architecture: Architecture, seq_len: u64) -> ResourceSummary {
+    let resources = ResourceTable::default();
+    let chan_size = 2;
+
+    let mut ctx = ProgramBuilder::default();
+
+    let (q, kt, v) = add_generators(&mut ctx, seq_len, seq_len, chan_size);
+    let (out, expected) = match architecture {
+        Architecture::Streamed => {
+            let config = StreamAttnConfig {
+                seq_len,
+                resources: resources.clone(),
+                ..Default::default()
+            };
+            (
+                streamed_attn(&mut ctx, q, kt, v, &config, seq_len),
+                streamed_reference(0..seq_len, seq_len),
+            )
+        }
+        Architecture::Flash => {
+            let config = FlashAttnConfig {
+                seq_len,
+                resources: resources.clone(),
+                ..Default::default()
+            };
+            (
+                flash_attn(&mut ctx, q, kt, v, &config, seq_len),
+                flash_reference(0..seq_len, seq_len),
+            )
+        }
+    };
+    add_checker(&mut ctx, out, expected);
+
+    resources.summary()
+}
@@ -0,0 +1,304 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use dam::{context_tools::*, simulation::ProgramBuilder};
+
+use crate::graph::{
+    flashattn::{flash_attn, FlashAttnConfig},
+    multihead::{add_checker, add_generators, flash_reference, streamed_reference},
+    streamattn::{streamed_attn, StreamAttnConfig},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone)]
+pub struct OperandTraffic {
+    pub operand: String,
+    pub direction: Direction,
+    pub elements: u64,
+    pub bytes: u64,
+}
+
+// Off-chip traffic of one simulated graph, one entry per tapped operand stream
+#[derive(Debug, Clone, Default)]
+pub struct TrafficReport {
+    pub operands: Vec<OperandTraffic>,
+}
+
+impl TrafficReport {
+    pub fn bytes(&self, direction: Direction) -> u64 {
+        self.operands
+            .iter()
+            .filter(|o| o.direction == direction)
+            .map(|o| o.bytes)
+            .sum()
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.operands.iter().map(|o| o.bytes).sum()
+    }
+
+    pub fn operand(&self, operand: &str) -> Option<&OperandTraffic> {
+        self.operands.iter().find(|o| o.operand == operand)
+    }
+
+    // Ops per off-chip byte
+    pub fn arithmetic_intensity(&self, ops: u64) -> f64 {
+        (ops as f64) / (self.total_bytes() as f64)
+    }
+}
+
+impl fmt::Display for TrafficReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<8} {:<6} {:>12} {:>14}",
+            "operand", "dir", "elements", "bytes"
+        )?;
+        for o in self.operands.iter() {
+            writeln!(
+                f,
+                "{:<8} {:<6} {:>12} {:>14}",
+                o.operand,
+                format!("{:?}", o.direction),
+                o.elements,
+                o.bytes
+            )?;
+        }
+        write!(
+            f,
+            "read {} bytes, written {} bytes",
+            self.bytes(Direction::Read),
+            self.bytes(Direction::Write)
+        )
+    }
+}
+
+#[context_macro]
+pub struct TrafficTap<A: Clone> {
+    // Pass-through placed on a stream crossing the chip boundary, counts what goes through
+    pub in_stream: Receiver<A>,
+    pub out_stream: Sender<A>,
+    pub loop_bound: u64,
+    index: usize, // entry in the report
+    report: Arc<Mutex<TrafficReport>>,
+}
+
+impl<A: DAMType> TrafficTap<A>
+where
+    TrafficTap<A>: Context,
+{
+    pub fn new(
+        in_stream: Receiver<A>,
+        out_stream: Sender<A>,
+        loop_bound: u64,
+        operand: &str,
+        direction: Direction,
+        report: Arc<Mutex<TrafficReport>>,
+    ) -> Self {
+        let index = {
+            let mut report = report.lock().unwrap();
+            report.operands.push(OperandTraffic {
+                operand: operand.to_string(),
+                direction,
+                elements: 0,
+                bytes: 0,
+            });
+            report.operands.len() - 1
+        };
+        let tap = TrafficTap {
+            in_stream,
+            out_stream,
+            loop_bound,
+            index,
+            report,
+            context_info: Default::default(),
+        };
+        (tap.in_stream).attach_receiver(&tap);
+        (tap.out_stream).attach_sender(&tap);
+
+        tap
+    }
+}
+
+impl<A> Context for TrafficTap<A>
+where
+    A: DAMType,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        for _i in 0..self.loop_bound {
+            let in_deq = self.in_stream.dequeue(&self.time);
+            match in_deq {
+                Ok(in_elem) => {
+                    let curr_time = self.time.tick();
+                    self.out_stream
+                        .enqueue(&self.time, ChannelElement::new(curr_time, in_elem.data))
+                        .unwrap();
+                }
+                _ => {
+                    panic!("Reached unhandled case");
+                }
+            }
+        }
+
+        let mut report = self.report.lock().unwrap();
+        let entry = &mut report.operands[self.index];
+        entry.elements += self.loop_bound;
+        entry.bytes += self.loop_bound * (std::mem::size_of::<A>() as u64);
+    }
+}
+
+// Inserts a tap on 'stream' and returns the downstream end
+pub fn add_traffic_tap<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    stream: Receiver<f64>,
+    loop_bound: u64,
+    operand: &str,
+    direction: Direction,
+    report: &Arc<Mutex<TrafficReport>>,
+    chan_size: usize,
+) -> Receiver<f64> {
+    let (sender, receiver) = ctx.bounded::<f64>(chan_size);
+    ctx.add_child(TrafficTap::new(
+        stream,
+        sender,
+        loop_bound,
+        operand,
+        direction,
+        report.clone(),
+    ));
+
+    receiver
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Architecture {
+    Streamed,
+    Flash,
+}
+
+// Useful ops of one attention head with d = 1: a multiply-add per score for QK^T and for PV
+pub fn attention_ops(seq_len: u64) -> u64 {
+    4 * seq_len * seq_len
+}
+
+pub struct ArchTraffic {
+    pub architecture: Architecture,
+    pub seq_len: u64,
+    pub traffic: TrafficReport,
+    pub elapsed_cycles: u64,
+}
+
+impl ArchTraffic {
+    pub fn arithmetic_intensity(&self) -> f64 {
+        self.traffic
+            .arithmetic_intensity(attention_ops(self.seq_len))
+    }
+}
+
+impl fmt::Display for ArchTraffic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:?} attention (N = {}), {} cycles",
+            self.architecture, self.seq_len, self.elapsed_cycles
+        )?;
+        writeln!(f, "{}", self.traffic)?;
+        write!(
+            f,
+            "arithmetic intensity {:.3} ops/byte",
+            self.arithmetic_intensity()
+        )
+    }
+}
+
+// Builds one head of the given architecture on the synthetic test inputs,
+// Q, K and V are read from off-chip and the output is written back
+pub fn run_attn_traffic(architecture: Architecture, seq_len: u64) -> ArchTraffic {
+    let report: Arc<Mutex<TrafficReport>> = Default::default();
+    let chan_size = 2;
+
+    let mut ctx = ProgramBuilder::default();
+
+    let (q, kt, v) = add_generators(&mut ctx, seq_len, seq_len, chan_size);
+    let kv_len = seq_len * seq_len;
+    let q = add_traffic_tap(
+        &mut ctx,
+        q,
+        seq_len,
+        "Q",
+        Direction::Read,
+        &report,
+        chan_size,
+    );
+    let kt = add_traffic_tap(
+        &mut ctx,
+        kt,
+        kv_len,
+        "K",
+        Direction::Read,
+        &report,
+        chan_size,
+    );
+    let v = add_traffic_tap(
+        &mut ctx,
+        v,
+        kv_len,
+        "V",
+        Direction::Read,
+        &report,
+        chan_size,
+    );
+
+    let (out, expected) = match architecture {
+        Architecture::Streamed => {
+            let config = StreamAttnConfig {
+                seq_len,
+                ..Default::default()
+            };
+            (
+                streamed_attn(&mut ctx, q, kt, v, &config, seq_len),
+                streamed_reference(0..seq_len, seq_len),
+            )
+        }
+        Architecture::Flash => {
+            let config = FlashAttnConfig {
+                seq_len,
+                ..Default::default()
+            };
+            (
+                flash_attn(&mut ctx, q, kt, v, &config, seq_len),
+                flash_reference(0..seq_len, seq_len),
+            )
+        }
+    };
+    let out = add_traffic_tap(
+        &mut ctx,
+        out,
+        seq_len,
+        "O",
+        Direction::Write,
+        &report,
+        chan_size,
+    );
+    add_checker(&mut ctx, out, expected);
+
+    let initialized = ctx.initialize(Default::default()).unwrap();
+    let summary = initialized.run(Default::default());
+
+    let traffic = report.lock().unwrap().clone();
+    ArchTraffic {
+        architecture,
+        seq_len,
+        traffic,
+        elapsed_cycles: summary.elapsed_cycles().unwrap(),
+    }
+}
@@ -0,0 +1,153 @@
+use std::fmt;
+
+use dam::{context_tools::*, simulation::ProgramBuilder};
+
+use super::{
+    flashattn::{flash_attn_rows, FlashAttnConfig},
+    multihead::{synthetic_k, synthetic_q, synthetic_v},
+};
+use crate::node::kv_cache::{DecodeDriver, KVCache};
+
+// Decode-mode attention: one new query per step over a KV cache that grows by one entry per step
+pub struct DecodeConfig {
+    pub num_steps: u64,
+    pub cache_capacity: usize,
+    pub cache_latency: u64,
+    pub append_latency: u64,
+    pub token_latency: u64, // rest of the layer, between an attention output and the next token
+    pub attn: FlashAttnConfig,
+}
+
+impl Default for DecodeConfig {
+    fn default() -> Self {
+        DecodeConfig {
+            num_steps: 16,
+            cache_capacity: 4096,
+            cache_latency: 2,
+            append_latency: 1,
+            token_latency: 0,
+            attn: Default::default(),
+        }
+    }
+}
+
+// Wires the K and V caches in front of a flash attention pipeline.
+// q, k_new and v_new carry one element per step, step t attends over prompt_len + t + 1 keys.
+// Returns the receiver of the attention output (one element per step).
+pub fn decode_attn<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    q: Receiver<f64>,
+    k_new: Receiver<f64>,
+    v_new: Receiver<f64>,
+    k_prompt: Vec<f64>,
+    v_prompt: Vec<f64>,
+    config: &DecodeConfig,
+) -> Receiver<f64> {
+    assert!(k_prompt.len() == v_prompt.len());
+    let prompt_len = k_prompt.len() as u64;
+    let chan_size = config.attn.chan_size;
+
+    let (kt_sender, kt_receiver) = ctx.bounded::<f64>(chan_size);
+    let (v_sender, v_receiver) = ctx.bounded::<f64>(chan_size);
+    ctx.add_child(KVCache::new(
+        k_new,
+        kt_sender,
+        k_prompt,
+        config.cache_capacity,
+        config.cache_latency,
+        config.append_latency,
+        config.attn.init_inverval,
+        config.num_steps,
+    ));
+    ctx.add_child(KVCache::new(
+        v_new,
+        v_sender,
+        v_prompt,
+        config.cache_capacity,
+        config.cache_latency,
+        config.append_latency,
+        config.attn.init_inverval,
+        config.num_steps,
+    ));
+
+    let row_lengths = (0..config.num_steps).map(|t| prompt_len + t + 1).collect();
+    flash_attn_rows(ctx, q, kt_receiver, v_receiver, &config.attn, row_lengths)
+}
+
+pub struct DecodeReport {
+    pub prompt_len: u64,
+    pub token_latencies: Vec<u64>, // cycles from issuing a token's query to its attention output
+    pub outputs: Vec<f64>,
+    pub elapsed_cycles: u64,
+}
+
+impl DecodeReport {
+    pub fn mean_token_latency(&self) -> f64 {
+        (self.token_latencies.iter().sum::<u64>() as f64) / (self.token_latencies.len() as f64)
+    }
+}
+
+impl fmt::Display for DecodeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} decode steps after a {} token prompt: {:.1} cycles/token (first {}, last {}), {} cycles",
+            self.token_latencies.len(),
+            self.prompt_len,
+            self.mean_token_latency(),
+            self.token_latencies.first().unwrap_or(&0),
+            self.token_latencies.last().unwrap_or(&0),
+            self.elapsed_cycles
+        )
+    }
+}
+
+// Runs 'config.num_steps' decode steps after a prompt of 'prompt_len' tokens on the synthetic inputs
+pub fn run_decode_attn(prompt_len: u64, config: &DecodeConfig) -> DecodeReport {
+    let chan_size = config.attn.chan_size;
+    let k_prompt: Vec<f64> = (0..prompt_len).map(synthetic_k).collect();
+    let v_prompt: Vec<f64> = (0..prompt_len).map(synthetic_v).collect();
+    // Decode step t appends key prompt_len + t
+    let tokens = (0..config.num_steps)
+        .map(|t| {
+            (
+                synthetic_q(t),
+                synthetic_k(prompt_len + t),
+                synthetic_v(prompt_len + t),
+            )
+        })
+        .collect();
+
+    let mut ctx = ProgramBuilder::default();
+
+    let (q_sender, q_receiver) = ctx.bounded::<f64>(chan_size);
+    let (k_sender, k_receiver) = ctx.bounded::<f64>(chan_size);
+    let (v_sender, v_receiver) = ctx.bounded::<f64>(chan_size);
+    let out = decode_attn(
+        &mut ctx, q_receiver, k_receiver, v_receiver, k_prompt, v_prompt, config,
+    );
+    let driver = DecodeDriver::new(
+        tokens,
+        q_sender,
+        k_sender,
+        v_sender,
+        out,
+        config.token_latency,
+    );
+    let timings = driver.timings();
+    ctx.add_child(driver);
+
+    let initialized = ctx.initialize(Default::default()).unwrap();
+    let summary = initialized.run(Default::default());
+
+    let timings = timings.lock().unwrap();
+    DecodeReport {
+        prompt_len,
+        token_latencies: timings
+            .iter()
+            .map(|t| t.done_cycle - t.issue_cycle)
+            .collect(),
+        outputs: timings.iter().map(|t| t.output).collect(),
+        elapsed_cycles: summary.elapsed_cycles().unwrap(),
+    }
+}
@@ -0,0 +1,400 @@
+use dam::{context_tools::*, simulation::ProgramBuilder};
+
+use crate::{
+    analysis::{cost::OpCounter, resource::ResourceTable},
+    node::{
+        broadcast::Broadcast,
+        flashattn_binary_op::BinaryOp,
+        flashattn_lse::LogSumExp,
+        flashattn_running_op::{IncrMax, IncrOutP, IncrSum, Recurrence},
+        flashattn_tiled_op::{TiledIncrMax, TiledIncrOutP, TiledIncrSum},
+        score_scale::ScoreScale,
+        streamattn_binary::BinaryOpType,
+        streamattn_qkt::QKTExp,
+    },
+};
+
+// Latencies, IIs and FIFO depth of the single-head flash attention pipeline
+// QKTExp -> IncrMax -> (IncrSum, IncrOutP) -> Div
+pub struct FlashAttnConfig {
+    pub seq_len: u64, // keys per query row
+    pub qkt_latency: u64,
+    pub running_latency: u64,
+    pub rowsum_latency: u64,
+    pub outerp_latency: u64,
+    pub div_latency: u64,
+    pub lse_latency: u64,
+    pub muticycle_ii: u64, // II of the loop-carried IncrSum / IncrOutP updates
+    pub init_inverval: u64,
+    pub chan_size: usize,               // FIFO Depth
+    pub op_counter: OpCounter,          // shared by all nodes, see analysis::cost
+    pub resources: ResourceTable,       // filled in while wiring, see analysis::resource
+    pub score_scale: ScoreScale,        // applied to q*k in QKTExp
+    pub recurrence: Option<Recurrence>, // when set, replaces muticycle_ii in IncrSum / IncrOutP
+}
+
+impl Default for FlashAttnConfig {
+    fn default() -> Self {
+        FlashAttnConfig {
+            seq_len: 512,
+            qkt_latency: 11,
+            running_latency: 3,
+            rowsum_latency: 8,
+            outerp_latency: 12,
+            div_latency: 21,
+            lse_latency: 16,
+            muticycle_ii: 2,
+            init_inverval: 1,
+            chan_size: 2,
+            op_counter: Default::default(),
+            resources: Default::default(),
+            score_scale: Default::default(),
+            recurrence: None,
+        }
+    }
+}
+
+impl FlashAttnConfig {
+    // II of the loop-carried IncrSum / IncrOutP updates with 'num_accumulators' rows in flight
+    pub fn running_ii(&self, num_accumulators: u64) -> u64 {
+        match self.recurrence {
+            Some(recurrence) => self.init_inverval.max(recurrence.min_ii(num_accumulators)),
+            None => self.muticycle_ii,
+        }
+    }
+
+    // II the running ops issue at, they derive the rest from the recurrence
+    pub(crate) fn running_issue_ii(&self) -> u64 {
+        match self.recurrence {
+            Some(_) => self.init_inverval,
+            None => self.muticycle_ii,
+        }
+    }
+}
+
+// Wires one flash attention pipeline for 'num_rows' query rows of 'config.seq_len' keys each.
+// q carries one element per row, kt and v carry 'seq_len' elements per row.
+// Returns the receiver of the normalized output (one element per row).
+pub fn flash_attn<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    q: Receiver<f64>,
+    kt: Receiver<f64>,
+    v: Receiver<f64>,
+    config: &FlashAttnConfig,
+    num_rows: u64,
+) -> Receiver<f64> {
+    let row_lengths = vec![config.seq_len; num_rows as usize];
+    flash_attn_rows(ctx, q, kt, v, config, row_lengths)
+}
+
+// Same pipeline with row i attending over 'row_lengths[i]' keys, e.g. decode steps.
+// kt and v carry 'row_lengths[i]' elements for row i, every row needs at least one key.
+pub fn flash_attn_rows<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    q: Receiver<f64>,
+    kt: Receiver<f64>,
+    v: Receiver<f64>,
+    config: &FlashAttnConfig,
+    row_lengths: Vec<u64>,
+) -> Receiver<f64> {
+    flash_attn_impl(ctx, q, kt, v, config, row_lengths, false).0
+}
+
+// Same as 'flash_attn', also returns the per-row logsumexp m + ln(l) of the softmax
+pub fn flash_attn_lse<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    q: Receiver<f64>,
+    kt: Receiver<f64>,
+    v: Receiver<f64>,
+    config: &FlashAttnConfig,
+    num_rows: u64,
+) -> (Receiver<f64>, Receiver<f64>) {
+    let row_lengths = vec![config.seq_len; num_rows as usize];
+    let (out, lse) = flash_attn_impl(ctx, q, kt, v, config, row_lengths, true);
+    (out, lse.unwrap())
+}
+
+fn flash_attn_impl<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    q: Receiver<f64>,
+    kt: Receiver<f64>,
+    v: Receiver<f64>,
+    config: &FlashAttnConfig,
+    row_lengths: Vec<u64>,
+    with_lse: bool,
+) -> (Receiver<f64>, Option<Receiver<f64>>) {
+    // A row without keys never produces its output, the following rows would desync
+    assert!(row_lengths.iter().all(|&l| l > 0));
+    let chan_size = config.chan_size;
+    let num_rows = row_lengths.len() as u64;
+    let resources = &config.resources;
+
+    // QKT & Exp block
+    let qkt_exp_depth = chan_size + (config.qkt_latency + config.score_scale.latency - 1) as usize;
+    let (qkt_exp_sender, qkt_exp_receiver) = ctx.bounded::<f64>(qkt_exp_depth);
+    resources.add_fifo::<f64>("qkt_exp", qkt_exp_depth);
+    let qkt_exp = QKTExp::new(
+        q,
+        kt,
+        vec![qkt_exp_sender],
+        config.qkt_latency,
+        config.init_inverval,
+        config.seq_len,
+    )
+    .with_row_lengths(row_lengths.clone())
+    .with_op_counter(config.op_counter.clone())
+    .with_score_scale(config.score_scale.clone());
+    resources.add_node("QKTExp", &qkt_exp);
+    ctx.add_child(qkt_exp);
+
+    // Incremental Max
+    let (delta_sender1, delta_receiver1) = ctx.bounded::<f64>(chan_size);
+    let (delta_sender2, delta_receiver2) = ctx.bounded::<f64>(chan_size);
+    let (curr_sender1, curr_receiver1) = ctx.bounded::<f64>(chan_size);
+    let (curr_sender2, curr_receiver2) = ctx.bounded::<f64>(chan_size);
+    for name in ["delta1", "delta2", "curr1", "curr2"] {
+        resources.add_fifo::<f64>(name, chan_size);
+    }
+    let (max_senders, max_receiver) = if with_lse {
+        let (max_sender, max_receiver) = ctx.bounded::<f64>(chan_size);
+        resources.add_fifo::<f64>("max", chan_size);
+        (vec![max_sender], Some(max_receiver))
+    } else {
+        (vec![], None)
+    };
+    let incr_max = IncrMax::new(
+        qkt_exp_receiver,
+        vec![delta_sender1, delta_sender2],
+        vec![curr_sender1, curr_sender2],
+        config.running_latency,
+        config.init_inverval,
+        config.seq_len,
+        num_rows,
+    )
+    .with_row_lengths(row_lengths.clone())
+    .with_max_out(max_senders)
+    .with_op_counter(config.op_counter.clone());
+    resources.add_node("IncrMax", &incr_max);
+    ctx.add_child(incr_max);
+
+    // Incremental Sum
+    let running_ii = config.running_ii(1);
+    let rowsum_depth = chan_size + (running_ii - 1 + config.rowsum_latency - 1) as usize;
+    let (rowsum_sender, rowsum_receiver) = ctx.bounded::<f64>(rowsum_depth);
+    resources.add_fifo::<f64>("rowsum", rowsum_depth);
+    let mut incr_sum = IncrSum::new(
+        delta_receiver1,
+        curr_receiver1,
+        rowsum_sender,
+        config.rowsum_latency,
+        config.running_issue_ii(),
+        config.seq_len,
+        num_rows,
+    )
+    .with_row_lengths(row_lengths.clone())
+    .with_op_counter(config.op_counter.clone());
+    if let Some(recurrence) = config.recurrence {
+        incr_sum = incr_sum.with_recurrence(recurrence);
+    }
+    resources.add_node("IncrSum", &incr_sum);
+    ctx.add_child(incr_sum);
+
+    // The row sum also feeds the LogSumExp
+    let (rowsum_receiver, lse_sum_receiver) = if with_lse {
+        let (div_sum_sender, div_sum_receiver) = ctx.bounded::<f64>(chan_size);
+        let (lse_sum_sender, lse_sum_receiver) = ctx.bounded::<f64>(chan_size);
+        resources.add_fifo::<f64>("div_sum", chan_size);
+        resources.add_fifo::<f64>("lse_sum", chan_size);
+        ctx.add_child(Broadcast::new(
+            rowsum_receiver,
+            vec![div_sum_sender, lse_sum_sender],
+            1,
+            config.init_inverval,
+            num_rows,
+        ));
+        (div_sum_receiver, Some(lse_sum_receiver))
+    } else {
+        (rowsum_receiver, None)
+    };
+
+    // Incremental outer product
+    let matmul_depth = chan_size + (running_ii - 1 + config.outerp_latency - 1) as usize;
+    let (matmul_sender, matmul_receiver) = ctx.bounded::<f64>(matmul_depth);
+    resources.add_fifo::<f64>("matmul", matmul_depth);
+    let mut incr_outer_p = IncrOutP::new(
+        delta_receiver2,
+        curr_receiver2,
+        v,
+        matmul_sender,
+        config.outerp_latency,
+        config.running_issue_ii(),
+        config.seq_len,
+        num_rows,
+    )
+    .with_row_lengths(row_lengths.clone())
+    .with_op_counter(config.op_counter.clone());
+    if let Some(recurrence) = config.recurrence {
+        incr_outer_p = incr_outer_p.with_recurrence(recurrence);
+    }
+    resources.add_node("IncrOutP", &incr_outer_p);
+    ctx.add_child(incr_outer_p);
+
+    // Div
+    let final_depth = chan_size + (config.div_latency - 1) as usize;
+    let (final_sender, final_receiver) = ctx.bounded::<f64>(final_depth);
+    resources.add_fifo::<f64>("final", final_depth);
+    let div = BinaryOp::new(
+        matmul_receiver,
+        rowsum_receiver,
+        final_sender,
+        config.div_latency,
+        config.init_inverval,
+        num_rows,
+        BinaryOpType::Div,
+    )
+    .with_op_counter(config.op_counter.clone());
+    resources.add_node("Div", &div);
+    ctx.add_child(div);
+
+    // LogSumExp
+    let lse_receiver = max_receiver.map(|max_receiver| {
+        let lse_depth = chan_size + (config.lse_latency - 1) as usize;
+        let (lse_sender, lse_receiver) = ctx.bounded::<f64>(lse_depth);
+        resources.add_fifo::<f64>("lse", lse_depth);
+        let lse = LogSumExp::new(
+            max_receiver,
+            lse_sum_receiver.unwrap(),
+            vec![lse_sender],
+            config.lse_latency,
+            config.init_inverval,
+            num_rows,
+        )
+        .with_op_counter(config.op_counter.clone());
+        resources.add_node("LogSumExp", &lse);
+        ctx.add_child(lse);
+        lse_receiver
+    });
+
+    (final_receiver, lse_receiver)
+}
+
+// Same pipeline as 'flash_attn' with the running ops replaced by their tiled variants.
+// 'config.running_ii(1)' becomes the II of the once-per-tile rescale,
+// elements issue every 'config.init_inverval'.
+pub fn tiled_flash_attn<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    q: Receiver<f64>,
+    kt: Receiver<f64>,
+    v: Receiver<f64>,
+    config: &FlashAttnConfig,
+    num_rows: u64,
+    tile_size: u64,
+) -> Receiver<f64> {
+    let chan_size = config.chan_size;
+    let resources = &config.resources;
+
+    // QKT & Exp block
+    let qkt_exp_depth = chan_size + (config.qkt_latency + config.score_scale.latency - 1) as usize;
+    let (qkt_exp_sender, qkt_exp_receiver) = ctx.bounded::<f64>(qkt_exp_depth);
+    resources.add_fifo::<f64>("qkt_exp", qkt_exp_depth);
+    let qkt_exp = QKTExp::new(
+        q,
+        kt,
+        vec![qkt_exp_sender],
+        config.qkt_latency,
+        config.init_inverval,
+        config.seq_len,
+    )
+    .with_outer_loop_bound(num_rows)
+    .with_op_counter(config.op_counter.clone())
+    .with_score_scale(config.score_scale.clone());
+    resources.add_node("QKTExp", &qkt_exp);
+    ctx.add_child(qkt_exp);
+
+    // Tiled Incremental Max
+    let (delta_sender1, delta_receiver1) = ctx.bounded::<f64>(chan_size);
+    let (delta_sender2, delta_receiver2) = ctx.bounded::<f64>(chan_size);
+    let (curr_sender1, curr_receiver1) = ctx.bounded::<f64>(chan_size);
+    let (curr_sender2, curr_receiver2) = ctx.bounded::<f64>(chan_size);
+    for name in ["delta1", "delta2", "curr1", "curr2"] {
+        resources.add_fifo::<f64>(name, chan_size);
+    }
+    let incr_max = TiledIncrMax::new(
+        qkt_exp_receiver,
+        vec![delta_sender1, delta_sender2],
+        vec![curr_sender1, curr_sender2],
+        config.running_latency,
+        config.init_inverval,
+        tile_size,
+        config.seq_len,
+        num_rows,
+    )
+    .with_op_counter(config.op_counter.clone());
+    resources.add_node("TiledIncrMax", &incr_max);
+    ctx.add_child(incr_max);
+
+    // Tiled Incremental Sum
+    let running_ii = config.running_ii(1);
+    let rowsum_depth = chan_size + (running_ii - 1 + config.rowsum_latency - 1) as usize;
+    let (rowsum_sender, rowsum_receiver) = ctx.bounded::<f64>(rowsum_depth);
+    resources.add_fifo::<f64>("rowsum", rowsum_depth);
+    let mut incr_sum = TiledIncrSum::new(
+        delta_receiver1,
+        curr_receiver1,
+        rowsum_sender,
+        config.rowsum_latency,
+        config.init_inverval,
+        config.running_issue_ii(),
+        tile_size,
+        config.seq_len,
+        num_rows,
+    )
+    .with_op_counter(config.op_counter.clone());
+    if let Some(recurrence) = config.recurrence {
+        incr_sum = incr_sum.with_recurrence(recurrence);
+    }
+    resources.add_node("TiledIncrSum", &incr_sum);
+    ctx.add_child(incr_sum);
+
+    // Tiled Incremental outer product
+    let matmul_depth = chan_size + (running_ii - 1 + config.outerp_latency - 1) as usize;
+    let (matmul_sender, matmul_receiver) = ctx.bounded::<f64>(matmul_depth);
+    resources.add_fifo::<f64>("matmul", matmul_depth);
+    let mut incr_outer_p = TiledIncrOutP::new(
+        delta_receiver2,
+        curr_receiver2,
+        v,
+        matmul_sender,
+        config.outerp_latency,
+        config.init_inverval,
+        config.running_issue_ii(),
+        tile_size,
+        config.seq_len,
+        num_rows,
+    )
+    .with_op_counter(config.op_counter.clone());
+    if let Some(recurrence) = config.recurrence {
+        incr_outer_p = incr_outer_p.with_recurrence(recurrence);
+    }
+    resources.add_node("TiledIncrOutP", &incr_outer_p);
+    ctx.add_child(incr_outer_p);
+
+    // Div
+    let final_depth = chan_size + (config.div_latency - 1) as usize;
+    let (final_sender, final_receiver) = ctx.bounded::<f64>(final_depth);
+    resources.add_fifo::<f64>("final", final_depth);
+    let div = BinaryOp::new(
+        matmul_receiver,
+        rowsum_receiver,
+        final_sender,
+        config.div_latency,
+        config.init_inverval,
+        num_rows,
+        BinaryOpType::Div,
+    )
+    .with_op_counter(config.op_counter.clone());
+    resources.add_node("Div", &div);
+    ctx.add_child(div);
+
+    final_receiver
+}
@@ -0,0 +1,258 @@
+use dam::{context_tools::*, simulation::ProgramBuilder};
+
+use crate::{
+    analysis::cost::OpCounter,
+    node::{
+        broadcast::Broadcast,
+        flashattn_backward_op::{ColumnAccum, RecomputeP},
+        flashattn_binary_op::BinaryOp,
+        score_scale::ScoreScale,
+        streamattn_binary::{Binary, BinaryOpType},
+        streamattn_matvec::MatVecProd,
+    },
+};
+
+// Latencies, II and FIFO depth of the flash attention backward pipeline
+pub struct FlashAttnBwdConfig {
+    pub seq_len: u64,
+    pub recompute_latency: u64, // q k, the two exps and the subtract
+    pub binary_latency: u64,    // element-wise mul / sub
+    pub accum_latency: u64,     // column accumulators
+    pub matvec_latency: u64,
+    pub init_inverval: u64,
+    pub chan_size: usize,        // FIFO Depth
+    pub op_counter: OpCounter,   // shared by all nodes, see analysis::cost
+    pub score_scale: ScoreScale, // must match the forward pass
+}
+
+impl Default for FlashAttnBwdConfig {
+    fn default() -> Self {
+        FlashAttnBwdConfig {
+            seq_len: 512,
+            recompute_latency: 11,
+            binary_latency: 8,
+            accum_latency: 4,
+            matvec_latency: 12,
+            init_inverval: 1,
+            chan_size: 2,
+            op_counter: Default::default(),
+            score_scale: Default::default(),
+        }
+    }
+}
+
+// FlashAttention backward pass for 'config.seq_len' rows, row-major like the forward pass.
+// The scores are the ones the forward running ops see, s = exp(scale(q k)) from QKTExp,
+// so lse is the one 'flash_attn_lse' emits with the same score scale:
+//   s_ij  = exp(scale(q_i k_j))        RecomputeP
+//   P_ij  = exp(s_ij - lse_i)          RecomputeP
+//   D_i   = rowsum(dO_i o O_i)         BinaryOp Mul (d = 1)
+//   dP_ij = dO_i v_j                   Binary Mul
+//   dS_ij = P_ij (dP_ij - D_i)         Binary Sub, BinaryOp Mul
+//   dX_ij = dS_ij s_ij scale'(q_i k_j) BinaryOp Mul, the gradient w.r.t. q_i k_j
+//   dQ_i  = sum_j dX_ij k_j            MatVecProd
+//   dK_j  = sum_i dX_ij q_i            ColumnAccum
+//   dV_j  = sum_i P_ij dO_i            ColumnAccum
+// q, o, d_o and lse carry one element per row, kt and v carry 'seq_len' elements per row.
+// dK and dV need all rows, so their 'seq_len' accumulators drain after the last row.
+// Returns the receivers of (dQ, dK, dV), 'seq_len' elements each.
+pub fn flash_attn_backward<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    q: Receiver<f64>,
+    kt: Receiver<f64>,
+    v: Receiver<f64>,
+    o: Receiver<f64>,
+    d_o: Receiver<f64>,
+    lse: Receiver<f64>,
+    config: &FlashAttnBwdConfig,
+) -> (Receiver<f64>, Receiver<f64>, Receiver<f64>) {
+    let chan_size = config.chan_size;
+    let seq_len = config.seq_len;
+    let kv_len = seq_len * seq_len;
+    // dS of an element trails the P it was computed from by this many cycles, dX by one more binary op
+    let recompute_latency = config.recompute_latency + config.score_scale.latency;
+    let ds_lag = (recompute_latency + 2 * config.binary_latency) as usize;
+    let dx_lag = ds_lag + config.binary_latency as usize;
+
+    // Fan out the operands used twice
+    let (q_recompute_sender, q_recompute_receiver) = ctx.bounded::<f64>(chan_size);
+    let (q_dk_sender, q_dk_receiver) = ctx.bounded::<f64>(chan_size);
+    ctx.add_child(Broadcast::new(
+        q,
+        vec![q_recompute_sender, q_dk_sender],
+        1,
+        config.init_inverval,
+        seq_len,
+    ));
+    let (kt_recompute_sender, kt_recompute_receiver) = ctx.bounded::<f64>(chan_size);
+    let (kt_dq_sender, kt_dq_receiver) = ctx.bounded::<f64>(chan_size + dx_lag);
+    ctx.add_child(Broadcast::new(
+        kt,
+        vec![kt_recompute_sender, kt_dq_sender],
+        1,
+        config.init_inverval,
+        kv_len,
+    ));
+    let (do_dp_sender, do_dp_receiver) = ctx.bounded::<f64>(chan_size);
+    let (do_d_sender, do_d_receiver) = ctx.bounded::<f64>(chan_size);
+    let (do_dv_sender, do_dv_receiver) = ctx.bounded::<f64>(chan_size);
+    ctx.add_child(Broadcast::new(
+        d_o,
+        vec![do_dp_sender, do_d_sender, do_dv_sender],
+        1,
+        config.init_inverval,
+        seq_len,
+    ));
+
+    // Recompute P
+    let (p_dv_sender, p_dv_receiver) = ctx.bounded::<f64>(chan_size + recompute_latency as usize);
+    let (p_ds_sender, p_ds_receiver) = ctx.bounded::<f64>(chan_size + ds_lag);
+    let (grad_sender, grad_receiver) = ctx.bounded::<f64>(chan_size + dx_lag);
+    ctx.add_child(
+        RecomputeP::new(
+            q_recompute_receiver,
+            kt_recompute_receiver,
+            lse,
+            vec![p_dv_sender, p_ds_sender],
+            config.recompute_latency,
+            config.init_inverval,
+            seq_len,
+            seq_len,
+        )
+        .with_score_scale(config.score_scale.clone())
+        .with_score_grad(vec![grad_sender])
+        .with_op_counter(config.op_counter.clone()),
+    );
+
+    // D = rowsum(dO o O)
+    let (d_sender, d_receiver) = ctx.bounded::<f64>(chan_size + config.binary_latency as usize);
+    ctx.add_child(
+        BinaryOp::new(
+            do_d_receiver,
+            o,
+            d_sender,
+            config.binary_latency,
+            config.init_inverval,
+            seq_len,
+            BinaryOpType::Mul,
+        )
+        .with_op_counter(config.op_counter.clone()),
+    );
+
+    // dP = dO V^T
+    let (dp_sender, dp_receiver) = ctx.bounded::<f64>(chan_size + config.binary_latency as usize);
+    ctx.add_child(
+        Binary::<f64>::new(
+            v,
+            do_dp_receiver,
+            dp_sender,
+            config.binary_latency,
+            config.init_inverval,
+            seq_len,
+            seq_len,
+            BinaryOpType::Mul,
+        )
+        .with_op_counter(config.op_counter.clone()),
+    );
+
+    // dS = P o (dP - D)
+    let (dp_d_sender, dp_d_receiver) =
+        ctx.bounded::<f64>(chan_size + config.binary_latency as usize);
+    ctx.add_child(
+        Binary::<f64>::new(
+            dp_receiver,
+            d_receiver,
+            dp_d_sender,
+            config.binary_latency,
+            config.init_inverval,
+            seq_len,
+            seq_len,
+            BinaryOpType::Sub,
+        )
+        .with_op_counter(config.op_counter.clone()),
+    );
+    let (ds_sender, ds_receiver) = ctx.bounded::<f64>(chan_size + config.binary_latency as usize);
+    ctx.add_child(
+        BinaryOp::new(
+            p_ds_receiver,
+            dp_d_receiver,
+            ds_sender,
+            config.binary_latency,
+            config.init_inverval,
+            kv_len,
+            BinaryOpType::Mul,
+        )
+        .with_op_counter(config.op_counter.clone()),
+    );
+
+    // dX = dS o s o scale'
+    let (dx_sender, dx_receiver) = ctx.bounded::<f64>(chan_size + config.binary_latency as usize);
+    ctx.add_child(
+        BinaryOp::new(
+            ds_receiver,
+            grad_receiver,
+            dx_sender,
+            config.binary_latency,
+            config.init_inverval,
+            kv_len,
+            BinaryOpType::Mul,
+        )
+        .with_op_counter(config.op_counter.clone()),
+    );
+    let (dx_dq_sender, dx_dq_receiver) = ctx.bounded::<f64>(chan_size);
+    let (dx_dk_sender, dx_dk_receiver) = ctx.bounded::<f64>(chan_size);
+    ctx.add_child(Broadcast::new(
+        dx_receiver,
+        vec![dx_dq_sender, dx_dk_sender],
+        1,
+        config.init_inverval,
+        kv_len,
+    ));
+
+    // dQ = dX K
+    let (dq_sender, dq_receiver) = ctx.bounded::<f64>(chan_size);
+    ctx.add_child(
+        MatVecProd::new(
+            dx_dq_receiver,
+            kt_dq_receiver,
+            dq_sender,
+            config.matvec_latency,
+            config.init_inverval,
+            seq_len,
+            seq_len,
+        )
+        .with_op_counter(config.op_counter.clone()),
+    );
+
+    // dK = dX^T Q
+    let (dk_sender, dk_receiver) = ctx.bounded::<f64>(chan_size);
+    ctx.add_child(
+        ColumnAccum::new(
+            dx_dk_receiver,
+            q_dk_receiver,
+            dk_sender,
+            config.accum_latency,
+            config.init_inverval,
+            seq_len,
+            seq_len,
+        )
+        .with_op_counter(config.op_counter.clone()),
+    );
+
+    // dV = P^T dO
+    let (dv_sender, dv_receiver) = ctx.bounded::<f64>(chan_size);
+    ctx.add_child(
+        ColumnAccum::new(
+            p_dv_receiver,
+            do_dv_receiver,
+            dv_sender,
+            config.accum_latency,
+            config.init_inverval,
+            seq_len,
+            seq_len,
+        )
+        .with_op_counter(config.op_counter.clone()),
+    );
+
+    (dq_receiver, dk_receiver, dv_receiver)
+}
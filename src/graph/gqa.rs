@@ -0,0 +1,162 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use dam::{context_tools::*, simulation::ProgramBuilder};
+
+use super::{
+    flashattn::{flash_attn, FlashAttnConfig},
+    multihead::{add_checker, add_kv_generators, add_q_generator, flash_reference},
+};
+use crate::{
+    analysis::traffic::{add_traffic_tap, Direction, TrafficReport},
+    node::broadcast::Broadcast,
+};
+
+// Grouped-query attention: query head h reads the K/V stream of group h / (num_q_heads / num_kv_heads).
+// Each K/V stream is generated once and fanned out to the QKTExp / IncrOutP of every head in its group.
+// Multi-query attention is the special case of a single K/V group.
+// Returns one output receiver per query head.
+pub fn grouped_query_attn<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    q_heads: Vec<Receiver<f64>>,
+    kv_heads: Vec<(Receiver<f64>, Receiver<f64>)>,
+    config: &FlashAttnConfig,
+) -> Vec<Receiver<f64>> {
+    assert!(!kv_heads.is_empty());
+    assert!(q_heads.len().is_multiple_of(kv_heads.len()));
+    let group_size = q_heads.len() / kv_heads.len();
+    let kv_len = config.seq_len * config.seq_len;
+
+    let mut kv_per_q_head = vec![];
+    for (kt, v) in kv_heads {
+        let (kt_senders, kt_receivers): (Vec<_>, Vec<_>) = (0..group_size)
+            .map(|_| ctx.bounded::<f64>(config.chan_size))
+            .unzip();
+        let (v_senders, v_receivers): (Vec<_>, Vec<_>) = (0..group_size)
+            .map(|_| ctx.bounded::<f64>(config.chan_size))
+            .unzip();
+        ctx.add_child(Broadcast::new(
+            kt,
+            kt_senders,
+            1,
+            config.init_inverval,
+            kv_len,
+        ));
+        ctx.add_child(Broadcast::new(
+            v,
+            v_senders,
+            1,
+            config.init_inverval,
+            kv_len,
+        ));
+        kv_per_q_head.extend(kt_receivers.into_iter().zip(v_receivers));
+    }
+
+    q_heads
+        .into_iter()
+        .zip(kv_per_q_head)
+        .map(|(q, (kt, v))| flash_attn(ctx, q, kt, v, config, config.seq_len))
+        .collect()
+}
+
+pub struct GqaReport {
+    pub num_q_heads: u64,
+    pub num_kv_heads: u64,
+    pub seq_len: u64,
+    pub kv_elements_read: u64, // K and V elements counted by the traffic taps on the generators
+    pub elapsed_cycles: u64,
+}
+
+impl GqaReport {
+    // K and V elements multi-head attention would stream for the same number of query heads
+    pub fn mha_kv_elements(&self) -> u64 {
+        2 * self.num_q_heads * self.seq_len * self.seq_len
+    }
+
+    pub fn kv_bandwidth_saving(&self) -> f64 {
+        1_f64 - (self.kv_elements_read as f64) / (self.mha_kv_elements() as f64)
+    }
+}
+
+impl fmt::Display for GqaReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} query heads / {} kv heads (N = {}): {} K/V elements read ({} for MHA, {:.1}% saved), {} cycles",
+            self.num_q_heads,
+            self.num_kv_heads,
+            self.seq_len,
+            self.kv_elements_read,
+            self.mha_kv_elements(),
+            100_f64 * self.kv_bandwidth_saving(),
+            self.elapsed_cycles
+        )
+    }
+}
+
+// Builds and runs grouped-query attention on the synthetic test inputs
+pub fn run_grouped_query_attn(
+    num_q_heads: u64,
+    num_kv_heads: u64,
+    config: &FlashAttnConfig,
+) -> GqaReport {
+    assert!(num_kv_heads > 0);
+    let seq_len = config.seq_len;
+    let kv_len = seq_len * seq_len;
+    let report: Arc<Mutex<TrafficReport>> = Default::default();
+
+    let mut ctx = ProgramBuilder::default();
+
+    let q_heads = (0..num_q_heads)
+        .map(|_| add_q_generator(&mut ctx, seq_len, seq_len, config.chan_size))
+        .collect();
+    // K and V are tapped before the broadcast, so each group is counted once
+    let kv_heads = (0..num_kv_heads)
+        .map(|g| {
+            let (kt, v) = add_kv_generators(&mut ctx, seq_len, seq_len, config.chan_size);
+            let kt = add_traffic_tap(
+                &mut ctx,
+                kt,
+                kv_len,
+                &format!("K{}", g),
+                Direction::Read,
+                &report,
+                config.chan_size,
+            );
+            let v = add_traffic_tap(
+                &mut ctx,
+                v,
+                kv_len,
+                &format!("V{}", g),
+                Direction::Read,
+                &report,
+                config.chan_size,
+            );
+            (kt, v)
+        })
+        .collect();
+    let outputs = grouped_query_attn(&mut ctx, q_heads, kv_heads, config);
+    for out in outputs {
+        add_checker(&mut ctx, out, flash_reference(0..seq_len, seq_len));
+    }
+
+    let initialized = ctx.initialize(Default::default()).unwrap();
+    let summary = initialized.run(Default::default());
+    let kv_elements_read = report
+        .lock()
+        .unwrap()
+        .operands
+        .iter()
+        .map(|o| o.elements)
+        .sum();
+
+    GqaReport {
+        num_q_heads,
+        num_kv_heads,
+        seq_len,
+        kv_elements_read,
+        elapsed_cycles: summary.elapsed_cycles().unwrap(),
+    }
+}
@@ -0,0 +1,139 @@
+use std::fmt;
+
+use dam::{context_tools::*, simulation::ProgramBuilder, utility_contexts::GeneratorContext};
+
+use super::{
+    flashattn::{flash_attn_rows, FlashAttnConfig},
+    multihead::{row_reference, synthetic_k, synthetic_q, synthetic_v},
+    streamattn::{streamed_attn_rows, StreamAttnConfig},
+};
+use crate::{
+    analysis::{
+        error_report::{ErrorAnalysisContext, ErrorReport},
+        resource::ResourceSummary,
+        traffic::Architecture,
+    },
+    mask::{AttnMask, Dense},
+    node::broadcast::Broadcast,
+};
+
+// K and V carry only the keys the mask keeps, row after row
+pub(crate) fn add_masked_generators<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    mask: &dyn AttnMask,
+    chan_size: usize,
+) -> (Receiver<f64>, Receiver<f64>, Receiver<f64>) {
+    let (q_sender, q_receiver) = ctx.bounded::<f64>(chan_size);
+    let (kt_sender, kt_receiver) = ctx.bounded::<f64>(chan_size);
+    let (v_sender, v_receiver) = ctx.bounded::<f64>(chan_size);
+    let num_rows = mask.num_rows();
+    let kt_keys = mask.key_stream();
+    let v_keys = kt_keys.clone();
+    let q_iter = move || (0..num_rows).map(synthetic_q);
+    let kt_iter = move || kt_keys.into_iter().map(synthetic_k);
+    let v_iter = move || v_keys.into_iter().map(synthetic_v);
+    ctx.add_child(GeneratorContext::new(q_iter, q_sender)); // Q : [1,D] shaped vectors
+    ctx.add_child(GeneratorContext::new(kt_iter, kt_sender)); // KT: [D,1] shaped vectors
+    ctx.add_child(GeneratorContext::new(v_iter, v_sender)); // V: [D,1] shaped vectors
+
+    (q_receiver, kt_receiver, v_receiver)
+}
+
+// Attention over the unmasked keys of every row, computed in software.
+// The flash running ops weight key j by exp(s_j), the streamed pipeline by s_j = exp(q k).
+pub(crate) fn masked_reference(architecture: Architecture, mask: &dyn AttnMask) -> Vec<f64> {
+    let weight = match architecture {
+        Architecture::Streamed => |qk: f64| qk.exp(),
+        Architecture::Flash => |qk: f64| qk.exp().exp(),
+    };
+    (0..mask.num_rows())
+        .map(|row| row_reference(row, mask.key_indices(row).into_iter(), weight))
+        .collect()
+}
+
+// Dense attention on the synthetic inputs, the reference the masks are compared against
+pub fn dense_reference(architecture: Architecture, seq_len: u64) -> Vec<f64> {
+    masked_reference(architecture, &Dense::new(seq_len))
+}
+
+pub struct MaskedAttnReport {
+    pub architecture: Architecture,
+    pub num_rows: u64,
+    pub num_keys: u64, // scores computed over all rows
+    pub elapsed_cycles: u64,
+    pub resources: ResourceSummary,
+    pub error: ErrorReport,      // versus dense attention
+    pub mask_error: ErrorReport, // versus attention over the kept keys
+}
+
+impl fmt::Display for MaskedAttnReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} masked attention: {} rows, {} scores, {} cycles, {} FIFO bits, max abs err {:e}",
+            self.architecture,
+            self.num_rows,
+            self.num_keys,
+            self.elapsed_cycles,
+            self.resources.fifo_bits(),
+            self.error.max_abs_err
+        )
+    }
+}
+
+// Builds and runs one attention head streaming only the unmasked keys, and compares it to dense attention
+pub fn run_masked_attn(architecture: Architecture, mask: &dyn AttnMask) -> MaskedAttnReport {
+    let num_rows = mask.num_rows();
+    let row_lengths = mask.row_lengths();
+
+    let mut ctx = ProgramBuilder::default();
+
+    let (out, resources) = match architecture {
+        Architecture::Streamed => {
+            let config = StreamAttnConfig {
+                seq_len: num_rows,
+                ..Default::default()
+            };
+            let (q, kt, v) = add_masked_generators(&mut ctx, mask, config.chan_size);
+            let out = streamed_attn_rows(&mut ctx, q, kt, v, &config, row_lengths);
+            (out, config.resources)
+        }
+        Architecture::Flash => {
+            let config = FlashAttnConfig {
+                seq_len: num_rows,
+                ..Default::default()
+            };
+            let (q, kt, v) = add_masked_generators(&mut ctx, mask, config.chan_size);
+            let out = flash_attn_rows(&mut ctx, q, kt, v, &config, row_lengths);
+            (out, config.resources)
+        }
+    };
+    // The output is compared to dense attention and to attention over the kept keys
+    let (out_senders, out_receivers): (Vec<_>, Vec<_>) =
+        (0..2).map(|_| ctx.bounded::<f64>(2)).unzip();
+    ctx.add_child(Broadcast::new(out, out_senders, 1, 1, num_rows));
+    let mut out_receivers = out_receivers.into_iter();
+    let reference = dense_reference(architecture, num_rows);
+    let analysis = ErrorAnalysisContext::new(|| reference, out_receivers.next().unwrap());
+    let error = analysis.report();
+    ctx.add_child(analysis);
+    let reference = masked_reference(architecture, mask);
+    let analysis = ErrorAnalysisContext::new(|| reference, out_receivers.next().unwrap());
+    let mask_error = analysis.report();
+    ctx.add_child(analysis);
+
+    let initialized = ctx.initialize(Default::default()).unwrap();
+    let summary = initialized.run(Default::default());
+    let error = error.lock().unwrap().clone();
+    let mask_error = mask_error.lock().unwrap().clone();
+
+    MaskedAttnReport {
+        architecture,
+        num_rows,
+        num_keys: mask.num_keys(),
+        elapsed_cycles: summary.elapsed_cycles().unwrap(),
+        resources: resources.summary(),
+        error,
+        mask_error,
+    }
+}
@@ -0,0 +1,11 @@
+pub mod decode;
+pub mod flashattn;
+pub mod flashattn_backward;
+pub mod gqa;
+pub mod masked;
+pub mod multihead;
+pub mod normalizer;
+pub mod row_tiled;
+pub mod simd;
+pub mod split_k;
+pub mod streamattn;
@@ -0,0 +1,255 @@
+use std::fmt;
+
+use dam::{
+    context_tools::*,
+    simulation::ProgramBuilder,
+    utility_contexts::{ApproxCheckerContext, GeneratorContext},
+};
+
+use super::flashattn::{flash_attn, FlashAttnConfig};
+use crate::node::score_scale::ScoreScale;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HeadMapping {
+    Spatial,  // one pipeline per (batch, head), all running concurrently
+    Temporal, // one pipeline time-multiplexed over all (batch, head) pairs
+}
+
+// Spatial mapping: replicates the per-head pipeline once per (q, kt, v) triple
+pub fn spatial_multi_head_attn<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    inputs: Vec<(Receiver<f64>, Receiver<f64>, Receiver<f64>)>,
+    config: &FlashAttnConfig,
+) -> Vec<Receiver<f64>> {
+    inputs
+        .into_iter()
+        .map(|(q, kt, v)| flash_attn(ctx, q, kt, v, config, config.seq_len))
+        .collect()
+}
+
+// Temporal mapping: a single pipeline with the loop nest
+// for b in 0..batch_size { for h in 0..num_heads { for i in 0..seq_len { for j in 0..seq_len } } }
+// q, kt and v carry the rows of all heads back to back in that order.
+pub fn temporal_multi_head_attn<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    q: Receiver<f64>,
+    kt: Receiver<f64>,
+    v: Receiver<f64>,
+    num_heads: u64,
+    batch_size: u64,
+    config: &FlashAttnConfig,
+) -> Receiver<f64> {
+    flash_attn(
+        ctx,
+        q,
+        kt,
+        v,
+        config,
+        batch_size * num_heads * config.seq_len,
+    )
+}
+
+pub struct MultiHeadReport {
+    pub mapping: HeadMapping,
+    pub num_heads: u64,
+    pub batch_size: u64,
+    pub seq_len: u64,
+    pub num_pipelines: u64,
+    pub elapsed_cycles: u64,
+}
+
+impl MultiHeadReport {
+    pub fn cycles_per_head(&self) -> f64 {
+        (self.elapsed_cycles as f64) / ((self.num_heads * self.batch_size) as f64)
+    }
+}
+
+impl fmt::Display for MultiHeadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} mapping: {} heads x {} batches (N = {}) on {} pipeline(s): {} cycles ({:.1} cycles/head)",
+            self.mapping,
+            self.num_heads,
+            self.batch_size,
+            self.seq_len,
+            self.num_pipelines,
+            self.elapsed_cycles,
+            self.cycles_per_head()
+        )
+    }
+}
+
+// Synthetic inputs used throughout the tests: Q of query row 'row', K and V of key 'key'.
+// K and V depend on the key, so the output depends on every softmax weight.
+pub(crate) fn synthetic_q(row: u64) -> f64 {
+    (row as f64) * 0.01_f64
+}
+
+pub(crate) fn synthetic_k(key: u64) -> f64 {
+    0.1_f64 + 0.01_f64 * ((key % 7) as f64)
+}
+
+pub(crate) fn synthetic_v(key: u64) -> f64 {
+    1_f64 + 0.1_f64 * ((key % 13) as f64)
+}
+
+// Softmax-weighted sum of the synthetic V over 'keys' for query row 'row',
+// 'weight' maps q k to the weight of a key
+pub(crate) fn row_reference(
+    row: u64,
+    keys: impl Iterator<Item = u64> + Clone,
+    weight: impl Fn(f64) -> f64,
+) -> f64 {
+    let q = synthetic_q(row);
+    let weights: Vec<f64> = keys
+        .clone()
+        .map(|key| weight(q * synthetic_k(key)))
+        .collect();
+    let out: f64 = weights
+        .iter()
+        .zip(keys)
+        .map(|(w, key)| w * synthetic_v(key))
+        .sum();
+    out / weights.iter().sum::<f64>()
+}
+
+// Rows wrap around 'seq_len' like the generators, each row sees all 'seq_len' keys
+fn weighted_reference(
+    rows: impl IntoIterator<Item = u64>,
+    seq_len: u64,
+    weight: impl Fn(f64) -> f64 + Copy,
+) -> Vec<f64> {
+    rows.into_iter()
+        .map(|row| row_reference(row % seq_len, 0..seq_len, weight))
+        .collect()
+}
+
+// Flash attention output over 'seq_len' synthetic keys per row. The running ops take the
+// QKTExp output s = exp(q k) as their scores, so key j is weighted by exp(s_j).
+pub(crate) fn flash_reference(rows: impl IntoIterator<Item = u64>, seq_len: u64) -> Vec<f64> {
+    scaled_flash_reference(rows, seq_len, &ScoreScale::default())
+}
+
+// Same with QKTExp applying 'score_scale' to q k before the exponential
+pub(crate) fn scaled_flash_reference(
+    rows: impl IntoIterator<Item = u64>,
+    seq_len: u64,
+    score_scale: &ScoreScale,
+) -> Vec<f64> {
+    weighted_reference(rows, seq_len, |qk| score_scale.eval(qk).exp().exp())
+}
+
+// Streamed attention output, key j is weighted by s_j = exp(q k) directly
+pub(crate) fn streamed_reference(rows: impl IntoIterator<Item = u64>, seq_len: u64) -> Vec<f64> {
+    scaled_streamed_reference(rows, seq_len, &ScoreScale::default())
+}
+
+pub(crate) fn scaled_streamed_reference(
+    rows: impl IntoIterator<Item = u64>,
+    seq_len: u64,
+    score_scale: &ScoreScale,
+) -> Vec<f64> {
+    weighted_reference(rows, seq_len, |qk| score_scale.eval(qk).exp())
+}
+
+// Generators for 'num_rows' query rows of the synthetic inputs
+pub(crate) fn add_q_generator<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    num_rows: u64,
+    seq_len: u64,
+    chan_size: usize,
+) -> Receiver<f64> {
+    let (q_sender, q_receiver) = ctx.bounded::<f64>(chan_size);
+    let q_iter = move || (0..num_rows).map(move |i| synthetic_q(i % seq_len));
+    ctx.add_child(GeneratorContext::new(q_iter, q_sender)); // Q : [1,D] shaped vectors
+
+    q_receiver
+}
+
+// K and V are re-streamed once per query row
+pub(crate) fn add_kv_generators<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    num_rows: u64,
+    seq_len: u64,
+    chan_size: usize,
+) -> (Receiver<f64>, Receiver<f64>) {
+    let (kt_sender, kt_receiver) = ctx.bounded::<f64>(chan_size);
+    let (v_sender, v_receiver) = ctx.bounded::<f64>(chan_size);
+    let kt_iter = move || (0..(num_rows * seq_len)).map(move |i| synthetic_k(i % seq_len));
+    let v_iter = move || (0..(num_rows * seq_len)).map(move |i| synthetic_v(i % seq_len));
+    ctx.add_child(GeneratorContext::new(kt_iter, kt_sender)); // KT: [D,1] shaped vectors
+    ctx.add_child(GeneratorContext::new(v_iter, v_sender)); // V: [D,1] shaped vectors
+
+    (kt_receiver, v_receiver)
+}
+
+pub(crate) fn add_generators<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    num_rows: u64,
+    seq_len: u64,
+    chan_size: usize,
+) -> (Receiver<f64>, Receiver<f64>, Receiver<f64>) {
+    let q_receiver = add_q_generator(ctx, num_rows, seq_len, chan_size);
+    let (kt_receiver, v_receiver) = add_kv_generators(ctx, num_rows, seq_len, chan_size);
+
+    (q_receiver, kt_receiver, v_receiver)
+}
+
+// Checks 'out' against the expected rows, e.g. from 'flash_reference'
+pub(crate) fn add_checker<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    out: Receiver<f64>,
+    expected: Vec<f64>,
+) {
+    ctx.add_child(ApproxCheckerContext::new(
+        move || expected.into_iter(),
+        out,
+        |a, b| (a - b).abs() < 0.0001,
+    ));
+}
+
+// Builds and runs 'num_heads' x 'batch_size' attention heads with the given mapping
+pub fn run_multi_head_attn(
+    mapping: HeadMapping,
+    num_heads: u64,
+    batch_size: u64,
+    config: &FlashAttnConfig,
+) -> MultiHeadReport {
+    let seq_len = config.seq_len;
+    let num_instances = num_heads * batch_size;
+
+    let mut ctx = ProgramBuilder::default();
+
+    let num_pipelines = match mapping {
+        HeadMapping::Spatial => {
+            let inputs = (0..num_instances)
+                .map(|_| add_generators(&mut ctx, seq_len, seq_len, config.chan_size))
+                .collect();
+            let outputs = spatial_multi_head_attn(&mut ctx, inputs, config);
+            for out in outputs {
+                add_checker(&mut ctx, out, flash_reference(0..seq_len, seq_len));
+            }
+            num_instances
+        }
+        HeadMapping::Temporal => {
+            let num_rows = num_instances * seq_len;
+            let (q, kt, v) = add_generators(&mut ctx, num_rows, seq_len, config.chan_size);
+            let out = temporal_multi_head_attn(&mut ctx, q, kt, v, num_heads, batch_size, config);
+            add_checker(&mut ctx, out, flash_reference(0..num_rows, seq_len));
+            1
+        }
+    };
+
+    let initialized = ctx.initialize(Default::default()).unwrap();
+    let summary = initialized.run(Default::default());
+
+    MultiHeadReport {
+        mapping,
+        num_heads,
+        batch_size,
+        seq_len,
+        num_pipelines,
+        elapsed_cycles: summary.elapsed_cycles().unwrap(),
+    }
+}
@@ -0,0 +1,268 @@
+use std::fmt;
+
+use dam::{context_tools::*, simulation::ProgramBuilder, utility_contexts::GeneratorContext};
+
+use super::{
+    masked::add_masked_generators,
+    multihead::{scaled_streamed_reference, synthetic_k, synthetic_q, synthetic_v},
+    streamattn::{streamed_attn, StreamAttnConfig},
+};
+use crate::{
+    analysis::{
+        error_report::{ErrorAnalysisContext, ErrorReport},
+        resource::ResourceSummary,
+    },
+    mask::Dense,
+    node::{
+        normalizer::{linear_features, LinearAttn, QKTAct, ScoreActivation},
+        score_scale::ScoreScale,
+        streamattn_matvec::MatVecProd,
+    },
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Normalizer {
+    Softmax,     // the streamed QKTExp -> Sum -> Div -> MatVecProd pipeline
+    Sigmoid,     // sigmoid(s - ln(N)), no row sum
+    ReluSquared, // relu(s)^2 / N, no row sum
+    Linear,      // causal linear attention with a running KV state
+}
+
+pub const NORMALIZERS: [Normalizer; 4] = [
+    Normalizer::Softmax,
+    Normalizer::Sigmoid,
+    Normalizer::ReluSquared,
+    Normalizer::Linear,
+];
+
+impl Normalizer {
+    pub fn activation(&self, seq_len: u64) -> Option<ScoreActivation> {
+        match self {
+            Normalizer::Sigmoid => Some(ScoreActivation::Sigmoid {
+                bias: -(seq_len as f64).ln(),
+            }),
+            Normalizer::ReluSquared => Some(ScoreActivation::ReluSquared {
+                scale: 1_f64 / (seq_len as f64),
+            }),
+            _ => None,
+        }
+    }
+}
+
+// Attention with an elementwise activation instead of softmax: QKTAct -> MatVecProd.
+// Without a row sum to wait for, the scores need no long FIFO.
+pub fn activation_attn<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    q: Receiver<f64>,
+    kt: Receiver<f64>,
+    v: Receiver<f64>,
+    config: &StreamAttnConfig,
+    activation: ScoreActivation,
+    num_rows: u64,
+) -> Receiver<f64> {
+    let chan_size = config.chan_size;
+    let resources = &config.resources;
+
+    // QKT & activation block
+    let qkt_act_depth = chan_size + ((config.qkt_latency + config.score_scale.latency) as usize);
+    let (qkt_act_sender, qkt_act_receiver) = ctx.bounded::<f64>(qkt_act_depth);
+    resources.add_fifo::<f64>("qkt_act", qkt_act_depth);
+    let qkt_act = QKTAct::new(
+        q,
+        kt,
+        vec![qkt_act_sender],
+        activation,
+        config.qkt_latency,
+        config.init_inverval,
+        config.seq_len,
+    )
+    .with_outer_loop_bound(num_rows)
+    .with_op_counter(config.op_counter.clone())
+    .with_score_scale(config.score_scale.clone());
+    resources.add_node("QKTAct", &qkt_act);
+    ctx.add_child(qkt_act);
+
+    // Multiply with V
+    let (out_sender, out_receiver) = ctx.bounded::<f64>(chan_size);
+    resources.add_fifo::<f64>("out", chan_size);
+    let matvec = MatVecProd::new(
+        qkt_act_receiver,
+        v,
+        out_sender,
+        config.matvec_latency,
+        config.init_inverval,
+        config.seq_len,
+        num_rows,
+    )
+    .with_op_counter(config.op_counter.clone());
+    resources.add_node("MatVecProd", &matvec);
+    ctx.add_child(matvec);
+
+    out_receiver
+}
+
+// Causal linear attention over 'num_tokens' tokens, q, k and v carry one element per token.
+// Unlike the other normalizers token i only attends to tokens 0..=i.
+pub fn linear_attn<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    q: Receiver<f64>,
+    k: Receiver<f64>,
+    v: Receiver<f64>,
+    config: &StreamAttnConfig,
+    num_tokens: u64,
+) -> Receiver<f64> {
+    let resources = &config.resources;
+    let latency = config.matvec_latency + config.binary_latency;
+
+    let out_depth = config.chan_size + ((latency + config.score_scale.latency) as usize);
+    let (out_sender, out_receiver) = ctx.bounded::<f64>(out_depth);
+    resources.add_fifo::<f64>("out", out_depth);
+    let linear = LinearAttn::new(
+        q,
+        k,
+        v,
+        out_sender,
+        latency,
+        config.init_inverval,
+        num_tokens,
+    )
+    .with_op_counter(config.op_counter.clone())
+    .with_score_scale(config.score_scale.clone());
+    resources.add_node("LinearAttn", &linear);
+    ctx.add_child(linear);
+
+    out_receiver
+}
+
+// Software reference on the synthetic inputs of masked::add_masked_generators
+fn normalizer_reference(
+    normalizer: Normalizer,
+    seq_len: u64,
+    score_scale: &ScoreScale,
+) -> Vec<f64> {
+    match (normalizer, normalizer.activation(seq_len)) {
+        (Normalizer::Softmax, _) => scaled_streamed_reference(0..seq_len, seq_len, score_scale),
+        (Normalizer::Linear, _) => (0..seq_len)
+            .map(|i| {
+                let phi_q = linear_features(score_scale.eval(synthetic_q(i)));
+                let (mut num, mut den) = (0_f64, 0_f64);
+                for (f, phi) in phi_q.iter().enumerate() {
+                    let state: f64 = (0..=i)
+                        .map(|j| linear_features(synthetic_k(j))[f] * synthetic_v(j))
+                        .sum();
+                    let norm: f64 = (0..=i).map(|j| linear_features(synthetic_k(j))[f]).sum();
+                    num += phi * state;
+                    den += phi * norm;
+                }
+                num / den
+            })
+            .collect(),
+        (_, Some(activation)) => (0..seq_len)
+            .map(|i| {
+                (0..seq_len)
+                    .map(|j| {
+                        let score = score_scale.eval(synthetic_q(i) * synthetic_k(j));
+                        activation.eval(score) * synthetic_v(j)
+                    })
+                    .sum()
+            })
+            .collect(),
+        (_, None) => {
+            panic!("Reached unhandled case");
+        }
+    }
+}
+
+pub struct NormalizerReport {
+    pub normalizer: Normalizer,
+    pub seq_len: u64,
+    pub elapsed_cycles: u64,
+    pub resources: ResourceSummary,
+    pub error: ErrorReport, // versus the software reference
+}
+
+impl fmt::Display for NormalizerReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} attention ({}): N = {}, {} cycles, {} FIFO bits, {} storage bits, max abs err {:e}",
+            self.normalizer,
+            if self.normalizer == Normalizer::Linear {
+                "causal"
+            } else {
+                "full"
+            },
+            self.seq_len,
+            self.elapsed_cycles,
+            self.resources.fifo_bits(),
+            self.resources.storage_bits(),
+            self.error.max_abs_err
+        )
+    }
+}
+
+// Builds and runs one attention head with the given normalizer and score scale on the synthetic inputs
+pub fn run_normalizer_attn(
+    normalizer: Normalizer,
+    seq_len: u64,
+    score_scale: ScoreScale,
+) -> NormalizerReport {
+    let reference = normalizer_reference(normalizer, seq_len, &score_scale);
+    let config = StreamAttnConfig {
+        seq_len,
+        score_scale,
+        ..Default::default()
+    };
+
+    let mut ctx = ProgramBuilder::default();
+
+    let out = match (normalizer, normalizer.activation(seq_len)) {
+        (Normalizer::Softmax, _) => {
+            let (q, kt, v) =
+                add_masked_generators(&mut ctx, &Dense::new(seq_len), config.chan_size);
+            streamed_attn(&mut ctx, q, kt, v, &config, seq_len)
+        }
+        (Normalizer::Linear, _) => {
+            // Q, K and V are streamed once
+            let (q_sender, q) = ctx.bounded::<f64>(config.chan_size);
+            let (k_sender, k) = ctx.bounded::<f64>(config.chan_size);
+            let (v_sender, v) = ctx.bounded::<f64>(config.chan_size);
+            ctx.add_child(GeneratorContext::new(
+                move || (0..seq_len).map(synthetic_q),
+                q_sender,
+            ));
+            ctx.add_child(GeneratorContext::new(
+                move || (0..seq_len).map(synthetic_k),
+                k_sender,
+            ));
+            ctx.add_child(GeneratorContext::new(
+                move || (0..seq_len).map(synthetic_v),
+                v_sender,
+            ));
+            linear_attn(&mut ctx, q, k, v, &config, seq_len)
+        }
+        (_, Some(activation)) => {
+            let (q, kt, v) =
+                add_masked_generators(&mut ctx, &Dense::new(seq_len), config.chan_size);
+            activation_attn(&mut ctx, q, kt, v, &config, activation, seq_len)
+        }
+        (_, None) => {
+            panic!("Reached unhandled case");
+        }
+    };
+    let analysis = ErrorAnalysisContext::new(|| reference, out);
+    let error = analysis.report();
+    ctx.add_child(analysis);
+
+    let initialized = ctx.initialize(Default::default()).unwrap();
+    let summary = initialized.run(Default::default());
+    let error = error.lock().unwrap().clone();
+
+    NormalizerReport {
+        normalizer,
+        seq_len,
+        elapsed_cycles: summary.elapsed_cycles().unwrap(),
+        resources: config.resources.summary(),
+        error,
+    }
+}
@@ -0,0 +1,295 @@
+use dam::{context_tools::*, simulation::ProgramBuilder};
+
+use super::flashattn::FlashAttnConfig;
+use crate::node::{
+    broadcast::{Broadcast, Interleave},
+    flashattn_binary_op::BinaryOp,
+    flashattn_running_op::{IncrMax, IncrOutP, IncrSum},
+    streamattn_binary::BinaryOpType,
+    streamattn_qkt::QKTExpMultiRow,
+};
+
+// Query-tiled (Br) flash attention: Br query rows share every streamed K and V element.
+// K and V are streamed num_rows / Br times instead of num_rows times.
+// Lane r computes rows r, Br + r, 2 Br + r, ... and gets its own running-op pipeline.
+// Returns one output receiver per lane.
+pub fn row_tiled_flash_attn<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    q: Receiver<f64>,
+    kt: Receiver<f64>,
+    v: Receiver<f64>,
+    config: &FlashAttnConfig,
+    num_rows: u64,
+    rows_per_tile: u64,
+) -> Vec<Receiver<f64>> {
+    let chan_size = config.chan_size;
+    let rows_per_lane = num_rows / rows_per_tile;
+    let running_ii = config.running_ii(1);
+
+    // QKT & Exp block, one score stream per lane
+    let (qkt_exp_senders, qkt_exp_receivers): (Vec<_>, Vec<_>) = (0..rows_per_tile)
+        .map(|_| {
+            ctx.bounded::<f64>(
+                chan_size + (config.qkt_latency + config.score_scale.latency - 1) as usize,
+            )
+        })
+        .unzip();
+    ctx.add_child(
+        QKTExpMultiRow::new(
+            q,
+            kt,
+            qkt_exp_senders.into_iter().map(|s| vec![s]).collect(),
+            config.qkt_latency,
+            config.init_inverval,
+            config.seq_len,
+            num_rows,
+        )
+        .with_op_counter(config.op_counter.clone())
+        .with_score_scale(config.score_scale.clone()),
+    );
+
+    // V is shared by the Br lanes as well
+    let (v_senders, v_receivers): (Vec<_>, Vec<_>) = (0..rows_per_tile)
+        .map(|_| ctx.bounded::<f64>(chan_size))
+        .unzip();
+    ctx.add_child(Broadcast::new(
+        v,
+        v_senders,
+        1,
+        config.init_inverval,
+        rows_per_lane * config.seq_len,
+    ));
+
+    qkt_exp_receivers
+        .into_iter()
+        .zip(v_receivers)
+        .map(|(qkt_exp_receiver, v_receiver)| {
+            // Incremental Max
+            let (delta_sender1, delta_receiver1) = ctx.bounded::<f64>(chan_size);
+            let (delta_sender2, delta_receiver2) = ctx.bounded::<f64>(chan_size);
+            let (curr_sender1, curr_receiver1) = ctx.bounded::<f64>(chan_size);
+            let (curr_sender2, curr_receiver2) = ctx.bounded::<f64>(chan_size);
+            ctx.add_child(
+                IncrMax::new(
+                    qkt_exp_receiver,
+                    vec![delta_sender1, delta_sender2],
+                    vec![curr_sender1, curr_sender2],
+                    config.running_latency,
+                    config.init_inverval,
+                    config.seq_len,
+                    rows_per_lane,
+                )
+                .with_op_counter(config.op_counter.clone()),
+            );
+
+            // Incremental Sum
+            let (rowsum_sender, rowsum_receiver) = ctx
+                .bounded::<f64>(chan_size + (running_ii - 1 + config.rowsum_latency - 1) as usize);
+            let mut incr_sum = IncrSum::new(
+                delta_receiver1,
+                curr_receiver1,
+                rowsum_sender,
+                config.rowsum_latency,
+                config.running_issue_ii(),
+                config.seq_len,
+                rows_per_lane,
+            )
+            .with_op_counter(config.op_counter.clone());
+            if let Some(recurrence) = config.recurrence {
+                incr_sum = incr_sum.with_recurrence(recurrence);
+            }
+            ctx.add_child(incr_sum);
+
+            // Incremental outer product
+            let (matmul_sender, matmul_receiver) = ctx
+                .bounded::<f64>(chan_size + (running_ii - 1 + config.outerp_latency - 1) as usize);
+            let mut incr_outer_p = IncrOutP::new(
+                delta_receiver2,
+                curr_receiver2,
+                v_receiver,
+                matmul_sender,
+                config.outerp_latency,
+                config.running_issue_ii(),
+                config.seq_len,
+                rows_per_lane,
+            )
+            .with_op_counter(config.op_counter.clone());
+            if let Some(recurrence) = config.recurrence {
+                incr_outer_p = incr_outer_p.with_recurrence(recurrence);
+            }
+            ctx.add_child(incr_outer_p);
+
+            // Div
+            let (final_sender, final_receiver) =
+                ctx.bounded::<f64>(chan_size + (config.div_latency - 1) as usize);
+            ctx.add_child(
+                BinaryOp::new(
+                    matmul_receiver,
+                    rowsum_receiver,
+                    final_sender,
+                    config.div_latency,
+                    config.init_inverval,
+                    rows_per_lane,
+                    BinaryOpType::Div,
+                )
+                .with_op_counter(config.op_counter.clone()),
+            );
+
+            final_receiver
+        })
+        .collect()
+}
+
+// Flash attention with 'num_accumulators' rows interleaved through a single IncrSum / IncrOutP,
+// which keep one accumulator per row so consecutive updates of a row are 'num_accumulators'
+// elements apart and the recurrence ('config.recurrence') is hidden.
+// Score rows come from a QKTExpMultiRow with one IncrMax per row, so K and V are streamed
+// num_rows / num_accumulators times. Returns the output of every row, in order.
+pub fn interleaved_flash_attn<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    q: Receiver<f64>,
+    kt: Receiver<f64>,
+    v: Receiver<f64>,
+    config: &FlashAttnConfig,
+    num_rows: u64,
+    num_accumulators: u64,
+) -> Receiver<f64> {
+    let chan_size = config.chan_size;
+    let resources = &config.resources;
+    let rows_per_lane = num_rows / num_accumulators;
+
+    // QKT & Exp block, one score stream per row of a group
+    let qkt_exp_depth = chan_size + (config.qkt_latency + config.score_scale.latency - 1) as usize;
+    let (qkt_exp_senders, qkt_exp_receivers): (Vec<_>, Vec<_>) = (0..num_accumulators)
+        .map(|_| {
+            resources.add_fifo::<f64>("qkt_exp", qkt_exp_depth);
+            ctx.bounded::<f64>(qkt_exp_depth)
+        })
+        .unzip();
+    let qkt_exp = QKTExpMultiRow::new(
+        q,
+        kt,
+        qkt_exp_senders.into_iter().map(|s| vec![s]).collect(),
+        config.qkt_latency,
+        config.init_inverval,
+        config.seq_len,
+        num_rows,
+    )
+    .with_op_counter(config.op_counter.clone())
+    .with_score_scale(config.score_scale.clone());
+    resources.add_node("QKTExpMultiRow", &qkt_exp);
+    ctx.add_child(qkt_exp);
+
+    // Incremental Max, then interleave the rows of a group element by element
+    let mut delta_receivers = [vec![], vec![]];
+    let mut curr_receivers = [vec![], vec![]];
+    for qkt_exp_receiver in qkt_exp_receivers {
+        let (delta_sender1, delta_receiver1) = ctx.bounded::<f64>(chan_size);
+        let (delta_sender2, delta_receiver2) = ctx.bounded::<f64>(chan_size);
+        let (curr_sender1, curr_receiver1) = ctx.bounded::<f64>(chan_size);
+        let (curr_sender2, curr_receiver2) = ctx.bounded::<f64>(chan_size);
+        for name in ["delta1", "delta2", "curr1", "curr2"] {
+            resources.add_fifo::<f64>(name, chan_size);
+        }
+        let incr_max = IncrMax::new(
+            qkt_exp_receiver,
+            vec![delta_sender1, delta_sender2],
+            vec![curr_sender1, curr_sender2],
+            config.running_latency,
+            config.init_inverval,
+            config.seq_len,
+            rows_per_lane,
+        )
+        .with_op_counter(config.op_counter.clone());
+        resources.add_node("IncrMax", &incr_max);
+        ctx.add_child(incr_max);
+        delta_receivers[0].push(delta_receiver1);
+        delta_receivers[1].push(delta_receiver2);
+        curr_receivers[0].push(curr_receiver1);
+        curr_receivers[1].push(curr_receiver2);
+    }
+    let [delta_receivers1, delta_receivers2] = delta_receivers;
+    let [curr_receivers1, curr_receivers2] = curr_receivers;
+    let mut interleave = |name: &str, in_streams: Vec<Receiver<f64>>| {
+        let (sender, receiver) = ctx.bounded::<f64>(chan_size);
+        resources.add_fifo::<f64>(name, chan_size);
+        let interleave = Interleave::new(
+            in_streams,
+            sender,
+            1,
+            config.init_inverval,
+            rows_per_lane * config.seq_len,
+        );
+        resources.add_node("Interleave", &interleave);
+        ctx.add_child(interleave);
+        receiver
+    };
+    let delta_receiver1 = interleave("delta1_interleaved", delta_receivers1);
+    let delta_receiver2 = interleave("delta2_interleaved", delta_receivers2);
+    let curr_receiver1 = interleave("curr1_interleaved", curr_receivers1);
+    let curr_receiver2 = interleave("curr2_interleaved", curr_receivers2);
+
+    let running_ii = config.running_ii(num_accumulators);
+
+    // Incremental Sum
+    let rowsum_depth = chan_size + (running_ii - 1 + config.rowsum_latency - 1) as usize;
+    let (rowsum_sender, rowsum_receiver) = ctx.bounded::<f64>(rowsum_depth);
+    resources.add_fifo::<f64>("rowsum", rowsum_depth);
+    let mut incr_sum = IncrSum::new(
+        delta_receiver1,
+        curr_receiver1,
+        rowsum_sender,
+        config.rowsum_latency,
+        config.running_issue_ii(),
+        config.seq_len,
+        num_rows,
+    )
+    .with_accumulators(num_accumulators)
+    .with_op_counter(config.op_counter.clone());
+    if let Some(recurrence) = config.recurrence {
+        incr_sum = incr_sum.with_recurrence(recurrence);
+    }
+    resources.add_node("IncrSum", &incr_sum);
+    ctx.add_child(incr_sum);
+
+    // Incremental outer product, the rows of a group share each V element
+    let matmul_depth = chan_size + (running_ii - 1 + config.outerp_latency - 1) as usize;
+    let (matmul_sender, matmul_receiver) = ctx.bounded::<f64>(matmul_depth);
+    resources.add_fifo::<f64>("matmul", matmul_depth);
+    let mut incr_outer_p = IncrOutP::new(
+        delta_receiver2,
+        curr_receiver2,
+        v,
+        matmul_sender,
+        config.outerp_latency,
+        config.running_issue_ii(),
+        config.seq_len,
+        num_rows,
+    )
+    .with_accumulators(num_accumulators)
+    .with_op_counter(config.op_counter.clone());
+    if let Some(recurrence) = config.recurrence {
+        incr_outer_p = incr_outer_p.with_recurrence(recurrence);
+    }
+    resources.add_node("IncrOutP", &incr_outer_p);
+    ctx.add_child(incr_outer_p);
+
+    // Div
+    let final_depth = chan_size + (config.div_latency - 1) as usize;
+    let (final_sender, final_receiver) = ctx.bounded::<f64>(final_depth);
+    resources.add_fifo::<f64>("final", final_depth);
+    let div = BinaryOp::new(
+        matmul_receiver,
+        rowsum_receiver,
+        final_sender,
+        config.div_latency,
+        config.init_inverval,
+        num_rows,
+        BinaryOpType::Div,
+    )
+    .with_op_counter(config.op_counter.clone());
+    resources.add_node("Div", &div);
+    ctx.add_child(div);
+
+    final_receiver
+}
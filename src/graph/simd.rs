@@ -0,0 +1,239 @@
+use std::fmt;
+
+use dam::{context_tools::*, simulation::ProgramBuilder, utility_contexts::GeneratorContext};
+use ndarray::Array1;
+
+use super::{
+    flashattn::FlashAttnConfig,
+    multihead::{add_checker, add_q_generator, flash_reference, synthetic_k, synthetic_v},
+};
+use crate::{
+    analysis::resource::{ResourceSummary, UnitKind},
+    node::{
+        flashattn_binary_op::BinaryOp,
+        simd_op::{adder_tree_latency, SimdIncrMax, SimdIncrOutP, SimdIncrSum, SimdQKTExp},
+        streamattn_binary::BinaryOpType,
+    },
+};
+
+// Same pipeline as 'flash_attn' with 'lanes' keys per token between QKTExp and the running ops.
+// q carries one element per row, kt and v carry 'seq_len / lanes' packed tokens per row.
+// Returns the receiver of the normalized output (one element per row).
+pub fn simd_flash_attn<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    q: Receiver<f64>,
+    kt: Receiver<Array1<f64>>,
+    v: Receiver<Array1<f64>>,
+    config: &FlashAttnConfig,
+    num_rows: u64,
+    lanes: u64,
+) -> Receiver<f64> {
+    let row_lengths = vec![config.seq_len; num_rows as usize];
+    simd_flash_attn_rows(ctx, q, kt, v, config, row_lengths, lanes)
+}
+
+// Same pipeline with row i attending over 'row_lengths[i]' keys, a multiple of 'lanes'.
+// kt and v carry 'row_lengths[i] / lanes' packed tokens for row i.
+pub fn simd_flash_attn_rows<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    q: Receiver<f64>,
+    kt: Receiver<Array1<f64>>,
+    v: Receiver<Array1<f64>>,
+    config: &FlashAttnConfig,
+    row_lengths: Vec<u64>,
+    lanes: u64,
+) -> Receiver<f64> {
+    let chan_size = config.chan_size;
+    let num_rows = row_lengths.len() as u64;
+    let resources = &config.resources;
+    // Packed FIFOs hold 'lanes' words per entry
+    let add_packed_fifo = |name: &str, depth: usize| {
+        resources.add_fifo::<f64>(name, depth * lanes as usize);
+    };
+
+    // Comparator tree of the W-way max
+    let max_tree_latency = adder_tree_latency(lanes, None);
+
+    // QKT & Exp block, also covering the scores in flight through the comparator tree
+    let qkt_exp_depth = chan_size
+        + (config.qkt_latency + config.score_scale.latency + max_tree_latency - 1) as usize;
+    let (qkt_exp_sender, qkt_exp_receiver) = ctx.bounded::<Array1<f64>>(qkt_exp_depth);
+    add_packed_fifo("qkt_exp", qkt_exp_depth);
+    let qkt_exp = SimdQKTExp::new(
+        q,
+        kt,
+        vec![qkt_exp_sender],
+        config.qkt_latency,
+        config.init_inverval,
+        lanes,
+        config.seq_len,
+        num_rows,
+    )
+    .with_row_lengths(row_lengths.clone())
+    .with_op_counter(config.op_counter.clone())
+    .with_score_scale(config.score_scale.clone());
+    resources.add_node("SimdQKTExp", &qkt_exp);
+    ctx.add_child(qkt_exp);
+
+    // Incremental Max, its outputs trail the comparator tree
+    let max_depth = chan_size + max_tree_latency as usize;
+    let (delta_sender1, delta_receiver1) = ctx.bounded::<f64>(max_depth);
+    let (delta_sender2, delta_receiver2) = ctx.bounded::<f64>(max_depth);
+    let (curr_sender1, curr_receiver1) = ctx.bounded::<Array1<f64>>(max_depth);
+    let (curr_sender2, curr_receiver2) = ctx.bounded::<Array1<f64>>(max_depth);
+    for name in ["delta1", "delta2"] {
+        resources.add_fifo::<f64>(name, max_depth);
+    }
+    for name in ["curr1", "curr2"] {
+        add_packed_fifo(name, max_depth);
+    }
+    let incr_max = SimdIncrMax::new(
+        qkt_exp_receiver,
+        vec![delta_sender1, delta_sender2],
+        vec![curr_sender1, curr_sender2],
+        config.running_latency,
+        config.init_inverval,
+        lanes,
+        config.seq_len,
+        num_rows,
+    )
+    .with_row_lengths(row_lengths.clone())
+    .with_op_counter(config.op_counter.clone());
+    resources.add_node("SimdIncrMax", &incr_max);
+    ctx.add_child(incr_max);
+
+    // Incremental Sum, the adder tree sits in front of the recurrence
+    let running_ii = config.running_ii(1);
+    let tree_latency = adder_tree_latency(lanes, config.recurrence);
+    let rowsum_depth =
+        chan_size + (running_ii - 1 + config.rowsum_latency + tree_latency - 1) as usize;
+    let (rowsum_sender, rowsum_receiver) = ctx.bounded::<f64>(rowsum_depth);
+    resources.add_fifo::<f64>("rowsum", rowsum_depth);
+    let mut incr_sum = SimdIncrSum::new(
+        delta_receiver1,
+        curr_receiver1,
+        rowsum_sender,
+        config.rowsum_latency,
+        config.running_issue_ii(),
+        lanes,
+        config.seq_len,
+        num_rows,
+    )
+    .with_row_lengths(row_lengths.clone())
+    .with_op_counter(config.op_counter.clone());
+    if let Some(recurrence) = config.recurrence {
+        incr_sum = incr_sum.with_recurrence(recurrence);
+    }
+    resources.add_node("SimdIncrSum", &incr_sum);
+    ctx.add_child(incr_sum);
+
+    // Incremental outer product
+    let matmul_depth =
+        chan_size + (running_ii - 1 + config.outerp_latency + tree_latency - 1) as usize;
+    let (matmul_sender, matmul_receiver) = ctx.bounded::<f64>(matmul_depth);
+    resources.add_fifo::<f64>("matmul", matmul_depth);
+    let mut incr_outer_p = SimdIncrOutP::new(
+        delta_receiver2,
+        curr_receiver2,
+        v,
+        matmul_sender,
+        config.outerp_latency,
+        config.running_issue_ii(),
+        lanes,
+        config.seq_len,
+        num_rows,
+    )
+    .with_row_lengths(row_lengths)
+    .with_op_counter(config.op_counter.clone());
+    if let Some(recurrence) = config.recurrence {
+        incr_outer_p = incr_outer_p.with_recurrence(recurrence);
+    }
+    resources.add_node("SimdIncrOutP", &incr_outer_p);
+    ctx.add_child(incr_outer_p);
+
+    // Div, once per row so it stays scalar
+    let final_depth = chan_size + (config.div_latency - 1) as usize;
+    let (final_sender, final_receiver) = ctx.bounded::<f64>(final_depth);
+    resources.add_fifo::<f64>("final", final_depth);
+    let div = BinaryOp::new(
+        matmul_receiver,
+        rowsum_receiver,
+        final_sender,
+        config.div_latency,
+        config.init_inverval,
+        num_rows,
+        BinaryOpType::Div,
+    )
+    .with_op_counter(config.op_counter.clone());
+    resources.add_node("Div", &div);
+    ctx.add_child(div);
+
+    final_receiver
+}
+
+// Same synthetic K/V as 'add_kv_generators', packed 'lanes' keys per token
+fn add_packed_kv_generators<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    num_rows: u64,
+    seq_len: u64,
+    lanes: u64,
+    chan_size: usize,
+) -> (Receiver<Array1<f64>>, Receiver<Array1<f64>>) {
+    let (kt_sender, kt_receiver) = ctx.bounded::<Array1<f64>>(chan_size);
+    let (v_sender, v_receiver) = ctx.bounded::<Array1<f64>>(chan_size);
+    let num_tokens = num_rows * seq_len / lanes;
+    let packed = move |f: fn(u64) -> f64| {
+        move || {
+            (0..num_tokens).map(move |t| {
+                Array1::from_iter((0..lanes).map(move |l| f((t * lanes + l) % seq_len)))
+            })
+        }
+    };
+    ctx.add_child(GeneratorContext::new(packed(synthetic_k), kt_sender));
+    ctx.add_child(GeneratorContext::new(packed(synthetic_v), v_sender));
+
+    (kt_receiver, v_receiver)
+}
+
+#[derive(Debug, Clone)]
+pub struct SimdReport {
+    pub lanes: u64,
+    pub elapsed_cycles: u64,
+    pub resources: ResourceSummary,
+}
+
+impl fmt::Display for SimdReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "W = {}: {} cycles, {} multipliers, {} adders, {} exp units, {} storage bits",
+            self.lanes,
+            self.elapsed_cycles,
+            self.resources.units(UnitKind::Multiplier),
+            self.resources.units(UnitKind::Adder),
+            self.resources.units(UnitKind::ExpUnit),
+            self.resources.storage_bits()
+        )
+    }
+}
+
+// Builds and runs one 'lanes'-wide flash attention head on the synthetic inputs
+pub fn run_simd_flash_attn(lanes: u64, config: &FlashAttnConfig) -> SimdReport {
+    let seq_len = config.seq_len;
+
+    let mut ctx = ProgramBuilder::default();
+
+    let q = add_q_generator(&mut ctx, seq_len, seq_len, config.chan_size);
+    let (kt, v) = add_packed_kv_generators(&mut ctx, seq_len, seq_len, lanes, config.chan_size);
+    let out = simd_flash_attn(&mut ctx, q, kt, v, config, seq_len, lanes);
+    add_checker(&mut ctx, out, flash_reference(0..seq_len, seq_len));
+
+    let initialized = ctx.initialize(Default::default()).unwrap();
+    let summary = initialized.run(Default::default());
+
+    SimdReport {
+        lanes,
+        elapsed_cycles: summary.elapsed_cycles().unwrap(),
+        resources: config.resources.summary(),
+    }
+}
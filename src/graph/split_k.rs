@@ -0,0 +1,233 @@
+use std::fmt;
+
+use dam::{context_tools::*, simulation::ProgramBuilder, utility_contexts::GeneratorContext};
+
+use super::{
+    flashattn::FlashAttnConfig,
+    multihead::{flash_reference, synthetic_k, synthetic_q, synthetic_v},
+};
+use crate::{
+    analysis::error_report::{ErrorAnalysisContext, ErrorReport},
+    node::{
+        broadcast::Broadcast,
+        flashattn_running_op::{IncrMax, IncrOutP, IncrSum},
+        softmax_merge::SoftmaxMerge,
+        streamattn_qkt::QKTExp,
+    },
+};
+
+// Split-K (FlashDecoding) attention: the keys of every row are split into P contiguous
+// partitions of config.seq_len / P keys, each with its own online-softmax pipeline.
+// kv_parts[p] carries the K and V of partition p, q is broadcast to all partitions,
+// and a SoftmaxMerge combines the P partial (max, sum, output) states of each row.
+pub fn split_k_flash_attn<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    q: Receiver<f64>,
+    kv_parts: Vec<(Receiver<f64>, Receiver<f64>)>,
+    config: &FlashAttnConfig,
+    num_rows: u64,
+    merge_latency: u64,
+) -> Receiver<f64> {
+    let chan_size = config.chan_size;
+    let num_partitions = kv_parts.len() as u64;
+    assert!(config.seq_len.is_multiple_of(num_partitions));
+    let part_len = config.seq_len / num_partitions;
+    let running_ii = config.running_ii(1);
+
+    let (q_senders, q_receivers): (Vec<_>, Vec<_>) = (0..num_partitions)
+        .map(|_| ctx.bounded::<f64>(chan_size))
+        .unzip();
+    ctx.add_child(Broadcast::new(
+        q,
+        q_senders,
+        1,
+        config.init_inverval,
+        num_rows,
+    ));
+
+    let mut max_receivers = vec![];
+    let mut sum_receivers = vec![];
+    let mut out_p_receivers = vec![];
+    for (q, (kt, v)) in q_receivers.into_iter().zip(kv_parts) {
+        // QKT & Exp block
+        let (qkt_exp_sender, qkt_exp_receiver) = ctx.bounded::<f64>(
+            chan_size + (config.qkt_latency + config.score_scale.latency - 1) as usize,
+        );
+        ctx.add_child(
+            QKTExp::new(
+                q,
+                kt,
+                vec![qkt_exp_sender],
+                config.qkt_latency,
+                config.init_inverval,
+                part_len,
+            )
+            .with_outer_loop_bound(num_rows)
+            .with_op_counter(config.op_counter.clone())
+            .with_score_scale(config.score_scale.clone()),
+        );
+
+        // Incremental Max, also emits the partition max for the merge
+        let (delta_sender1, delta_receiver1) = ctx.bounded::<f64>(chan_size);
+        let (delta_sender2, delta_receiver2) = ctx.bounded::<f64>(chan_size);
+        let (curr_sender1, curr_receiver1) = ctx.bounded::<f64>(chan_size);
+        let (curr_sender2, curr_receiver2) = ctx.bounded::<f64>(chan_size);
+        let (max_sender, max_receiver) = ctx.bounded::<f64>(chan_size);
+        ctx.add_child(
+            IncrMax::new(
+                qkt_exp_receiver,
+                vec![delta_sender1, delta_sender2],
+                vec![curr_sender1, curr_sender2],
+                config.running_latency,
+                config.init_inverval,
+                part_len,
+                num_rows,
+            )
+            .with_max_out(vec![max_sender])
+            .with_op_counter(config.op_counter.clone()),
+        );
+
+        // Incremental Sum
+        let (rowsum_sender, rowsum_receiver) =
+            ctx.bounded::<f64>(chan_size + (running_ii - 1 + config.rowsum_latency - 1) as usize);
+        let mut incr_sum = IncrSum::new(
+            delta_receiver1,
+            curr_receiver1,
+            rowsum_sender,
+            config.rowsum_latency,
+            config.running_issue_ii(),
+            part_len,
+            num_rows,
+        )
+        .with_op_counter(config.op_counter.clone());
+        if let Some(recurrence) = config.recurrence {
+            incr_sum = incr_sum.with_recurrence(recurrence);
+        }
+        ctx.add_child(incr_sum);
+
+        // Incremental outer product
+        let (matmul_sender, matmul_receiver) =
+            ctx.bounded::<f64>(chan_size + (running_ii - 1 + config.outerp_latency - 1) as usize);
+        let mut incr_outer_p = IncrOutP::new(
+            delta_receiver2,
+            curr_receiver2,
+            v,
+            matmul_sender,
+            config.outerp_latency,
+            config.running_issue_ii(),
+            part_len,
+            num_rows,
+        )
+        .with_op_counter(config.op_counter.clone());
+        if let Some(recurrence) = config.recurrence {
+            incr_outer_p = incr_outer_p.with_recurrence(recurrence);
+        }
+        ctx.add_child(incr_outer_p);
+
+        max_receivers.push(max_receiver);
+        sum_receivers.push(rowsum_receiver);
+        out_p_receivers.push(matmul_receiver);
+    }
+
+    // Merge, replaces the final Div
+    let (final_sender, final_receiver) =
+        ctx.bounded::<f64>(chan_size + (merge_latency - 1) as usize);
+    ctx.add_child(
+        SoftmaxMerge::new(
+            max_receivers,
+            sum_receivers,
+            out_p_receivers,
+            final_sender,
+            merge_latency,
+            config.init_inverval,
+            num_rows,
+        )
+        .with_op_counter(config.op_counter.clone()),
+    );
+
+    final_receiver
+}
+
+pub struct SplitKReport {
+    pub num_partitions: u64,
+    pub seq_len: u64,
+    pub num_rows: u64,
+    pub elapsed_cycles: u64,
+    pub error: ErrorReport, // versus dense attention over all keys
+}
+
+impl fmt::Display for SplitKReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "split-K P = {}: {} rows x {} keys, {} cycles, max abs err {:e}",
+            self.num_partitions,
+            self.num_rows,
+            self.seq_len,
+            self.elapsed_cycles,
+            self.error.max_abs_err
+        )
+    }
+}
+
+// Key-dependent K and V of partition 'part', re-streamed once per query row
+fn add_partition_generators<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    part: u64,
+    part_len: u64,
+    num_rows: u64,
+    chan_size: usize,
+) -> (Receiver<f64>, Receiver<f64>) {
+    let (kt_sender, kt_receiver) = ctx.bounded::<f64>(chan_size);
+    let (v_sender, v_receiver) = ctx.bounded::<f64>(chan_size);
+    let keys = move || (0..(num_rows * part_len)).map(move |i| part * part_len + i % part_len);
+    ctx.add_child(GeneratorContext::new(
+        move || keys().map(synthetic_k),
+        kt_sender,
+    )); // KT: [D,1] shaped vectors
+    ctx.add_child(GeneratorContext::new(
+        move || keys().map(synthetic_v),
+        v_sender,
+    )); // V: [D,1] shaped vectors
+
+    (kt_receiver, v_receiver)
+}
+
+// Builds and runs split-K attention over 'num_partitions' partitions on the synthetic
+// inputs, and compares the merged rows to dense attention over all keys
+pub fn run_split_k_attn(
+    num_partitions: u64,
+    num_rows: u64,
+    merge_latency: u64,
+    config: &FlashAttnConfig,
+) -> SplitKReport {
+    let part_len = config.seq_len / num_partitions;
+
+    let mut ctx = ProgramBuilder::default();
+
+    let (q_sender, q) = ctx.bounded::<f64>(config.chan_size);
+    ctx.add_child(GeneratorContext::new(
+        move || (0..num_rows).map(synthetic_q),
+        q_sender,
+    )); // Q : [1,D] shaped vectors
+    let kv_parts = (0..num_partitions)
+        .map(|p| add_partition_generators(&mut ctx, p, part_len, num_rows, config.chan_size))
+        .collect();
+    let out = split_k_flash_attn(&mut ctx, q, kv_parts, config, num_rows, merge_latency);
+    let reference = flash_reference(0..num_rows, config.seq_len);
+    let analysis = ErrorAnalysisContext::new(|| reference, out);
+    let error = analysis.report();
+    ctx.add_child(analysis);
+
+    let initialized = ctx.initialize(Default::default()).unwrap();
+    let summary = initialized.run(Default::default());
+    let error = error.lock().unwrap().clone();
+
+    SplitKReport {
+        num_partitions,
+        seq_len: config.seq_len,
+        num_rows,
+        elapsed_cycles: summary.elapsed_cycles().unwrap(),
+        error,
+    }
+}
@@ -0,0 +1,163 @@
+use dam::{context_tools::*, simulation::ProgramBuilder};
+
+use crate::{
+    analysis::{cost::OpCounter, resource::ResourceTable},
+    node::{
+        score_scale::ScoreScale,
+        streamattn_binary::{Binary, BinaryOpType},
+        streamattn_matvec::MatVecProd,
+        streamattn_qkt::QKTExp,
+        streamattn_reduce::{ReduceOp, ReduceOpType},
+    },
+};
+
+// Latencies, II and FIFO depths of the streamed attention pipeline
+// QKTExp -> (ReduceOp Sum, long FIFO) -> Div -> MatVecProd
+pub struct StreamAttnConfig {
+    pub seq_len: u64, // keys per query row
+    pub qkt_latency: u64,
+    pub reduce_latency: u64,
+    pub binary_latency: u64,
+    pub matvec_latency: u64,
+    pub init_inverval: u64,
+    pub chan_size: usize,         // FIFO Depth
+    pub op_counter: OpCounter,    // shared by all nodes, see analysis::cost
+    pub resources: ResourceTable, // filled in while wiring, see analysis::resource
+    pub score_scale: ScoreScale,  // applied to q*k in QKTExp
+}
+
+impl StreamAttnConfig {
+    // The exponentiated scores wait in this FIFO until their row sum is known,
+    // it holds the longest row plus what is in flight in the QKT and reduce pipelines
+    pub fn chan_size_long(&self, row_lengths: &[u64]) -> usize {
+        let longest = *row_lengths.iter().max().unwrap_or(&0);
+        (longest + self.qkt_latency + self.score_scale.latency + self.reduce_latency) as usize + 2
+    }
+}
+
+impl Default for StreamAttnConfig {
+    fn default() -> Self {
+        StreamAttnConfig {
+            seq_len: 512,
+            qkt_latency: 11,
+            reduce_latency: 2,
+            binary_latency: 8,
+            matvec_latency: 12,
+            init_inverval: 1,
+            chan_size: 2,
+            op_counter: Default::default(),
+            resources: Default::default(),
+            score_scale: Default::default(),
+        }
+    }
+}
+
+// Wires one streamed attention pipeline for 'num_rows' query rows of 'config.seq_len' keys each.
+// q carries one element per row, kt and v carry 'seq_len' elements per row.
+// Returns the receiver of the output (one element per row).
+pub fn streamed_attn<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    q: Receiver<f64>,
+    kt: Receiver<f64>,
+    v: Receiver<f64>,
+    config: &StreamAttnConfig,
+    num_rows: u64,
+) -> Receiver<f64> {
+    let row_lengths = vec![config.seq_len; num_rows as usize];
+    streamed_attn_rows(ctx, q, kt, v, config, row_lengths)
+}
+
+// Same pipeline with row i attending over 'row_lengths[i]' keys, e.g. a sliding window.
+// kt and v carry 'row_lengths[i]' elements for row i, every row needs at least one key.
+// The long FIFO only has to hold the longest row.
+pub fn streamed_attn_rows<'a>(
+    ctx: &mut ProgramBuilder<'a>,
+    q: Receiver<f64>,
+    kt: Receiver<f64>,
+    v: Receiver<f64>,
+    config: &StreamAttnConfig,
+    row_lengths: Vec<u64>,
+) -> Receiver<f64> {
+    // A row without keys never produces its output, the following rows would desync
+    assert!(row_lengths.iter().all(|&l| l > 0));
+    let chan_size = config.chan_size;
+    let resources = &config.resources;
+    let chan_size_long = config.chan_size_long(&row_lengths);
+
+    // QKT & Exp block
+    let qkt_exp_short_depth =
+        chan_size + ((config.qkt_latency + config.score_scale.latency) as usize);
+    let (qkt_exp_short_sender, qkt_exp_short_receiver) = ctx.bounded::<f64>(qkt_exp_short_depth);
+    let (qkt_exp_long_sender, qkt_exp_long_receiver) = ctx.bounded::<f64>(chan_size_long);
+    resources.add_fifo::<f64>("qkt_exp_short", qkt_exp_short_depth);
+    resources.add_fifo::<f64>("qkt_exp_long", chan_size_long);
+    let qkt_exp = QKTExp::new(
+        q,
+        kt,
+        vec![qkt_exp_short_sender, qkt_exp_long_sender],
+        config.qkt_latency,
+        config.init_inverval,
+        config.seq_len,
+    )
+    .with_row_lengths(row_lengths.clone())
+    .with_op_counter(config.op_counter.clone())
+    .with_score_scale(config.score_scale.clone());
+    resources.add_node("QKTExp", &qkt_exp);
+    ctx.add_child(qkt_exp);
+
+    // Reduce
+    let rowsum_depth = chan_size + (config.reduce_latency as usize);
+    let (rowsum_sender, rowsum_receiver) = ctx.bounded::<f64>(rowsum_depth);
+    resources.add_fifo::<f64>("rowsum", rowsum_depth);
+    let reduce = ReduceOp::new(
+        qkt_exp_short_receiver,
+        rowsum_sender,
+        config.reduce_latency,
+        config.init_inverval,
+        config.seq_len,
+        row_lengths.len() as u64,
+        ReduceOpType::Sum,
+    )
+    .with_row_lengths(row_lengths.clone())
+    .with_op_counter(config.op_counter.clone());
+    resources.add_node("ReduceOp", &reduce);
+    ctx.add_child(reduce);
+
+    // Div
+    let div_depth = chan_size + (config.binary_latency as usize);
+    let (div_sender, div_receiver) = ctx.bounded::<f64>(div_depth);
+    resources.add_fifo::<f64>("div", div_depth);
+    let div = Binary::<f64>::new(
+        qkt_exp_long_receiver,
+        rowsum_receiver,
+        div_sender,
+        config.binary_latency,
+        config.init_inverval,
+        config.seq_len,
+        row_lengths.len() as u64,
+        BinaryOpType::Div,
+    )
+    .with_row_lengths(row_lengths.clone())
+    .with_op_counter(config.op_counter.clone());
+    resources.add_node("Div", &div);
+    ctx.add_child(div);
+
+    // Multiply with V
+    let (out_sender, out_receiver) = ctx.bounded::<f64>(chan_size);
+    resources.add_fifo::<f64>("out", chan_size);
+    let matvec = MatVecProd::new(
+        div_receiver,
+        v,
+        out_sender,
+        config.matvec_latency,
+        config.init_inverval,
+        config.seq_len,
+        row_lengths.len() as u64,
+    )
+    .with_row_lengths(row_lengths.clone())
+    .with_op_counter(config.op_counter.clone());
+    resources.add_node("MatVecProd", &matvec);
+    ctx.add_child(matvec);
+
+    out_receiver
+}
@@ -1,3 +1,6 @@
-
 pub mod test;
 pub mod node;
+pub mod analysis;
+pub mod graph;
+pub mod mask;
+pub mod memory;
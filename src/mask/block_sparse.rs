@@ -0,0 +1,96 @@
+use super::AttnMask;
+
+// BigBird / Longformer style layout over square blocks of 'block_size' queries x keys.
+// present[r][c] marks the key block c attended by query block r, the rest is never streamed.
+pub struct BlockSparse {
+    pub seq_len: u64,
+    pub block_size: u64,
+    pub present: Vec<Vec<bool>>,
+}
+
+impl BlockSparse {
+    pub fn new(seq_len: u64, block_size: u64) -> Self {
+        assert!(seq_len.is_multiple_of(block_size));
+        let num_blocks = (seq_len / block_size) as usize;
+        BlockSparse {
+            seq_len,
+            block_size,
+            present: vec![vec![false; num_blocks]; num_blocks],
+        }
+    }
+
+    pub fn num_blocks(&self) -> u64 {
+        self.seq_len / self.block_size
+    }
+
+    // Key blocks within 'radius' blocks of the query block
+    pub fn with_local(mut self, radius: u64) -> Self {
+        let num_blocks = self.num_blocks();
+        for r in 0..num_blocks {
+            for c in r.saturating_sub(radius)..=(r + radius).min(num_blocks - 1) {
+                self.present[r as usize][c as usize] = true;
+            }
+        }
+        self
+    }
+
+    // The first 'num_global' blocks attend to and are attended by every block
+    pub fn with_global(mut self, num_global: u64) -> Self {
+        let num_blocks = self.num_blocks();
+        for g in 0..num_global.min(num_blocks) {
+            for b in 0..num_blocks {
+                self.present[g as usize][b as usize] = true;
+                self.present[b as usize][g as usize] = true;
+            }
+        }
+        self
+    }
+
+    // 'per_row' extra key blocks per query block, picked by a seeded generator
+    pub fn with_random(mut self, per_row: u64, seed: u64) -> Self {
+        let num_blocks = self.num_blocks();
+        let mut state = seed;
+        for r in 0..num_blocks {
+            let mut added = 0;
+            // Stop early if the row is already full
+            while added < per_row && self.present[r as usize].iter().any(|p| !p) {
+                // splitmix64
+                state = state.wrapping_add(0x9E3779B97F4A7C15);
+                let mut z = state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+                z ^= z >> 31;
+                let c = (z % num_blocks) as usize;
+                if !self.present[r as usize][c] {
+                    self.present[r as usize][c] = true;
+                    added += 1;
+                }
+            }
+        }
+        self
+    }
+
+    pub fn is_present(&self, row_block: u64, col_block: u64) -> bool {
+        self.present[row_block as usize][col_block as usize]
+    }
+
+    // Fraction of present blocks
+    pub fn density(&self) -> f64 {
+        let present = self.present.iter().flatten().filter(|p| **p).count();
+        (present as f64) / ((self.num_blocks() * self.num_blocks()) as f64)
+    }
+}
+
+impl AttnMask for BlockSparse {
+    fn num_rows(&self) -> u64 {
+        self.seq_len
+    }
+
+    fn key_indices(&self, row: u64) -> Vec<u64> {
+        let row_block = row / self.block_size;
+        (0..self.num_blocks())
+            .filter(|c| self.is_present(row_block, *c))
+            .flat_map(|c| (c * self.block_size)..((c + 1) * self.block_size))
+            .collect()
+    }
+}
@@ -0,0 +1,59 @@
+pub mod block_sparse;
+pub mod window;
+
+use crate::memory::AccessPattern;
+
+// Which keys each query row attends to. Masked keys are never streamed,
+// so row i carries row_lengths()[i] K/V elements instead of seq_len.
+pub trait AttnMask {
+    fn num_rows(&self) -> u64;
+
+    // Attended keys of a row, in streaming order
+    fn key_indices(&self, row: u64) -> Vec<u64>;
+
+    fn row_lengths(&self) -> Vec<u64> {
+        (0..self.num_rows())
+            .map(|row| self.key_indices(row).len() as u64)
+            .collect()
+    }
+
+    fn num_keys(&self) -> u64 {
+        self.row_lengths().iter().sum()
+    }
+
+    // Key index of every element of the K/V streams, row after row
+    fn key_stream(&self) -> Vec<u64> {
+        (0..self.num_rows())
+            .flat_map(|row| self.key_indices(row))
+            .collect()
+    }
+
+    // Reads of a K or V buffer holding one entry per key
+    fn access_pattern(&self) -> AccessPattern {
+        let addresses = self.key_stream();
+        AccessPattern::new(addresses.len() as u64, move |i| {
+            addresses[i as usize] as usize
+        })
+    }
+}
+
+// Every query sees every key, the layout of the dense graphs
+pub struct Dense {
+    pub seq_len: u64,
+}
+
+impl Dense {
+    pub fn new(seq_len: u64) -> Self {
+        Dense { seq_len }
+    }
+}
+
+impl AttnMask for Dense {
+    fn num_rows(&self) -> u64 {
+        self.seq_len
+    }
+
+    fn key_indices(&self, _row: u64) -> Vec<u64> {
+        (0..self.seq_len).collect()
+    }
+}
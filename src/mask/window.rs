@@ -0,0 +1,23 @@
+use super::AttnMask;
+
+// Local attention: query i sees keys [i - window, i]
+pub struct SlidingWindow {
+    pub seq_len: u64,
+    pub window: u64,
+}
+
+impl SlidingWindow {
+    pub fn new(seq_len: u64, window: u64) -> Self {
+        SlidingWindow { seq_len, window }
+    }
+}
+
+impl AttnMask for SlidingWindow {
+    fn num_rows(&self) -> u64 {
+        self.seq_len
+    }
+
+    fn key_indices(&self, row: u64) -> Vec<u64> {
+        (row.saturating_sub(self.window)..=row).collect()
+    }
+}
@@ -0,0 +1,103 @@
+use std::sync::{Arc, Mutex};
+
+use dam::context_tools::*;
+
+use super::{AccessPattern, MemoryStats};
+use crate::analysis::cost::{OpCounter, OpKind};
+
+pub struct DramConfig {
+    pub bytes_per_cycle: u64, // peak bandwidth
+    pub burst_bytes: u64,     // minimum transfer size, aligned
+    pub latency: u64,         // request to data latency
+}
+
+#[context_macro]
+pub struct DramRead<A: Clone> {
+    // Read stream served from off-chip DRAM.
+    // Consecutive accesses falling into the same burst share one transfer,
+    // every new burst occupies the interface for burst_bytes / bytes_per_cycle cycles.
+    // Bursts are pipelined, so 'latency' is paid once per request rather than once per burst.
+    pub data: Vec<A>,
+    pub pattern: AccessPattern,
+    pub out_stream: Sender<A>,
+    pub config: DramConfig,
+    stats: Arc<Mutex<MemoryStats>>,
+    pub op_counter: OpCounter,
+}
+
+impl<A: DAMType> DramRead<A>
+where
+    DramRead<A>: Context,
+{
+    pub fn new(
+        data: Vec<A>,
+        pattern: AccessPattern,
+        out_stream: Sender<A>,
+        config: DramConfig,
+    ) -> Self {
+        let dram = DramRead {
+            data,
+            pattern,
+            out_stream,
+            config,
+            stats: Default::default(),
+            op_counter: Default::default(),
+            context_info: Default::default(),
+        };
+        (dram.out_stream).attach_sender(&dram);
+
+        dram
+    }
+
+    // Handle to read the statistics once the simulation has finished
+    pub fn stats(&self) -> Arc<Mutex<MemoryStats>> {
+        self.stats.clone()
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+}
+
+impl<A> Context for DramRead<A>
+where
+    A: DAMType,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        let word_bytes = std::mem::size_of::<A>() as u64;
+        let burst_cycles = self
+            .config
+            .burst_bytes
+            .div_ceil(self.config.bytes_per_cycle);
+        let mut current_burst = None;
+        let mut bursts = 0;
+        for i in 0..self.pattern.num_accesses {
+            let addr = (self.pattern.address)(i);
+            let burst = (addr as u64) * word_bytes / self.config.burst_bytes;
+            if current_burst != Some(burst) {
+                self.time.incr_cycles(burst_cycles);
+                current_burst = Some(burst);
+                bursts += 1;
+                self.op_counter
+                    .add(OpKind::DramByte, self.config.burst_bytes);
+            }
+
+            let curr_time = self.time.tick();
+            self.out_stream
+                .enqueue(
+                    &self.time,
+                    ChannelElement::new(curr_time + self.config.latency, self.data[addr].clone()),
+                )
+                .unwrap();
+        }
+
+        let mut stats = self.stats.lock().unwrap();
+        stats.reads += self.pattern.num_accesses;
+        stats.bytes_read += bursts * self.config.burst_bytes;
+        stats.busy_cycles += bursts * burst_cycles;
+        stats.bursts += bursts;
+    }
+}
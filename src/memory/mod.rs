@@ -0,0 +1,50 @@
+pub mod dram;
+pub mod sram;
+
+use std::fmt;
+
+// Sequence of word addresses a read port serves, one per streamed element
+pub struct AccessPattern {
+    pub num_accesses: u64,
+    pub address: Box<dyn Fn(u64) -> usize + Send + Sync>,
+}
+
+impl AccessPattern {
+    pub fn new<F>(num_accesses: u64, address: F) -> Self
+    where
+        F: Fn(u64) -> usize + Send + Sync + 'static,
+    {
+        AccessPattern {
+            num_accesses,
+            address: Box::new(address),
+        }
+    }
+
+    pub fn sequential(len: u64) -> Self {
+        AccessPattern::new(len, |i| i as usize)
+    }
+
+    // The whole buffer is streamed 'passes' times, e.g. K and V once per query row
+    pub fn repeated(len: u64, passes: u64) -> Self {
+        AccessPattern::new(len * passes, move |i| (i % len) as usize)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStats {
+    pub reads: u64,        // words delivered to the consumer
+    pub bytes_read: u64,   // bytes moved by the memory (whole bursts for DRAM)
+    pub busy_cycles: u64,  // cycles spent transferring data
+    pub stall_cycles: u64, // cycles lost to bank conflicts
+    pub bursts: u64,       // DRAM bursts issued
+}
+
+impl fmt::Display for MemoryStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} reads, {} bytes, {} busy cycles, {} stall cycles, {} bursts",
+            self.reads, self.bytes_read, self.busy_cycles, self.stall_cycles, self.bursts
+        )
+    }
+}
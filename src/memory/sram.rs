@@ -0,0 +1,114 @@
+use std::sync::{Arc, Mutex};
+
+use dam::context_tools::*;
+
+use super::{AccessPattern, MemoryStats};
+use crate::analysis::cost::{OpCounter, OpKind};
+
+pub struct SramConfig {
+    pub capacity: usize, // words
+    pub num_banks: usize,
+    pub ports_per_bank: usize, // accesses a bank serves per cycle
+    pub latency: u64,          // read latency
+}
+
+#[context_macro]
+pub struct SramRead<A: Clone> {
+    // Read port of an on-chip SRAM. Word 'addr' lives in bank addr % num_banks.
+    // Up to 'ports_per_bank' words per bank are delivered per cycle,
+    // a further access to a busy bank waits for the next cycle.
+    pub data: Vec<A>,
+    pub pattern: AccessPattern,
+    pub out_stream: Sender<A>,
+    pub config: SramConfig,
+    stats: Arc<Mutex<MemoryStats>>,
+    pub op_counter: OpCounter,
+}
+
+impl<A: DAMType> SramRead<A>
+where
+    SramRead<A>: Context,
+{
+    pub fn new(
+        data: Vec<A>,
+        pattern: AccessPattern,
+        out_stream: Sender<A>,
+        config: SramConfig,
+    ) -> Self {
+        assert!(data.len() <= config.capacity);
+        assert!(config.num_banks > 0);
+        assert!(config.ports_per_bank > 0);
+        let sram = SramRead {
+            data,
+            pattern,
+            out_stream,
+            config,
+            stats: Default::default(),
+            op_counter: Default::default(),
+            context_info: Default::default(),
+        };
+        (sram.out_stream).attach_sender(&sram);
+
+        sram
+    }
+
+    // Handle to read the statistics once the simulation has finished
+    pub fn stats(&self) -> Arc<Mutex<MemoryStats>> {
+        self.stats.clone()
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+}
+
+impl<A> Context for SramRead<A>
+where
+    A: DAMType,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        let word_bytes = std::mem::size_of::<A>() as u64;
+        let mut bank_use = vec![0; self.config.num_banks];
+        let mut cycle = self.time.tick();
+        let mut busy_cycles = 1;
+        let mut stall_cycles = 0;
+        for i in 0..self.pattern.num_accesses {
+            let addr = (self.pattern.address)(i);
+            let bank = addr % self.config.num_banks;
+            if self.time.tick() != cycle {
+                // Backpressure moved us to a later cycle, all banks are free again
+                bank_use.iter_mut().for_each(|b| *b = 0);
+                cycle = self.time.tick();
+                busy_cycles += 1;
+            } else if bank_use[bank] == self.config.ports_per_bank {
+                let conflict = bank_use.iter().any(|b| *b < self.config.ports_per_bank);
+                if conflict {
+                    stall_cycles += 1;
+                }
+                self.time.incr_cycles(1);
+                bank_use.iter_mut().for_each(|b| *b = 0);
+                cycle = self.time.tick();
+                busy_cycles += 1;
+            }
+            bank_use[bank] += 1;
+            self.op_counter.add(OpKind::SramAccess, 1);
+
+            let curr_time = self.time.tick();
+            self.out_stream
+                .enqueue(
+                    &self.time,
+                    ChannelElement::new(curr_time + self.config.latency, self.data[addr].clone()),
+                )
+                .unwrap();
+        }
+
+        let mut stats = self.stats.lock().unwrap();
+        stats.reads += self.pattern.num_accesses;
+        stats.bytes_read += self.pattern.num_accesses * word_bytes;
+        stats.busy_cycles += busy_cycles;
+        stats.stall_cycles += stall_cycles;
+    }
+}
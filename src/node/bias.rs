@@ -0,0 +1,95 @@
+use dam::context_tools::*;
+
+use crate::analysis::cost::{OpCounter, OpKind};
+
+// ALiBi slopes of 'num_heads' heads: the geometric sequence 2^(-8h/H), h = 1..H
+pub fn alibi_slopes(num_heads: u64) -> Vec<f64> {
+    (1..=num_heads)
+        .map(|h| 2_f64.powf(-8_f64 * (h as f64) / (num_heads as f64)))
+        .collect()
+}
+
+#[context_macro]
+pub struct AlibiBias<A: Clone> {
+    // Computes the ALiBi bias -slope * |i - j| on the fly, one element per score,
+    // in the order QKTExp consumes them (row i, then key j)
+    pub out_stream: Sender<A>,
+    pub slope: A,
+    pub latency: u64,       // pipeline depth
+    pub init_inverval: u64, // initiation interval
+    pub seq_len: u64,
+    pub outer_loop_bound: u64,
+    pub op_counter: OpCounter,
+    pub key_indices: Vec<Vec<u64>>, // per row, overrides 0..seq_len when set
+}
+
+impl<A: DAMType> AlibiBias<A>
+where
+    AlibiBias<A>: Context,
+{
+    pub fn new(
+        out_stream: Sender<A>,
+        slope: A,
+        latency: u64,       // pipeline depth
+        init_inverval: u64, // initiation interval
+        seq_len: u64,
+        outer_loop_bound: u64,
+    ) -> Self {
+        let alibi = AlibiBias {
+            out_stream,
+            slope,
+            latency,
+            init_inverval,
+            seq_len,
+            outer_loop_bound,
+            op_counter: Default::default(),
+            key_indices: vec![],
+            context_info: Default::default(),
+        };
+        (alibi.out_stream).attach_sender(&alibi);
+
+        alibi
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Only the keys a mask keeps, see mask::AttnMask::key_indices
+    pub fn with_key_indices(mut self, key_indices: Vec<Vec<u64>>) -> Self {
+        self.outer_loop_bound = key_indices.len() as u64;
+        self.key_indices = key_indices;
+        self
+    }
+}
+
+impl<A> Context for AlibiBias<A>
+where
+    A: DAMType + num::Float,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        for i in 0..self.outer_loop_bound {
+            let keys = match self.key_indices.get(i as usize) {
+                Some(keys) => keys.clone(),
+                None => (0..self.seq_len).collect(),
+            };
+            for j in keys {
+                let distance = <A as num::NumCast>::from(i.abs_diff(j)).unwrap();
+                let bias = -(self.slope * distance);
+                self.op_counter.add(OpKind::Mul, 1);
+
+                let curr_time = self.time.tick();
+                self.out_stream
+                    .enqueue(
+                        &self.time,
+                        ChannelElement::new(curr_time + self.latency, bias),
+                    )
+                    .unwrap();
+                self.time.incr_cycles(self.init_inverval);
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use dam::context_tools::*;
+
+#[context_macro]
+pub struct Broadcast<A: Clone> {
+    // Fans a single stream out to several consumers (e.g. one K/V stream shared by several query heads)
+    pub in_stream: Receiver<A>,
+    pub out_stream: Vec<Sender<A>>, // list of output FIFOs, each receives every element
+    pub latency: u64,               // pipeline depth
+    pub init_inverval: u64,         // initiation interval
+    pub loop_bound: u64,
+}
+
+impl<A: DAMType> Broadcast<A>
+where
+    Broadcast<A>: Context,
+{
+    pub fn new(
+        in_stream: Receiver<A>,
+        out_stream: Vec<Sender<A>>,
+        latency: u64,       // pipeline depth
+        init_inverval: u64, // initiation interval
+        loop_bound: u64,
+    ) -> Self {
+        let broadcast = Broadcast {
+            in_stream,
+            out_stream,
+            latency,
+            init_inverval,
+            loop_bound,
+            context_info: Default::default(),
+        };
+        (broadcast.in_stream).attach_receiver(&broadcast);
+        for i in broadcast.out_stream.iter() {
+            i.attach_sender(&broadcast);
+        }
+
+        broadcast
+    }
+}
+
+impl<A> Context for Broadcast<A>
+where
+    A: DAMType,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        for _i in 0..self.loop_bound {
+            let in_deq = self.in_stream.dequeue(&self.time);
+            match in_deq {
+                Ok(in_elem) => {
+                    let curr_time = self.time.tick();
+
+                    for k in self.out_stream.iter() {
+                        let _ = k.wait_until_available(&self.time);
+                    }
+                    for k in self.out_stream.iter() {
+                        k.enqueue(
+                            &self.time,
+                            ChannelElement::new(curr_time + self.latency, in_elem.data.clone()),
+                        )
+                        .unwrap();
+                    }
+                }
+                _ => {
+                    panic!("Reached unhandled case");
+                }
+            }
+            self.time.incr_cycles(self.init_inverval);
+        }
+    }
+}
+
+#[context_macro]
+pub struct Interleave<A: Clone> {
+    // Merges several streams round-robin, one element of each per round
+    // (e.g. rows of separate lanes feeding one multi-accumulator node)
+    pub in_stream: Vec<Receiver<A>>,
+    pub out_stream: Sender<A>,
+    pub latency: u64,       // pipeline depth
+    pub init_inverval: u64, // initiation interval, per output element
+    pub loop_bound: u64,    // rounds
+}
+
+impl<A: DAMType> Interleave<A>
+where
+    Interleave<A>: Context,
+{
+    pub fn new(
+        in_stream: Vec<Receiver<A>>,
+        out_stream: Sender<A>,
+        latency: u64,       // pipeline depth
+        init_inverval: u64, // initiation interval
+        loop_bound: u64,
+    ) -> Self {
+        let interleave = Interleave {
+            in_stream,
+            out_stream,
+            latency,
+            init_inverval,
+            loop_bound,
+            context_info: Default::default(),
+        };
+        for i in interleave.in_stream.iter() {
+            i.attach_receiver(&interleave);
+        }
+        (interleave.out_stream).attach_sender(&interleave);
+
+        interleave
+    }
+}
+
+impl<A> Context for Interleave<A>
+where
+    A: DAMType,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        for _i in 0..self.loop_bound {
+            for k in self.in_stream.iter() {
+                let in_deq = k.dequeue(&self.time);
+                match in_deq {
+                    Ok(in_elem) => {
+                        let curr_time = self.time.tick();
+                        self.out_stream
+                            .enqueue(
+                                &self.time,
+                                ChannelElement::new(curr_time + self.latency, in_elem.data),
+                            )
+                            .unwrap();
+                    }
+                    _ => {
+                        panic!("Reached unhandled case");
+                    }
+                }
+                self.time.incr_cycles(self.init_inverval);
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::f64::consts::LOG2_E;
+
+pub enum ExpUnitType {
+    Exact,                                // libm exp
+    Lut { table: Vec<f64> },              // 2^f read from a table indexed by the top bits of f
+    PiecewiseLinear { points: Vec<f64> }, // 2^f interpolated between equally spaced breakpoints
+    Exp2Shift,                            // 2^f ~= 1 + f, i.e. a shift and an add (Schraudolph)
+}
+
+// Hardware model of an exp unit: exp(x) = 2^(x * log2(e)) = 2^n * 2^f
+// The integer part n only adjusts the exponent.
+// The approximations differ in how 2^f on [0, 1) is computed.
+// 'latency' is added on top of the pipeline depth of the node that instantiates the unit,
+// and the node issues at the slower of its own II and the unit's II.
+pub struct ExpUnit {
+    pub unit_type: ExpUnitType,
+    pub latency: u64,       // pipeline depth
+    pub init_inverval: u64, // initiation interval
+}
+
+impl Default for ExpUnit {
+    // Exact exp whose cost is already folded into the latency and II of the owning node
+    fn default() -> Self {
+        ExpUnit::exact(0, 1)
+    }
+}
+
+impl ExpUnit {
+    pub fn exact(latency: u64, init_inverval: u64) -> Self {
+        ExpUnit {
+            unit_type: ExpUnitType::Exact,
+            latency,
+            init_inverval,
+        }
+    }
+
+    pub fn lut(index_bits: u32, latency: u64, init_inverval: u64) -> Self {
+        let entries = 1_u64 << index_bits;
+        // Each entry holds 2^f at the midpoint of its interval to halve the truncation error
+        let table = (0..entries)
+            .map(|i| (((i as f64) + 0.5) / (entries as f64)).exp2())
+            .collect();
+        ExpUnit {
+            unit_type: ExpUnitType::Lut { table },
+            latency,
+            init_inverval,
+        }
+    }
+
+    pub fn piecewise_linear(segments: u32, latency: u64, init_inverval: u64) -> Self {
+        assert!(segments > 0);
+        let points = (0..=segments)
+            .map(|i| ((i as f64) / (segments as f64)).exp2())
+            .collect();
+        ExpUnit {
+            unit_type: ExpUnitType::PiecewiseLinear { points },
+            latency,
+            init_inverval,
+        }
+    }
+
+    pub fn exp2_shift(latency: u64, init_inverval: u64) -> Self {
+        ExpUnit {
+            unit_type: ExpUnitType::Exp2Shift,
+            latency,
+            init_inverval,
+        }
+    }
+
+    fn pow2_frac(&self, f: f64) -> f64 {
+        match &self.unit_type {
+            ExpUnitType::Exact => f.exp2(),
+            ExpUnitType::Lut { table } => {
+                let idx = ((f * (table.len() as f64)) as usize).min(table.len() - 1);
+                table[idx]
+            }
+            ExpUnitType::PiecewiseLinear { points } => {
+                let segments = points.len() - 1;
+                let pos = f * (segments as f64);
+                let idx = (pos as usize).min(segments - 1);
+                let t = pos - (idx as f64);
+                points[idx] + (points[idx + 1] - points[idx]) * t
+            }
+            ExpUnitType::Exp2Shift => 1_f64 + f,
+        }
+    }
+
+    pub fn eval<A: num::Float>(&self, x: A) -> A {
+        if let ExpUnitType::Exact = self.unit_type {
+            return x.exp();
+        }
+        let y = x.to_f64().unwrap() * LOG2_E;
+        if y.is_nan() {
+            return A::nan();
+        }
+        // Anything below the smallest subnormal flushes to zero, anything above the largest finite saturates
+        if y < -1100_f64 {
+            return A::zero();
+        }
+        if y > 1100_f64 {
+            return A::infinity();
+        }
+        let n = y.floor();
+        let f = y - n;
+        A::from(self.pow2_frac(f) * 2_f64.powi(n as i32)).unwrap_or(A::infinity())
+    }
+}
@@ -0,0 +1,259 @@
+use dam::context_tools::*;
+
+use super::{exp_unit::ExpUnit, score_scale::ScoreScale, streamattn_reduce::MinMax};
+use crate::analysis::cost::{OpCounter, OpKind};
+
+#[context_macro]
+pub struct RecomputeP<A: Clone> {
+    // Backward pass: recomputes P_ij = exp(s_ij - lse_i) from the logsumexp stored by the
+    // forward pass, instead of keeping the N x N probabilities around.
+    // s_ij = exp(scale(q_i k_j)) is the score the forward running ops see (the QKTExp output),
+    // so the chain rule through q_i k_j also needs ds_ij / d(q_i k_j) = s_ij scale'(q_i k_j).
+    pub q: Receiver<A>,   // one per row
+    pub kt: Receiver<A>,  // 'seq_len' per row
+    pub lse: Receiver<A>, // one per row
+    pub out_fifo: Vec<Sender<A>>,
+    pub grad_fifo: Vec<Sender<A>>, // ds_ij / d(q_i k_j), same order as P
+    pub latency: u64,              // pipeline depth
+    pub init_inverval: u64,        // initiation interval
+    pub seq_len: u64,
+    pub outer_loop_bound: u64,
+    pub exp_unit: ExpUnit,
+    pub score_scale: ScoreScale, // same as the forward QKTExp
+    pub op_counter: OpCounter,
+}
+
+impl<A: DAMType> RecomputeP<A>
+where
+    RecomputeP<A>: Context,
+{
+    pub fn new(
+        q: Receiver<A>,
+        kt: Receiver<A>,
+        lse: Receiver<A>,
+        out_fifo: Vec<Sender<A>>,
+        latency: u64,
+        init_inverval: u64,
+        seq_len: u64,
+        outer_loop_bound: u64,
+    ) -> Self {
+        let recompute_p = RecomputeP {
+            q,
+            kt,
+            lse,
+            out_fifo,
+            grad_fifo: vec![],
+            latency,
+            init_inverval,
+            seq_len,
+            outer_loop_bound,
+            exp_unit: Default::default(),
+            score_scale: Default::default(),
+            op_counter: Default::default(),
+            context_info: Default::default(),
+        };
+        (recompute_p.q).attach_receiver(&recompute_p);
+        (recompute_p.kt).attach_receiver(&recompute_p);
+        (recompute_p.lse).attach_receiver(&recompute_p);
+        for i in recompute_p.out_fifo.iter() {
+            i.attach_sender(&recompute_p);
+        }
+
+        recompute_p
+    }
+
+    pub fn with_exp_unit(mut self, exp_unit: ExpUnit) -> Self {
+        self.exp_unit = exp_unit;
+        self
+    }
+
+    pub fn with_score_scale(mut self, score_scale: ScoreScale) -> Self {
+        self.score_scale = score_scale;
+        self
+    }
+
+    pub fn with_score_grad(mut self, grad_fifo: Vec<Sender<A>>) -> Self {
+        for i in grad_fifo.iter() {
+            i.attach_sender(&self);
+        }
+        self.grad_fifo = grad_fifo;
+        self
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+}
+
+impl<A> Context for RecomputeP<A>
+where
+    A: DAMType + num::Float,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        // Two exps in series, the score and P
+        let latency = self.latency + self.score_scale.latency + 2 * self.exp_unit.latency;
+        let init_inverval = self.init_inverval.max(self.exp_unit.init_inverval);
+        for _i in 0..self.outer_loop_bound {
+            let _ = self.q.peek_next(&self.time);
+            let _ = self.lse.peek_next(&self.time);
+            let q_deq = self.q.dequeue(&self.time);
+            let lse_deq = self.lse.dequeue(&self.time);
+            match (q_deq, lse_deq) {
+                (Ok(q), Ok(lse)) => {
+                    for _j in 0..self.seq_len {
+                        let kt_deq = self.kt.dequeue(&self.time);
+                        match kt_deq {
+                            Ok(kt) => {
+                                let qk = q.data * kt.data;
+                                let score = self.exp_unit.eval(self.score_scale.eval(qk));
+                                let p = self.exp_unit.eval(score - lse.data);
+                                self.op_counter.add(OpKind::Mul, 1);
+                                self.op_counter.add(OpKind::Add, 1);
+                                self.op_counter.add(OpKind::Exp, 2);
+                                for (kind, n) in self.score_scale.ops() {
+                                    self.op_counter.add(kind, n);
+                                }
+                                let curr_time = self.time.tick();
+
+                                for k in self.out_fifo.iter().chain(self.grad_fifo.iter()) {
+                                    let _ = k.wait_until_available(&self.time);
+                                }
+                                for k in self.out_fifo.iter() {
+                                    k.enqueue(
+                                        &self.time,
+                                        ChannelElement::new(curr_time + latency, p),
+                                    )
+                                    .unwrap();
+                                }
+                                if !self.grad_fifo.is_empty() {
+                                    let grad = score * self.score_scale.grad(qk);
+                                    self.op_counter.add(OpKind::Mul, 1);
+                                    for (kind, n) in self.score_scale.grad_ops() {
+                                        self.op_counter.add(kind, n);
+                                    }
+                                    for k in self.grad_fifo.iter() {
+                                        k.enqueue(
+                                            &self.time,
+                                            ChannelElement::new(curr_time + latency, grad),
+                                        )
+                                        .unwrap();
+                                    }
+                                }
+
+                                self.time.incr_cycles(init_inverval);
+                                // initiation interval
+                            }
+                            _ => {
+                                panic!("Reached unhandled case");
+                            }
+                        }
+                    }
+                }
+                (_, _) => {
+                    panic!("Reached unhandled case");
+                }
+            }
+        }
+    }
+}
+
+#[context_macro]
+pub struct ColumnAccum<A: Clone> {
+    // Column-wise reduction of a row-major N x M stream scaled per row: acc_j = sum_i a_ij b_i,
+    // e.g. dV = P^T dO and dK = dS^T Q. The M accumulators are held until the last row
+    // and then drained one per II.
+    pub in_stream: Receiver<A>,     // a_ij, 'inner_loop_bound' per row
+    pub in_row_stream: Receiver<A>, // b_i, one per row
+    pub out_stream: Sender<A>,
+    pub latency: u64,       // pipeline depth
+    pub init_inverval: u64, // initiation interval
+    pub inner_loop_bound: u64,
+    pub outer_loop_bound: u64,
+    pub op_counter: OpCounter,
+}
+
+impl<A: DAMType> ColumnAccum<A>
+where
+    ColumnAccum<A>: Context,
+{
+    pub fn new(
+        in_stream: Receiver<A>,
+        in_row_stream: Receiver<A>,
+        out_stream: Sender<A>,
+        latency: u64,
+        init_inverval: u64,
+        inner_loop_bound: u64,
+        outer_loop_bound: u64,
+    ) -> Self {
+        let column_accum = ColumnAccum {
+            in_stream,
+            in_row_stream,
+            out_stream,
+            latency,
+            init_inverval,
+            inner_loop_bound,
+            outer_loop_bound,
+            op_counter: Default::default(),
+            context_info: Default::default(),
+        };
+        (column_accum.in_stream).attach_receiver(&column_accum);
+        (column_accum.in_row_stream).attach_receiver(&column_accum);
+        (column_accum.out_stream).attach_sender(&column_accum);
+
+        column_accum
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+}
+
+impl<A> Context for ColumnAccum<A>
+where
+    A: DAMType + num::Num + MinMax + Copy,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        let mut accum = vec![A::get_zero(); self.inner_loop_bound as usize];
+        for _i in 0..self.outer_loop_bound {
+            let row_deq = self.in_row_stream.dequeue(&self.time);
+            match row_deq {
+                Ok(row_elem) => {
+                    for acc in accum.iter_mut() {
+                        let in_deq = self.in_stream.dequeue(&self.time);
+                        match in_deq {
+                            Ok(in_elem) => {
+                                *acc = *acc + in_elem.data * row_elem.data;
+                                self.op_counter.add(OpKind::Mul, 1);
+                                self.op_counter.add(OpKind::Add, 1);
+                            }
+                            _ => {
+                                panic!("Reached unhandled case");
+                            }
+                        }
+                        self.time.incr_cycles(self.init_inverval);
+                    }
+                }
+                _ => {
+                    panic!("Reached unhandled case");
+                }
+            }
+        }
+
+        for acc in accum {
+            let curr_time = self.time.tick();
+            self.out_stream
+                .enqueue(
+                    &self.time,
+                    ChannelElement::new(curr_time + self.latency, acc),
+                )
+                .unwrap();
+            self.time.incr_cycles(self.init_inverval);
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use super::streamattn_binary::BinaryOpType;
+use crate::analysis::cost::OpCounter;
 use dam::context_tools::*;
 
 #[context_macro]
@@ -11,6 +12,7 @@ pub struct BinaryOp<A: Clone> {
     pub init_inverval: u64, // initiation interval
     pub loop_bound: u64,
     pub op: BinaryOpType,
+    pub op_counter: OpCounter,
 }
 
 impl<A: DAMType> BinaryOp<A>
@@ -34,6 +36,7 @@ where
             init_inverval,
             loop_bound,
             op,
+            op_counter: Default::default(),
             context_info: Default::default(),
         };
         (binary_op.in1_stream).attach_receiver(&binary_op);
@@ -42,6 +45,11 @@ where
 
         binary_op
     }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
 }
 
 impl<A> Context for BinaryOp<A>
@@ -74,6 +82,7 @@ where
                             out_data = in1_data - in2_data;
                         }
                     }
+                    self.op_counter.add(self.op.op_kind(), 1);
                     let curr_time = self.time.tick();
                     self.out_stream
                         .enqueue(
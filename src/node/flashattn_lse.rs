@@ -0,0 +1,93 @@
+use dam::context_tools::*;
+
+use crate::analysis::cost::{OpCounter, OpKind};
+
+#[context_macro]
+pub struct LogSumExp<A: Clone> {
+    // Per-row logsumexp m + ln(l) of the softmax, from the final running max and sum.
+    // Stored by the forward pass for the backward pass and for merging partial results.
+    pub in_max_stream: Receiver<A>, // m from IncrMax
+    pub in_sum_stream: Receiver<A>, // l from IncrSum
+    pub out_stream: Vec<Sender<A>>,
+    pub latency: u64,       // pipeline depth of the log and the add
+    pub init_inverval: u64, // initiation interval
+    pub loop_bound: u64,
+    pub op_counter: OpCounter,
+}
+
+impl<A: DAMType> LogSumExp<A>
+where
+    LogSumExp<A>: Context,
+{
+    pub fn new(
+        in_max_stream: Receiver<A>,
+        in_sum_stream: Receiver<A>,
+        out_stream: Vec<Sender<A>>,
+        latency: u64,
+        init_inverval: u64,
+        loop_bound: u64,
+    ) -> Self {
+        let lse = LogSumExp {
+            in_max_stream,
+            in_sum_stream,
+            out_stream,
+            latency,
+            init_inverval,
+            loop_bound,
+            op_counter: Default::default(),
+            context_info: Default::default(),
+        };
+        (lse.in_max_stream).attach_receiver(&lse);
+        (lse.in_sum_stream).attach_receiver(&lse);
+        for i in lse.out_stream.iter() {
+            i.attach_sender(&lse);
+        }
+
+        lse
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+}
+
+impl<A> Context for LogSumExp<A>
+where
+    A: DAMType + num::Float,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        for _i in 0..self.loop_bound {
+            let _ = self.in_max_stream.peek_next(&self.time);
+            let _ = self.in_sum_stream.peek_next(&self.time);
+            let in_max_deq = self.in_max_stream.dequeue(&self.time);
+            let in_sum_deq = self.in_sum_stream.dequeue(&self.time);
+            match (in_max_deq, in_sum_deq) {
+                (Ok(in_max), Ok(in_sum)) => {
+                    let lse = in_max.data + in_sum.data.ln();
+                    // The log runs on the same kind of unit as the exponential
+                    self.op_counter.add(OpKind::Exp, 1);
+                    self.op_counter.add(OpKind::Add, 1);
+
+                    let curr_time = self.time.tick();
+                    for k in self.out_stream.iter() {
+                        let _ = k.wait_until_available(&self.time);
+                    }
+                    for k in self.out_stream.iter() {
+                        k.enqueue(
+                            &self.time,
+                            ChannelElement::new(curr_time + self.latency, lse),
+                        )
+                        .unwrap();
+                    }
+                }
+                (_, _) => {
+                    panic!("Reached unhandled case");
+                }
+            }
+            self.time.incr_cycles(self.init_inverval);
+        }
+    }
+}
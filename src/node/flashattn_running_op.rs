@@ -1,6 +1,51 @@
 use dam::context_tools::*;
 
-use super::streamattn_reduce::MinMax;
+use super::{exp_unit::ExpUnit, streamattn_reduce::MinMax};
+use crate::analysis::cost::{OpCounter, OpKind};
+
+// Multiply and add on the loop-carried 'temp_res * delta + curr' path of IncrSum and IncrOutP.
+// The next update of a row cannot issue before the previous one has left both units.
+#[derive(Clone, Copy, Debug)]
+pub struct Recurrence {
+    pub mul_latency: u64,
+    pub add_latency: u64,
+}
+
+impl Recurrence {
+    pub fn new(mul_latency: u64, add_latency: u64) -> Self {
+        Recurrence {
+            mul_latency,
+            add_latency,
+        }
+    }
+
+    pub fn latency(&self) -> u64 {
+        self.mul_latency + self.add_latency
+    }
+
+    // Minimal II when 'num_accumulators' rows take turns on the same units
+    pub fn min_ii(&self, num_accumulators: u64) -> u64 {
+        self.latency().div_ceil(num_accumulators).max(1)
+    }
+}
+
+// The rows of an interleaved group share every issue slot, so they must all have the same length
+fn check_group_lengths(row_lengths: &[u64], num_accumulators: u64) {
+    for group in row_lengths.chunks(num_accumulators as usize) {
+        assert!(group.iter().all(|l| *l == group[0]));
+    }
+}
+
+// Keys per row of interleaved group 'group'
+fn group_row_length(row_lengths: &[u64], default: u64, group: u64, num_accumulators: u64) -> u64 {
+    if row_lengths.is_empty() {
+        return default;
+    }
+    let start = (group * num_accumulators) as usize;
+    let lengths = &row_lengths[start..(start + num_accumulators as usize)];
+    check_group_lengths(lengths, num_accumulators);
+    lengths[0]
+}
 
 #[context_macro]
 pub struct IncrMax<A: Clone> {
@@ -11,6 +56,10 @@ pub struct IncrMax<A: Clone> {
     pub init_inverval: u64,
     pub inner_loop_bound: u64,
     pub outer_loop_bound: u64,
+    pub exp_unit: ExpUnit,
+    pub op_counter: OpCounter,
+    pub row_lengths: Vec<u64>, // overrides inner_loop_bound when set
+    pub max_out_stream: Vec<Sender<A>>, // final running max, one per row
 }
 
 impl<A: DAMType> IncrMax<A>
@@ -34,6 +83,10 @@ where
             init_inverval,
             inner_loop_bound,
             outer_loop_bound,
+            exp_unit: Default::default(),
+            row_lengths: vec![],
+            max_out_stream: vec![],
+            op_counter: Default::default(),
             context_info: Default::default(),
         };
         (incr_max.in_stream).attach_receiver(&incr_max);
@@ -46,6 +99,32 @@ where
 
         incr_max
     }
+
+    pub fn with_exp_unit(mut self, exp_unit: ExpUnit) -> Self {
+        self.exp_unit = exp_unit;
+        self
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Rows of different lengths, e.g. decode steps over a growing KV cache
+    pub fn with_row_lengths(mut self, row_lengths: Vec<u64>) -> Self {
+        self.outer_loop_bound = row_lengths.len() as u64;
+        self.row_lengths = row_lengths;
+        self
+    }
+
+    // Also emit the row max, needed to merge or rescale partial results downstream
+    pub fn with_max_out(mut self, max_out_stream: Vec<Sender<A>>) -> Self {
+        for i in max_out_stream.iter() {
+            i.attach_sender(&self);
+        }
+        self.max_out_stream = max_out_stream;
+        self
+    }
 }
 
 impl<A> Context for IncrMax<A>
@@ -55,36 +134,46 @@ where
     fn init(&mut self) {}
 
     fn run(&mut self) -> () {
-        for _i in 0..self.outer_loop_bound {
+        let latency = self.latency + self.exp_unit.latency;
+        let init_inverval = self.init_inverval.max(self.exp_unit.init_inverval);
+        for i in 0..self.outer_loop_bound {
+            let inner_loop_bound = self
+                .row_lengths
+                .get(i as usize)
+                .copied()
+                .unwrap_or(self.inner_loop_bound);
             let mut temp_res = A::get_min_val();
-            for _j in 0..self.inner_loop_bound {
+            for _j in 0..inner_loop_bound {
                 let in_deq = self.in_stream.dequeue(&self.time);
                 match in_deq {
                     Ok(in_elem) => {
                         // First Iteration
                         let in_data = in_elem.data;
                         let new_max = temp_res.get_max(in_data);
-                        let delta = (temp_res - new_max).exp();
-                        let curr = (in_data - new_max).exp();
+                        let delta = self.exp_unit.eval(temp_res - new_max);
+                        let curr = self.exp_unit.eval(in_data - new_max);
+                        // max, two subtractions and two exponentials
+                        self.op_counter.add(OpKind::Add, 3);
+                        self.op_counter.add(OpKind::Exp, 2);
                         temp_res = new_max;
 
                         let curr_time = self.time.tick();
                         for k in self.delta_out_stream.iter() {
                             k.enqueue(
                                 &self.time,
-                                ChannelElement::new(curr_time + self.latency, delta.clone()),
+                                ChannelElement::new(curr_time + latency, delta.clone()),
                             )
                             .unwrap();
                         }
                         for k in self.curr_out_stream.iter() {
                             k.enqueue(
                                 &self.time,
-                                ChannelElement::new(curr_time + self.latency, curr.clone()),
+                                ChannelElement::new(curr_time + latency, curr.clone()),
                             )
                             .unwrap();
                         }
 
-                        self.time.incr_cycles(self.init_inverval);
+                        self.time.incr_cycles(init_inverval);
                         // initiation interval
                     }
                     _ => {
@@ -92,6 +181,15 @@ where
                     }
                 }
             }
+
+            let curr_time = self.time.tick();
+            for k in self.max_out_stream.iter() {
+                k.enqueue(
+                    &self.time,
+                    ChannelElement::new(curr_time + latency, temp_res),
+                )
+                .unwrap();
+            }
         }
     }
 }
@@ -105,6 +203,10 @@ pub struct IncrSum<A: Clone> {
     pub init_inverval: u64,
     pub inner_loop_bound: u64,
     pub outer_loop_bound: u64,
+    pub op_counter: OpCounter,
+    pub row_lengths: Vec<u64>, // overrides inner_loop_bound when set
+    pub recurrence: Option<Recurrence>, // derives the II when set, see 'achieved_ii'
+    pub num_accumulators: u64, // rows interleaved element by element
 }
 
 impl<A: DAMType> IncrSum<A>
@@ -128,6 +230,10 @@ where
             init_inverval,
             inner_loop_bound,
             outer_loop_bound,
+            row_lengths: vec![],
+            recurrence: None,
+            num_accumulators: 1,
+            op_counter: Default::default(),
             context_info: Default::default(),
         };
         (incr_sum.in_delta_stream).attach_receiver(&incr_sum);
@@ -136,6 +242,45 @@ where
 
         incr_sum
     }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Rows of different lengths, e.g. decode steps over a growing KV cache
+    pub fn with_row_lengths(mut self, row_lengths: Vec<u64>) -> Self {
+        check_group_lengths(&row_lengths, self.num_accumulators);
+        self.outer_loop_bound = row_lengths.len() as u64;
+        self.row_lengths = row_lengths;
+        self
+    }
+
+    // Derive the II from the multiply and add latencies instead of trusting 'init_inverval'
+    pub fn with_recurrence(mut self, recurrence: Recurrence) -> Self {
+        self.recurrence = Some(recurrence);
+        self
+    }
+
+    // Keep 'num_accumulators' rows in flight to hide the recurrence.
+    // The inputs then carry element j of each row of a group before element j + 1,
+    // and the rows of a group share their length.
+    pub fn with_accumulators(mut self, num_accumulators: u64) -> Self {
+        assert!(num_accumulators > 0);
+        check_group_lengths(&self.row_lengths, num_accumulators);
+        self.num_accumulators = num_accumulators;
+        self
+    }
+
+    // II the recurrence allows, 'init_inverval' when it is not modeled
+    pub fn achieved_ii(&self) -> u64 {
+        match self.recurrence {
+            Some(recurrence) => self
+                .init_inverval
+                .max(recurrence.min_ii(self.num_accumulators)),
+            None => self.init_inverval,
+        }
+    }
 }
 
 impl<A> Context for IncrSum<A>
@@ -145,36 +290,49 @@ where
     fn init(&mut self) {}
 
     fn run(&mut self) -> () {
-        for _i in 0..self.outer_loop_bound {
-            let mut temp_res = A::get_zero();
-            for j in 0..self.inner_loop_bound {
-                let _ = self.in_delta_stream.peek_next(&self.time);
-                let _ = self.in_curr_stream.peek_next(&self.time);
-                let in_delta_deq = self.in_delta_stream.dequeue(&self.time);
-                let in_curr_deq = self.in_curr_stream.dequeue(&self.time);
-                match (in_delta_deq, in_curr_deq) {
-                    (Ok(in_delta), Ok(in_curr)) => {
-                        // First Iteration
-                        let in_delta_data = in_delta.data;
-                        let in_curr_data = in_curr.data;
-                        let new_sum = temp_res * in_delta_data + in_curr_data;
-                        temp_res = new_sum;
-
-                        if j == self.inner_loop_bound - 1 {
-                            let curr_time = self.time.tick();
-                            self.out_stream
-                                .enqueue(
-                                    &self.time,
-                                    ChannelElement::new(curr_time + self.latency, temp_res),
-                                )
-                                .unwrap();
-                        }
+        let init_inverval = self.achieved_ii();
+        let num_accumulators = self.num_accumulators;
+        assert!(self.outer_loop_bound.is_multiple_of(num_accumulators));
+        for i in 0..(self.outer_loop_bound / num_accumulators) {
+            let inner_loop_bound = group_row_length(
+                &self.row_lengths,
+                self.inner_loop_bound,
+                i,
+                num_accumulators,
+            );
+            let mut temp_res = vec![A::get_zero(); num_accumulators as usize];
+            for j in 0..inner_loop_bound {
+                for acc in temp_res.iter_mut() {
+                    let _ = self.in_delta_stream.peek_next(&self.time);
+                    let _ = self.in_curr_stream.peek_next(&self.time);
+                    let in_delta_deq = self.in_delta_stream.dequeue(&self.time);
+                    let in_curr_deq = self.in_curr_stream.dequeue(&self.time);
+                    match (in_delta_deq, in_curr_deq) {
+                        (Ok(in_delta), Ok(in_curr)) => {
+                            // First Iteration
+                            let in_delta_data = in_delta.data;
+                            let in_curr_data = in_curr.data;
+                            let new_sum = *acc * in_delta_data + in_curr_data;
+                            self.op_counter.add(OpKind::Mul, 1);
+                            self.op_counter.add(OpKind::Add, 1);
+                            *acc = new_sum;
 
-                        self.time.incr_cycles(self.init_inverval);
-                        // initiation interval
-                    }
-                    (_, _) => {
-                        panic!("Reached unhandled case");
+                            if j == inner_loop_bound - 1 {
+                                let curr_time = self.time.tick();
+                                self.out_stream
+                                    .enqueue(
+                                        &self.time,
+                                        ChannelElement::new(curr_time + self.latency, *acc),
+                                    )
+                                    .unwrap();
+                            }
+
+                            self.time.incr_cycles(init_inverval);
+                            // initiation interval
+                        }
+                        (_, _) => {
+                            panic!("Reached unhandled case");
+                        }
                     }
                 }
             }
@@ -192,6 +350,10 @@ pub struct IncrOutP<A: Clone> {
     pub init_inverval: u64,
     pub inner_loop_bound: u64,
     pub outer_loop_bound: u64,
+    pub op_counter: OpCounter,
+    pub row_lengths: Vec<u64>, // overrides inner_loop_bound when set
+    pub recurrence: Option<Recurrence>, // derives the II when set, see 'achieved_ii'
+    pub num_accumulators: u64, // rows interleaved element by element
 }
 
 impl<A: DAMType> IncrOutP<A>
@@ -217,6 +379,10 @@ where
             init_inverval,
             inner_loop_bound,
             outer_loop_bound,
+            row_lengths: vec![],
+            recurrence: None,
+            num_accumulators: 1,
+            op_counter: Default::default(),
             context_info: Default::default(),
         };
         (incr_outer_p.in_delta_stream).attach_receiver(&incr_outer_p);
@@ -226,6 +392,45 @@ where
 
         incr_outer_p
     }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Rows of different lengths, e.g. decode steps over a growing KV cache
+    pub fn with_row_lengths(mut self, row_lengths: Vec<u64>) -> Self {
+        check_group_lengths(&row_lengths, self.num_accumulators);
+        self.outer_loop_bound = row_lengths.len() as u64;
+        self.row_lengths = row_lengths;
+        self
+    }
+
+    // Derive the II from the multiply and add latencies instead of trusting 'init_inverval'
+    pub fn with_recurrence(mut self, recurrence: Recurrence) -> Self {
+        self.recurrence = Some(recurrence);
+        self
+    }
+
+    // Keep 'num_accumulators' rows in flight to hide the recurrence.
+    // The inputs then carry element j of each row of a group before element j + 1,
+    // and the rows of a group share their length.
+    pub fn with_accumulators(mut self, num_accumulators: u64) -> Self {
+        assert!(num_accumulators > 0);
+        check_group_lengths(&self.row_lengths, num_accumulators);
+        self.num_accumulators = num_accumulators;
+        self
+    }
+
+    // II the recurrence allows, 'init_inverval' when it is not modeled
+    pub fn achieved_ii(&self) -> u64 {
+        match self.recurrence {
+            Some(recurrence) => self
+                .init_inverval
+                .max(recurrence.min_ii(self.num_accumulators)),
+            None => self.init_inverval,
+        }
+    }
 }
 
 impl<A> Context for IncrOutP<A>
@@ -235,40 +440,59 @@ where
     fn init(&mut self) {}
 
     fn run(&mut self) -> () {
-        for _i in 0..self.outer_loop_bound {
-            let mut temp_res = A::get_zero();
-            for j in 0..self.inner_loop_bound {
-                let _ = self.in_delta_stream.peek_next(&self.time);
-                let _ = self.in_curr_stream.peek_next(&self.time);
+        let init_inverval = self.achieved_ii();
+        let num_accumulators = self.num_accumulators;
+        assert!(self.outer_loop_bound.is_multiple_of(num_accumulators));
+        for i in 0..(self.outer_loop_bound / num_accumulators) {
+            let inner_loop_bound = group_row_length(
+                &self.row_lengths,
+                self.inner_loop_bound,
+                i,
+                num_accumulators,
+            );
+            let mut temp_res = vec![A::get_zero(); num_accumulators as usize];
+            for j in 0..inner_loop_bound {
+                // The rows of a group share the V element
                 let _ = self.in_v_stream.peek_next(&self.time);
-                let in_delta_deq = self.in_delta_stream.dequeue(&self.time);
-                let in_curr_deq = self.in_curr_stream.dequeue(&self.time);
                 let in_v_deq = self.in_v_stream.dequeue(&self.time);
-                match (in_delta_deq, in_curr_deq, in_v_deq) {
-                    (Ok(in_delta), Ok(in_curr), Ok(in_v)) => {
-                        // First Iteration
-                        let in_delta_data = in_delta.data;
-                        let in_curr_data = in_curr.data;
-                        let in_v_data = in_v.data;
-                        let new_sum = temp_res * in_delta_data + in_curr_data * in_v_data;
-                        temp_res = new_sum;
-
-                        if j == self.inner_loop_bound - 1 {
-                            let curr_time = self.time.tick();
-                            self.out_stream
-                                .enqueue(
-                                    &self.time,
-                                    ChannelElement::new(curr_time + self.latency, temp_res),
-                                )
-                                .unwrap();
-                        }
-
-                        self.time.incr_cycles(self.init_inverval);
-                        // initiation interval
-                    }
-                    (_, _, _) => {
+                let in_v_data = match in_v_deq {
+                    Ok(in_v) => in_v.data,
+                    _ => {
                         panic!("Reached unhandled case");
                     }
+                };
+                for acc in temp_res.iter_mut() {
+                    let _ = self.in_delta_stream.peek_next(&self.time);
+                    let _ = self.in_curr_stream.peek_next(&self.time);
+                    let in_delta_deq = self.in_delta_stream.dequeue(&self.time);
+                    let in_curr_deq = self.in_curr_stream.dequeue(&self.time);
+                    match (in_delta_deq, in_curr_deq) {
+                        (Ok(in_delta), Ok(in_curr)) => {
+                            // First Iteration
+                            let in_delta_data = in_delta.data;
+                            let in_curr_data = in_curr.data;
+                            let new_sum = *acc * in_delta_data + in_curr_data * in_v_data;
+                            self.op_counter.add(OpKind::Mul, 2);
+                            self.op_counter.add(OpKind::Add, 1);
+                            *acc = new_sum;
+
+                            if j == inner_loop_bound - 1 {
+                                let curr_time = self.time.tick();
+                                self.out_stream
+                                    .enqueue(
+                                        &self.time,
+                                        ChannelElement::new(curr_time + self.latency, *acc),
+                                    )
+                                    .unwrap();
+                            }
+
+                            self.time.incr_cycles(init_inverval);
+                            // initiation interval
+                        }
+                        (_, _) => {
+                            panic!("Reached unhandled case");
+                        }
+                    }
                 }
             }
         }
@@ -0,0 +1,408 @@
+use dam::context_tools::*;
+
+use super::{exp_unit::ExpUnit, flashattn_running_op::Recurrence, streamattn_reduce::MinMax};
+use crate::analysis::cost::{OpCounter, OpKind};
+
+// Block-wise variants of the running ops in flashattn_running_op.
+// The K/V sequence of a row is split into tiles of 'tile_size' (Bc) keys.
+// The running max, sum and output are rescaled once per tile instead of once per key,
+// so the loop-carried rescale only has to complete once every 'tile_size' elements.
+
+#[context_macro]
+pub struct TiledIncrMax<A: Clone> {
+    pub in_stream: Receiver<A>,
+    pub delta_out_stream: Vec<Sender<A>>, // one exp(m_old - m_new) per tile
+    pub curr_out_stream: Vec<Sender<A>>,  // one exp(s - m_new) per element
+    pub latency: u64,
+    pub init_inverval: u64, // per element
+    pub tile_size: u64,
+    pub inner_loop_bound: u64,
+    pub outer_loop_bound: u64,
+    pub exp_unit: ExpUnit,
+    pub op_counter: OpCounter,
+}
+
+impl<A: DAMType> TiledIncrMax<A>
+where
+    TiledIncrMax<A>: Context,
+{
+    pub fn new(
+        in_stream: Receiver<A>,
+        delta_out_stream: Vec<Sender<A>>,
+        curr_out_stream: Vec<Sender<A>>,
+        latency: u64,
+        init_inverval: u64,
+        tile_size: u64,
+        inner_loop_bound: u64,
+        outer_loop_bound: u64,
+    ) -> Self {
+        assert!(inner_loop_bound.is_multiple_of(tile_size));
+        let incr_max = TiledIncrMax {
+            in_stream,
+            delta_out_stream,
+            curr_out_stream,
+            latency,
+            init_inverval,
+            tile_size,
+            inner_loop_bound,
+            outer_loop_bound,
+            exp_unit: Default::default(),
+            op_counter: Default::default(),
+            context_info: Default::default(),
+        };
+        (incr_max.in_stream).attach_receiver(&incr_max);
+        for i in incr_max.delta_out_stream.iter() {
+            i.attach_sender(&incr_max);
+        }
+        for i in incr_max.curr_out_stream.iter() {
+            i.attach_sender(&incr_max);
+        }
+
+        incr_max
+    }
+
+    pub fn with_exp_unit(mut self, exp_unit: ExpUnit) -> Self {
+        self.exp_unit = exp_unit;
+        self
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+}
+
+impl<A> Context for TiledIncrMax<A>
+where
+    A: DAMType + num::Float + MinMax + Copy,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        let latency = self.latency + self.exp_unit.latency;
+        let init_inverval = self.init_inverval.max(self.exp_unit.init_inverval);
+        let num_tiles = self.inner_loop_bound / self.tile_size;
+        // The block max needs the whole tile, so tiles are double buffered:
+        // the curr values of the previous tile drain while the next tile is read.
+        let mut prev_tile: Vec<A> = vec![];
+        for _i in 0..self.outer_loop_bound {
+            let mut temp_res = A::get_min_val();
+            for _t in 0..num_tiles {
+                let mut tile = Vec::with_capacity(self.tile_size as usize);
+                for k in 0..(self.tile_size as usize) {
+                    let in_deq = self.in_stream.dequeue(&self.time);
+                    match in_deq {
+                        Ok(in_elem) => {
+                            tile.push(in_elem.data);
+                        }
+                        _ => {
+                            panic!("Reached unhandled case");
+                        }
+                    }
+                    if k < prev_tile.len() {
+                        let curr_time = self.time.tick();
+                        for s in self.curr_out_stream.iter() {
+                            s.enqueue(
+                                &self.time,
+                                ChannelElement::new(curr_time + latency, prev_tile[k]),
+                            )
+                            .unwrap();
+                        }
+                    }
+                    self.time.incr_cycles(init_inverval);
+                    // initiation interval
+                }
+
+                let new_max = tile.iter().fold(temp_res, |acc, x| acc.get_max(*x));
+                let delta = self.exp_unit.eval(temp_res - new_max);
+                temp_res = new_max;
+
+                let curr_time = self.time.tick();
+                for s in self.delta_out_stream.iter() {
+                    s.enqueue(&self.time, ChannelElement::new(curr_time + latency, delta))
+                        .unwrap();
+                }
+                prev_tile = tile
+                    .iter()
+                    .map(|x| self.exp_unit.eval(*x - new_max))
+                    .collect();
+                // tile max, delta and one subtraction and exponential per element
+                self.op_counter.add(OpKind::Add, 2 * self.tile_size + 1);
+                self.op_counter.add(OpKind::Exp, self.tile_size + 1);
+            }
+        }
+        // Drain the last tile
+        for curr in prev_tile {
+            let curr_time = self.time.tick();
+            for s in self.curr_out_stream.iter() {
+                s.enqueue(&self.time, ChannelElement::new(curr_time + latency, curr))
+                    .unwrap();
+            }
+            self.time.incr_cycles(init_inverval);
+        }
+    }
+}
+
+#[context_macro]
+pub struct TiledIncrSum<A: Clone> {
+    pub in_delta_stream: Receiver<A>, // one element per tile
+    pub in_curr_stream: Receiver<A>,  // one element per key
+    pub out_stream: Sender<A>,
+    pub latency: u64,
+    pub init_inverval: u64,    // per element
+    pub rescale_inverval: u64, // II of the loop-carried l = l * delta + rowsum(tile)
+    pub tile_size: u64,
+    pub inner_loop_bound: u64,
+    pub outer_loop_bound: u64,
+    pub op_counter: OpCounter,
+    pub recurrence: Option<Recurrence>, // derives the rescale II when set, see 'rescale_ii'
+}
+
+impl<A: DAMType> TiledIncrSum<A>
+where
+    TiledIncrSum<A>: Context,
+{
+    pub fn new(
+        in_delta_stream: Receiver<A>,
+        in_curr_stream: Receiver<A>,
+        out_stream: Sender<A>,
+        latency: u64,
+        init_inverval: u64,
+        rescale_inverval: u64,
+        tile_size: u64,
+        inner_loop_bound: u64,
+        outer_loop_bound: u64,
+    ) -> Self {
+        assert!(inner_loop_bound.is_multiple_of(tile_size));
+        let incr_sum = TiledIncrSum {
+            in_delta_stream,
+            in_curr_stream,
+            out_stream,
+            latency,
+            init_inverval,
+            rescale_inverval,
+            tile_size,
+            inner_loop_bound,
+            outer_loop_bound,
+            recurrence: None,
+            op_counter: Default::default(),
+            context_info: Default::default(),
+        };
+        (incr_sum.in_delta_stream).attach_receiver(&incr_sum);
+        (incr_sum.in_curr_stream).attach_receiver(&incr_sum);
+        (incr_sum.out_stream).attach_sender(&incr_sum);
+
+        incr_sum
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Derive the rescale II from the multiply and add latencies instead of trusting
+    // 'rescale_inverval'
+    pub fn with_recurrence(mut self, recurrence: Recurrence) -> Self {
+        self.recurrence = Some(recurrence);
+        self
+    }
+
+    // II of the once-per-tile rescale, 'rescale_inverval' when the recurrence is not modeled
+    pub fn rescale_ii(&self) -> u64 {
+        match self.recurrence {
+            Some(recurrence) => self.rescale_inverval.max(recurrence.min_ii(1)),
+            None => self.rescale_inverval,
+        }
+    }
+}
+
+impl<A> Context for TiledIncrSum<A>
+where
+    A: DAMType + num::Num + MinMax + Copy,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        let num_tiles = self.inner_loop_bound / self.tile_size;
+        let tile_cycles = self.tile_size * self.init_inverval;
+        let rescale_inverval = self.rescale_ii();
+        for _i in 0..self.outer_loop_bound {
+            let mut temp_res = A::get_zero();
+            for t in 0..num_tiles {
+                let delta = match self.in_delta_stream.dequeue(&self.time) {
+                    Ok(in_delta) => in_delta.data,
+                    _ => {
+                        panic!("Reached unhandled case");
+                    }
+                };
+                let mut tile_sum = A::get_zero();
+                for _k in 0..self.tile_size {
+                    let in_curr_deq = self.in_curr_stream.dequeue(&self.time);
+                    match in_curr_deq {
+                        Ok(in_curr) => {
+                            tile_sum = tile_sum + in_curr.data;
+                            self.op_counter.add(OpKind::Add, 1);
+                        }
+                        _ => {
+                            panic!("Reached unhandled case");
+                        }
+                    }
+                    self.time.incr_cycles(self.init_inverval);
+                }
+                temp_res = temp_res * delta + tile_sum;
+                self.op_counter.add(OpKind::Mul, 1);
+                self.op_counter.add(OpKind::Add, 1);
+                // The rescale is hidden as long as it completes within one tile
+                if rescale_inverval > tile_cycles {
+                    self.time.incr_cycles(rescale_inverval - tile_cycles);
+                }
+
+                if t == num_tiles - 1 {
+                    let curr_time = self.time.tick();
+                    self.out_stream
+                        .enqueue(
+                            &self.time,
+                            ChannelElement::new(curr_time + self.latency, temp_res),
+                        )
+                        .unwrap();
+                }
+            }
+        }
+    }
+}
+
+#[context_macro]
+pub struct TiledIncrOutP<A: Clone> {
+    pub in_delta_stream: Receiver<A>, // one element per tile
+    pub in_curr_stream: Receiver<A>,  // one element per key
+    pub in_v_stream: Receiver<A>,     // should be an vector, but we assume d=1 for simplicity
+    pub out_stream: Sender<A>,
+    pub latency: u64,
+    pub init_inverval: u64,    // per element
+    pub rescale_inverval: u64, // II of the loop-carried o = o * delta + P(tile) V(tile)
+    pub tile_size: u64,
+    pub inner_loop_bound: u64,
+    pub outer_loop_bound: u64,
+    pub op_counter: OpCounter,
+    pub recurrence: Option<Recurrence>, // derives the rescale II when set, see 'rescale_ii'
+}
+
+impl<A: DAMType> TiledIncrOutP<A>
+where
+    TiledIncrOutP<A>: Context,
+{
+    pub fn new(
+        in_delta_stream: Receiver<A>,
+        in_curr_stream: Receiver<A>,
+        in_v_stream: Receiver<A>,
+        out_stream: Sender<A>,
+        latency: u64,
+        init_inverval: u64,
+        rescale_inverval: u64,
+        tile_size: u64,
+        inner_loop_bound: u64,
+        outer_loop_bound: u64,
+    ) -> Self {
+        assert!(inner_loop_bound.is_multiple_of(tile_size));
+        let incr_outer_p = TiledIncrOutP {
+            in_delta_stream,
+            in_curr_stream,
+            in_v_stream,
+            out_stream,
+            latency,
+            init_inverval,
+            rescale_inverval,
+            tile_size,
+            inner_loop_bound,
+            outer_loop_bound,
+            recurrence: None,
+            op_counter: Default::default(),
+            context_info: Default::default(),
+        };
+        (incr_outer_p.in_delta_stream).attach_receiver(&incr_outer_p);
+        (incr_outer_p.in_curr_stream).attach_receiver(&incr_outer_p);
+        (incr_outer_p.in_v_stream).attach_receiver(&incr_outer_p);
+        (incr_outer_p.out_stream).attach_sender(&incr_outer_p);
+
+        incr_outer_p
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Derive the rescale II from the multiply and add latencies instead of trusting
+    // 'rescale_inverval'
+    pub fn with_recurrence(mut self, recurrence: Recurrence) -> Self {
+        self.recurrence = Some(recurrence);
+        self
+    }
+
+    // II of the once-per-tile rescale, 'rescale_inverval' when the recurrence is not modeled
+    pub fn rescale_ii(&self) -> u64 {
+        match self.recurrence {
+            Some(recurrence) => self.rescale_inverval.max(recurrence.min_ii(1)),
+            None => self.rescale_inverval,
+        }
+    }
+}
+
+impl<A> Context for TiledIncrOutP<A>
+where
+    A: DAMType + num::Num + MinMax + Copy,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        let num_tiles = self.inner_loop_bound / self.tile_size;
+        let tile_cycles = self.tile_size * self.init_inverval;
+        let rescale_inverval = self.rescale_ii();
+        for _i in 0..self.outer_loop_bound {
+            let mut temp_res = A::get_zero();
+            for t in 0..num_tiles {
+                let delta = match self.in_delta_stream.dequeue(&self.time) {
+                    Ok(in_delta) => in_delta.data,
+                    _ => {
+                        panic!("Reached unhandled case");
+                    }
+                };
+                let mut tile_sum = A::get_zero();
+                for _k in 0..self.tile_size {
+                    let _ = self.in_curr_stream.peek_next(&self.time);
+                    let _ = self.in_v_stream.peek_next(&self.time);
+                    let in_curr_deq = self.in_curr_stream.dequeue(&self.time);
+                    let in_v_deq = self.in_v_stream.dequeue(&self.time);
+                    match (in_curr_deq, in_v_deq) {
+                        (Ok(in_curr), Ok(in_v)) => {
+                            tile_sum = tile_sum + in_curr.data * in_v.data;
+                            self.op_counter.add(OpKind::Mul, 1);
+                            self.op_counter.add(OpKind::Add, 1);
+                        }
+                        (_, _) => {
+                            panic!("Reached unhandled case");
+                        }
+                    }
+                    self.time.incr_cycles(self.init_inverval);
+                }
+                temp_res = temp_res * delta + tile_sum;
+                self.op_counter.add(OpKind::Mul, 1);
+                self.op_counter.add(OpKind::Add, 1);
+                // The rescale is hidden as long as it completes within one tile
+                if rescale_inverval > tile_cycles {
+                    self.time.incr_cycles(rescale_inverval - tile_cycles);
+                }
+
+                if t == num_tiles - 1 {
+                    let curr_time = self.time.tick();
+                    self.out_stream
+                        .enqueue(
+                            &self.time,
+                            ChannelElement::new(curr_time + self.latency, temp_res),
+                        )
+                        .unwrap();
+                }
+            }
+        }
+    }
+}
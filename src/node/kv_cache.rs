@@ -0,0 +1,171 @@
+use std::sync::{Arc, Mutex};
+
+use dam::context_tools::*;
+
+#[context_macro]
+pub struct KVCache<A: Clone> {
+    // K (or V) entries of every token seen so far. Each decode step appends the entry
+    // of the new token and then streams the whole cache, oldest entry first.
+    pub in_stream: Receiver<A>, // one new entry per step
+    pub out_stream: Sender<A>,
+    pub cache: Vec<A>, // starts with the prompt entries
+    pub capacity: usize,
+    pub latency: u64,        // read latency
+    pub append_latency: u64, // write of the new entry before the step can stream it
+    pub init_inverval: u64,  // one entry read per II
+    pub num_steps: u64,
+}
+
+impl<A: DAMType> KVCache<A>
+where
+    KVCache<A>: Context,
+{
+    pub fn new(
+        in_stream: Receiver<A>,
+        out_stream: Sender<A>,
+        cache: Vec<A>,
+        capacity: usize,
+        latency: u64,
+        append_latency: u64,
+        init_inverval: u64,
+        num_steps: u64,
+    ) -> Self {
+        assert!(cache.len() + (num_steps as usize) <= capacity);
+        let kv_cache = KVCache {
+            in_stream,
+            out_stream,
+            cache,
+            capacity,
+            latency,
+            append_latency,
+            init_inverval,
+            num_steps,
+            context_info: Default::default(),
+        };
+        (kv_cache.in_stream).attach_receiver(&kv_cache);
+        (kv_cache.out_stream).attach_sender(&kv_cache);
+
+        kv_cache
+    }
+}
+
+impl<A> Context for KVCache<A>
+where
+    A: DAMType,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        for _i in 0..self.num_steps {
+            let in_deq = self.in_stream.dequeue(&self.time);
+            match in_deq {
+                Ok(in_elem) => {
+                    self.cache.push(in_elem.data);
+                }
+                _ => {
+                    panic!("Reached unhandled case");
+                }
+            }
+            self.time.incr_cycles(self.append_latency);
+
+            for k in 0..self.cache.len() {
+                let curr_time = self.time.tick();
+                self.out_stream
+                    .enqueue(
+                        &self.time,
+                        ChannelElement::new(curr_time + self.latency, self.cache[k].clone()),
+                    )
+                    .unwrap();
+                self.time.incr_cycles(self.init_inverval);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TokenTiming<A> {
+    pub issue_cycle: u64,
+    pub done_cycle: u64,
+    pub output: A,
+}
+
+#[context_macro]
+pub struct DecodeDriver<A: Clone> {
+    // Stands in for the rest of the decoder layer: the q, k and v of token t + 1 are only
+    // issued once the attention output of token t is back, 'latency' cycles later.
+    pub tokens: Vec<(A, A, A)>, // (q, k, v) of every generated token
+    pub q_out: Sender<A>,
+    pub k_out: Sender<A>,
+    pub v_out: Sender<A>,
+    pub in_stream: Receiver<A>, // attention output, one per token
+    pub latency: u64,
+    timings: Arc<Mutex<Vec<TokenTiming<A>>>>,
+}
+
+impl<A: DAMType> DecodeDriver<A>
+where
+    DecodeDriver<A>: Context,
+{
+    pub fn new(
+        tokens: Vec<(A, A, A)>,
+        q_out: Sender<A>,
+        k_out: Sender<A>,
+        v_out: Sender<A>,
+        in_stream: Receiver<A>,
+        latency: u64,
+    ) -> Self {
+        let driver = DecodeDriver {
+            tokens,
+            q_out,
+            k_out,
+            v_out,
+            in_stream,
+            latency,
+            timings: Default::default(),
+            context_info: Default::default(),
+        };
+        (driver.q_out).attach_sender(&driver);
+        (driver.k_out).attach_sender(&driver);
+        (driver.v_out).attach_sender(&driver);
+        (driver.in_stream).attach_receiver(&driver);
+
+        driver
+    }
+
+    // Handle to read the per-token timings once the simulation has finished
+    pub fn timings(&self) -> Arc<Mutex<Vec<TokenTiming<A>>>> {
+        self.timings.clone()
+    }
+}
+
+impl<A> Context for DecodeDriver<A>
+where
+    A: DAMType,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        for (q, k, v) in self.tokens.clone() {
+            let issue_time = self.time.tick();
+            for (s, data) in [(&self.k_out, k), (&self.v_out, v), (&self.q_out, q)] {
+                s.enqueue(&self.time, ChannelElement::new(issue_time, data))
+                    .unwrap();
+            }
+
+            let in_deq = self.in_stream.dequeue(&self.time);
+            match in_deq {
+                Ok(in_elem) => {
+                    self.timings.lock().unwrap().push(TokenTiming {
+                        issue_cycle: issue_time.time(),
+                        done_cycle: self.time.tick().time(),
+                        output: in_elem.data,
+                    });
+                }
+                _ => {
+                    panic!("Reached unhandled case");
+                }
+            }
+            self.time.incr_cycles(self.latency);
+        }
+    }
+}
@@ -1,6 +1,21 @@
+pub mod bias;
+pub mod broadcast;
+pub mod exp_unit;
+pub mod flashattn_backward_op;
 pub mod flashattn_binary_op;
+pub mod flashattn_lse;
 pub mod flashattn_running_op;
+pub mod flashattn_tiled_op;
+pub mod kv_cache;
+pub mod normalizer;
+pub mod quant_convert;
+pub mod quant_qkt;
+pub mod rope;
+pub mod score_scale;
+pub mod simd_op;
+pub mod softmax_merge;
 pub mod streamattn_binary;
 pub mod streamattn_matvec;
 pub mod streamattn_qkt;
 pub mod streamattn_reduce;
+pub mod tree_reduce;
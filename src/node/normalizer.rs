@@ -0,0 +1,303 @@
+use dam::context_tools::*;
+
+use super::score_scale::ScoreScale;
+use crate::analysis::{
+    cost::{OpCounter, OpKind},
+    resource::UnitKind,
+};
+
+// Elementwise score activations that need no row-wise normalization
+#[derive(Clone)]
+pub enum ScoreActivation {
+    Sigmoid { bias: f64 }, // sigmoid(s + b), b = -ln(N) keeps the row mass close to softmax
+    ReluSquared { scale: f64 }, // relu(s)^2 * scale, scale = 1/N in ReLU attention
+}
+
+impl ScoreActivation {
+    pub fn eval<A: num::Float>(&self, x: A) -> A {
+        let cast = |v: f64| A::from(v).unwrap();
+        match *self {
+            ScoreActivation::Sigmoid { bias } => A::one() / (A::one() + (-(x + cast(bias))).exp()),
+            ScoreActivation::ReluSquared { scale } => {
+                let r = x.max(A::zero());
+                r * r * cast(scale)
+            }
+        }
+    }
+
+    // Operations per score
+    pub fn ops(&self) -> Vec<(OpKind, u64)> {
+        match self {
+            ScoreActivation::Sigmoid { .. } => {
+                vec![(OpKind::Add, 2), (OpKind::Exp, 1), (OpKind::Div, 1)]
+            }
+            ScoreActivation::ReluSquared { .. } => vec![(OpKind::Add, 1), (OpKind::Mul, 2)],
+        }
+    }
+
+    pub fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        match self {
+            ScoreActivation::Sigmoid { .. } => vec![
+                (UnitKind::Adder, 2),
+                (UnitKind::ExpUnit, 1),
+                (UnitKind::Divider, 1),
+            ],
+            ScoreActivation::ReluSquared { .. } => {
+                vec![(UnitKind::Adder, 1), (UnitKind::Multiplier, 2)]
+            }
+        }
+    }
+}
+
+#[context_macro]
+pub struct QKTAct<A: Clone> {
+    // q*k followed by an elementwise activation, the exp-free counterpart of QKTExp
+    pub q: Receiver<A>,           // operand 1: Vector
+    pub kt: Receiver<A>,          // operand 2: Vector
+    pub out_fifo: Vec<Sender<A>>, // list of output scalar FIFOs
+    pub activation: ScoreActivation,
+    pub latency: u64,       // pipeline depth
+    pub init_inverval: u64, // initiation interval
+    pub seq_len: u64,
+    pub outer_loop_bound: u64, // number of query rows (defaults to seq_len)
+    pub op_counter: OpCounter,
+    pub score_scale: ScoreScale, // applied to q*k before the activation
+}
+
+impl<A: DAMType> QKTAct<A>
+where
+    QKTAct<A>: Context,
+{
+    pub fn new(
+        q: Receiver<A>,
+        kt: Receiver<A>,
+        out_fifo: Vec<Sender<A>>,
+        activation: ScoreActivation,
+        latency: u64,       // pipeline depth
+        init_inverval: u64, // initiation interval
+        seq_len: u64,
+    ) -> Self {
+        let qkt_act = QKTAct {
+            q,
+            kt,
+            out_fifo,
+            activation,
+            latency,
+            init_inverval,
+            seq_len,
+            outer_loop_bound: seq_len,
+            op_counter: Default::default(),
+            score_scale: Default::default(),
+            context_info: Default::default(),
+        };
+        (qkt_act.q).attach_receiver(&qkt_act);
+        (qkt_act.kt).attach_receiver(&qkt_act);
+        for i in qkt_act.out_fifo.iter() {
+            i.attach_sender(&qkt_act);
+        }
+
+        qkt_act
+    }
+
+    pub fn with_outer_loop_bound(mut self, outer_loop_bound: u64) -> Self {
+        self.outer_loop_bound = outer_loop_bound;
+        self
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    pub fn with_score_scale(mut self, score_scale: ScoreScale) -> Self {
+        self.score_scale = score_scale;
+        self
+    }
+}
+
+impl<A> Context for QKTAct<A>
+where
+    A: DAMType + num::Float,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        let latency = self.latency + self.score_scale.latency;
+        for _i in 0..self.outer_loop_bound {
+            let q_deq = self.q.dequeue(&self.time);
+            match q_deq {
+                Ok(q) => {
+                    for _j in 0..self.seq_len {
+                        let kt_deq = self.kt.dequeue(&self.time);
+                        match kt_deq {
+                            Ok(kt) => {
+                                let score = self.score_scale.eval(q.data * kt.data);
+                                let res = self.activation.eval(score);
+                                self.op_counter.add(OpKind::Mul, 1);
+                                for (kind, n) in self.score_scale.ops() {
+                                    self.op_counter.add(kind, n);
+                                }
+                                for (kind, n) in self.activation.ops() {
+                                    self.op_counter.add(kind, n);
+                                }
+                                let curr_time = self.time.tick();
+
+                                for k in self.out_fifo.iter() {
+                                    let _ = k.wait_until_available(&self.time);
+                                }
+                                for k in self.out_fifo.iter() {
+                                    k.enqueue(
+                                        &self.time,
+                                        ChannelElement::new(curr_time + latency, res),
+                                    )
+                                    .unwrap();
+                                }
+
+                                self.time.incr_cycles(self.init_inverval);
+                                // initiation interval
+                            }
+                            _ => {
+                                panic!("Reached unhandled case");
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    panic!("Reached unhandled case");
+                }
+            }
+        }
+    }
+}
+
+// elu(x) + 1, positive for any x
+pub fn elu_feature<A: num::Float>(x: A) -> A {
+    if x > A::zero() {
+        x + A::one()
+    } else {
+        x.exp()
+    }
+}
+
+pub const NUM_FEATURES: usize = 2;
+
+// Feature map of linear attention: [elu(x) + 1, elu(-x) + 1].
+// With a single feature phi(q) would cancel between the projected state and the normalizer.
+pub fn linear_features<A: num::Float>(x: A) -> [A; NUM_FEATURES] {
+    [elu_feature(x), elu_feature(-x)]
+}
+
+#[context_macro]
+pub struct LinearAttn<A: Clone> {
+    // Causal linear attention with a running KV state: S += phi(k) v, z += phi(k),
+    // out = phi(q) . S / phi(q) . z, so token i attends to tokens 0..=i.
+    // K and V are streamed once instead of once per query row.
+    pub q: Receiver<A>,
+    pub k: Receiver<A>,
+    pub v: Receiver<A>,
+    pub out_stream: Sender<A>,
+    pub latency: u64,       // pipeline depth
+    pub init_inverval: u64, // initiation interval, bounded by the state update recurrence
+    pub loop_bound: u64,    // number of tokens
+    pub op_counter: OpCounter,
+    pub score_scale: ScoreScale, // applied to q, which scales q k for the linear scales
+}
+
+impl<A: DAMType> LinearAttn<A>
+where
+    LinearAttn<A>: Context,
+{
+    pub fn new(
+        q: Receiver<A>,
+        k: Receiver<A>,
+        v: Receiver<A>,
+        out_stream: Sender<A>,
+        latency: u64,       // pipeline depth
+        init_inverval: u64, // initiation interval
+        loop_bound: u64,
+    ) -> Self {
+        let linear_attn = LinearAttn {
+            q,
+            k,
+            v,
+            out_stream,
+            latency,
+            init_inverval,
+            loop_bound,
+            op_counter: Default::default(),
+            score_scale: Default::default(),
+            context_info: Default::default(),
+        };
+        (linear_attn.q).attach_receiver(&linear_attn);
+        (linear_attn.k).attach_receiver(&linear_attn);
+        (linear_attn.v).attach_receiver(&linear_attn);
+        (linear_attn.out_stream).attach_sender(&linear_attn);
+
+        linear_attn
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    pub fn with_score_scale(mut self, score_scale: ScoreScale) -> Self {
+        self.score_scale = score_scale;
+        self
+    }
+}
+
+impl<A> Context for LinearAttn<A>
+where
+    A: DAMType + num::Float,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        let latency = self.latency + self.score_scale.latency;
+        let mut state = [A::zero(); NUM_FEATURES];
+        let mut norm = [A::zero(); NUM_FEATURES];
+        for _i in 0..self.loop_bound {
+            let _ = self.q.peek_next(&self.time);
+            let _ = self.k.peek_next(&self.time);
+            let _ = self.v.peek_next(&self.time);
+            let q_deq = self.q.dequeue(&self.time);
+            let k_deq = self.k.dequeue(&self.time);
+            let v_deq = self.v.dequeue(&self.time);
+            match (q_deq, k_deq, v_deq) {
+                (Ok(q), Ok(k), Ok(v)) => {
+                    let phi_k = linear_features(k.data);
+                    let phi_q = linear_features(self.score_scale.eval(q.data));
+                    let mut num = A::zero();
+                    let mut den = A::zero();
+                    for f in 0..NUM_FEATURES {
+                        state[f] = state[f] + phi_k[f] * v.data;
+                        norm[f] = norm[f] + phi_k[f];
+                        num = num + phi_q[f] * state[f];
+                        den = den + phi_q[f] * norm[f];
+                    }
+                    let res = num / den;
+                    // four feature evaluations (compare, exp, add), per feature two state updates
+                    // and two projections, and a divide
+                    for (kind, n) in self.score_scale.ops() {
+                        self.op_counter.add(kind, n);
+                    }
+                    self.op_counter.add(OpKind::Exp, 4);
+                    self.op_counter.add(OpKind::Add, 12);
+                    self.op_counter.add(OpKind::Mul, 6);
+                    self.op_counter.add(OpKind::Div, 1);
+
+                    let curr_time = self.time.tick();
+                    self.out_stream
+                        .enqueue(&self.time, ChannelElement::new(curr_time + latency, res))
+                        .unwrap();
+                    self.time.incr_cycles(self.init_inverval);
+                    // initiation interval
+                }
+                (_, _, _) => {
+                    panic!("Reached unhandled case");
+                }
+            }
+        }
+    }
+}
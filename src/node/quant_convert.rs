@@ -0,0 +1,221 @@
+use dam::context_tools::*;
+
+use super::quant_qkt::{fixed_multiplier, rounding_shift_wide, MULTIPLIER_SHIFT};
+
+#[context_macro]
+pub struct Quantize<A: Clone, B: Clone> {
+    // Float -> Integer: round(x / scale), saturated to the range of B
+    pub in_stream: Receiver<A>,
+    pub out_stream: Sender<B>,
+    pub scale: f64,
+    pub latency: u64,       // pipeline depth
+    pub init_inverval: u64, // initiation interval
+    pub loop_bound: u64,
+}
+
+impl<A: DAMType, B: DAMType> Quantize<A, B>
+where
+    Quantize<A, B>: Context,
+{
+    pub fn new(
+        in_stream: Receiver<A>,
+        out_stream: Sender<B>,
+        scale: f64,
+        latency: u64,       // pipeline depth
+        init_inverval: u64, // initiation interval
+        loop_bound: u64,
+    ) -> Self {
+        let quantize = Quantize {
+            in_stream,
+            out_stream,
+            scale,
+            latency,
+            init_inverval,
+            loop_bound,
+            context_info: Default::default(),
+        };
+        (quantize.in_stream).attach_receiver(&quantize);
+        (quantize.out_stream).attach_sender(&quantize);
+
+        quantize
+    }
+}
+
+impl<A, B> Context for Quantize<A, B>
+where
+    A: DAMType + num::Float,
+    B: DAMType + num::PrimInt,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        let lo = B::min_value().to_f64().unwrap();
+        let hi = B::max_value().to_f64().unwrap();
+        for _i in 0..self.loop_bound {
+            let in_deq = self.in_stream.dequeue(&self.time);
+            match in_deq {
+                Ok(in_elem) => {
+                    let scaled = (in_elem.data.to_f64().unwrap() / self.scale).round();
+                    let out_data = B::from(scaled.clamp(lo, hi)).unwrap();
+                    let curr_time = self.time.tick();
+                    self.out_stream
+                        .enqueue(
+                            &self.time,
+                            ChannelElement::new(curr_time + self.latency, out_data),
+                        )
+                        .unwrap();
+                }
+                _ => {
+                    panic!("Reached unhandled case");
+                }
+            }
+            self.time.incr_cycles(self.init_inverval);
+        }
+    }
+}
+
+#[context_macro]
+pub struct Dequantize<A: Clone, B: Clone> {
+    // Integer -> Float: x * scale
+    pub in_stream: Receiver<A>,
+    pub out_stream: Sender<B>,
+    pub scale: f64,
+    pub latency: u64,       // pipeline depth
+    pub init_inverval: u64, // initiation interval
+    pub loop_bound: u64,
+}
+
+impl<A: DAMType, B: DAMType> Dequantize<A, B>
+where
+    Dequantize<A, B>: Context,
+{
+    pub fn new(
+        in_stream: Receiver<A>,
+        out_stream: Sender<B>,
+        scale: f64,
+        latency: u64,       // pipeline depth
+        init_inverval: u64, // initiation interval
+        loop_bound: u64,
+    ) -> Self {
+        let dequantize = Dequantize {
+            in_stream,
+            out_stream,
+            scale,
+            latency,
+            init_inverval,
+            loop_bound,
+            context_info: Default::default(),
+        };
+        (dequantize.in_stream).attach_receiver(&dequantize);
+        (dequantize.out_stream).attach_sender(&dequantize);
+
+        dequantize
+    }
+}
+
+impl<A, B> Context for Dequantize<A, B>
+where
+    A: DAMType + num::PrimInt,
+    B: DAMType + num::Float,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        for _i in 0..self.loop_bound {
+            let in_deq = self.in_stream.dequeue(&self.time);
+            match in_deq {
+                Ok(in_elem) => {
+                    let out_data = B::from(in_elem.data.to_f64().unwrap() * self.scale).unwrap();
+                    let curr_time = self.time.tick();
+                    self.out_stream
+                        .enqueue(
+                            &self.time,
+                            ChannelElement::new(curr_time + self.latency, out_data),
+                        )
+                        .unwrap();
+                }
+                _ => {
+                    panic!("Reached unhandled case");
+                }
+            }
+            self.time.incr_cycles(self.init_inverval);
+        }
+    }
+}
+
+#[context_macro]
+pub struct Requantize<A: Clone, B: Clone> {
+    // Integer -> Integer: (x * M) >> MULTIPLIER_SHIFT with rounding, saturated to the range of B
+    // M is the fixed-point multiplier derived from the real-valued rescale factor
+    pub in_stream: Receiver<A>,
+    pub out_stream: Sender<B>,
+    pub multiplier: i64,
+    pub latency: u64,       // pipeline depth
+    pub init_inverval: u64, // initiation interval
+    pub loop_bound: u64,
+}
+
+impl<A: DAMType, B: DAMType> Requantize<A, B>
+where
+    Requantize<A, B>: Context,
+{
+    pub fn new(
+        in_stream: Receiver<A>,
+        out_stream: Sender<B>,
+        scale: f64,         // real-valued rescale factor (in_scale / out_scale)
+        latency: u64,       // pipeline depth
+        init_inverval: u64, // initiation interval
+        loop_bound: u64,
+    ) -> Self {
+        let requantize = Requantize {
+            in_stream,
+            out_stream,
+            multiplier: fixed_multiplier(scale),
+            latency,
+            init_inverval,
+            loop_bound,
+            context_info: Default::default(),
+        };
+        (requantize.in_stream).attach_receiver(&requantize);
+        (requantize.out_stream).attach_sender(&requantize);
+
+        requantize
+    }
+}
+
+impl<A, B> Context for Requantize<A, B>
+where
+    A: DAMType + num::PrimInt,
+    B: DAMType + num::PrimInt,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        let lo = B::min_value().to_i128().unwrap();
+        let hi = B::max_value().to_i128().unwrap();
+        for _i in 0..self.loop_bound {
+            let in_deq = self.in_stream.dequeue(&self.time);
+            match in_deq {
+                Ok(in_elem) => {
+                    // Widened so large accumulators saturate instead of overflowing
+                    let scaled = rounding_shift_wide(
+                        in_elem.data.to_i128().unwrap() * (self.multiplier as i128),
+                        MULTIPLIER_SHIFT,
+                    );
+                    let out_data = B::from(scaled.clamp(lo, hi)).unwrap();
+                    let curr_time = self.time.tick();
+                    self.out_stream
+                        .enqueue(
+                            &self.time,
+                            ChannelElement::new(curr_time + self.latency, out_data),
+                        )
+                        .unwrap();
+                }
+                _ => {
+                    panic!("Reached unhandled case");
+                }
+            }
+            self.time.incr_cycles(self.init_inverval);
+        }
+    }
+}
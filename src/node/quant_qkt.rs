@@ -0,0 +1,197 @@
+use dam::context_tools::*;
+
+// log2(e) in Q2.30
+const LOG2E_Q30: i64 = 1549082005;
+// 2^f ~= 1 + f * (C1 + C2 * f) on [0, 1), exact at both ends. Coefficients in Q2.30
+const POW2_C1_Q30: i64 = 704911507;
+const POW2_C2_Q30: i64 = 368830317;
+
+pub const MULTIPLIER_SHIFT: u32 = 30;
+
+pub fn rounding_shift(x: i64, shift: u32) -> i64 {
+    if shift == 0 {
+        x
+    } else if shift >= 63 {
+        0
+    } else {
+        (x + (1 << (shift - 1))) >> shift
+    }
+}
+
+// Same rounding on a 128-bit product, for wide accumulators times a Q30 multiplier
+pub fn rounding_shift_wide(x: i128, shift: u32) -> i128 {
+    if shift == 0 {
+        x
+    } else if shift >= 127 {
+        0
+    } else {
+        (x + (1 << (shift - 1))) >> shift
+    }
+}
+
+// Converts a real-valued scale into an integer multiplier applied as (x * M) >> MULTIPLIER_SHIFT
+pub fn fixed_multiplier(scale: f64) -> i64 {
+    (scale * ((1_i64 << MULTIPLIER_SHIFT) as f64)).round() as i64
+}
+
+pub enum FixedExpMethod {
+    Polynomial,
+    Lut { index_bits: u32, table: Vec<i64> },
+}
+
+// Fixed-point exp unit: exp(x) = 2^(x * log2(e)) = 2^n * 2^f
+// The integer part n is a shift, the fractional part 2^f is approximated by a polynomial or a LUT.
+// Inputs and outputs are signed fixed-point values with 'frac_bits' fractional bits.
+pub struct FixedExp {
+    pub frac_bits: u32,
+    pub method: FixedExpMethod,
+}
+
+impl FixedExp {
+    pub fn polynomial(frac_bits: u32) -> Self {
+        FixedExp {
+            frac_bits,
+            method: FixedExpMethod::Polynomial,
+        }
+    }
+
+    pub fn lut(frac_bits: u32, index_bits: u32) -> Self {
+        assert!(index_bits <= frac_bits);
+        let entries = 1_u64 << index_bits;
+        let one = (1_i64 << frac_bits) as f64;
+        // Each entry holds 2^f at the midpoint of its interval to halve the truncation error
+        let table = (0..entries)
+            .map(|i| ((((i as f64) + 0.5) / (entries as f64)).exp2() * one).round() as i64)
+            .collect();
+        FixedExp {
+            frac_bits,
+            method: FixedExpMethod::Lut { index_bits, table },
+        }
+    }
+
+    fn pow2_frac(&self, f: i64) -> i64 {
+        match &self.method {
+            FixedExpMethod::Polynomial => {
+                let one = 1_i64 << self.frac_bits;
+                let inner = POW2_C1_Q30 + rounding_shift(POW2_C2_Q30 * f, self.frac_bits);
+                one + rounding_shift(f * inner, MULTIPLIER_SHIFT)
+            }
+            FixedExpMethod::Lut { index_bits, table } => {
+                table[(f >> (self.frac_bits - index_bits)) as usize]
+            }
+        }
+    }
+
+    pub fn eval(&self, x: i64) -> i64 {
+        let y = rounding_shift(x * LOG2E_Q30, MULTIPLIER_SHIFT);
+        let n = y >> self.frac_bits;
+        let f = y - (n << self.frac_bits);
+        let p = self.pow2_frac(f);
+        if n >= 0 {
+            p.checked_shl(n as u32)
+                .filter(|v| (*v >> n) == p)
+                .unwrap_or(i64::MAX)
+        } else {
+            rounding_shift(p, (-n) as u32)
+        }
+    }
+}
+
+#[context_macro]
+pub struct QKTExpFixed {
+    pub q: Receiver<i8>,            // operand 1: int8 vector of 'head_dim' elements
+    pub kt: Receiver<i8>,           // operand 2: int8 vector of 'head_dim' elements
+    pub out_fifo: Vec<Sender<i32>>, // list of output scalar FIFOs (fixed-point exp scores)
+    pub score_multiplier: i64,      // int32 accumulator -> fixed-point score (scale_q * scale_k)
+    pub exp: FixedExp,
+    pub latency: u64,       // pipeline depth
+    pub init_inverval: u64, // initiation interval
+    pub seq_len: u64,
+    pub head_dim: u64,
+}
+
+impl QKTExpFixed {
+    pub fn new(
+        q: Receiver<i8>,
+        kt: Receiver<i8>,
+        out_fifo: Vec<Sender<i32>>,
+        in_scale: f64, // real value of one LSB of the int32 accumulator
+        exp: FixedExp,
+        latency: u64,
+        init_inverval: u64,
+        seq_len: u64,
+        head_dim: u64,
+    ) -> Self {
+        let score_multiplier = fixed_multiplier(in_scale * ((1_i64 << exp.frac_bits) as f64));
+        let qkt_exp = QKTExpFixed {
+            q,
+            kt,
+            out_fifo,
+            score_multiplier,
+            exp,
+            latency,
+            init_inverval,
+            seq_len,
+            head_dim,
+            context_info: Default::default(),
+        };
+        (qkt_exp.q).attach_receiver(&qkt_exp);
+        (qkt_exp.kt).attach_receiver(&qkt_exp);
+        for i in qkt_exp.out_fifo.iter() {
+            i.attach_sender(&qkt_exp);
+        }
+
+        qkt_exp
+    }
+}
+
+impl Context for QKTExpFixed {
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        for _i in 0..self.seq_len {
+            let mut q_vec = Vec::with_capacity(self.head_dim as usize);
+            for _d in 0..self.head_dim {
+                match self.q.dequeue(&self.time) {
+                    Ok(q) => q_vec.push(q.data as i32),
+                    _ => {
+                        panic!("Reached unhandled case");
+                    }
+                }
+            }
+            for _j in 0..self.seq_len {
+                // int8 x int8 products accumulated in int32 by a 'head_dim' wide dot-product unit
+                let mut accum: i32 = 0;
+                for q_elem in q_vec.iter() {
+                    match self.kt.dequeue(&self.time) {
+                        Ok(kt) => {
+                            accum = accum.wrapping_add(q_elem * (kt.data as i32));
+                        }
+                        _ => {
+                            panic!("Reached unhandled case");
+                        }
+                    }
+                }
+                let score =
+                    rounding_shift((accum as i64) * self.score_multiplier, MULTIPLIER_SHIFT);
+                let qkt_exp_res =
+                    self.exp.eval(score).clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+                let curr_time = self.time.tick();
+
+                for k in self.out_fifo.iter() {
+                    let _ = k.wait_until_available(&self.time);
+                }
+                for k in self.out_fifo.iter() {
+                    k.enqueue(
+                        &self.time,
+                        ChannelElement::new(curr_time + self.latency, qkt_exp_res),
+                    )
+                    .unwrap();
+                }
+
+                self.time.incr_cycles(self.init_inverval);
+                // initiation interval
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use dam::context_tools::*;
+use ndarray::Array1;
+
+use crate::analysis::cost::{OpCounter, OpKind};
+
+// Rotation angle of pair m (elements 2m, 2m+1) at 'position': position * base^(-2m/d)
+pub fn rope_angle(base: f64, head_dim: usize, pair: usize, position: u64) -> f64 {
+    (position as f64) * base.powf(-2_f64 * (pair as f64) / (head_dim as f64))
+}
+
+#[context_macro]
+pub struct Rope<A: Clone> {
+    // Rotary positional embedding of Q or K vectors before they reach the QK^T stage.
+    // Vector t sits at position t % seq_len, so a K stream re-streamed per query row keeps its positions.
+    // The sin/cos pairs come from a table, each element pair costs four multiplies and two adds.
+    pub in_stream: Receiver<Array1<A>>,
+    pub out_stream: Sender<Array1<A>>,
+    pub base: f64,          // base frequency, 10000 in most models
+    pub latency: u64,       // pipeline depth
+    pub init_inverval: u64, // initiation interval
+    pub seq_len: u64,
+    pub loop_bound: u64, // number of vectors
+    pub op_counter: OpCounter,
+    pub positions: Vec<u64>, // position of every vector, overrides t % seq_len when set
+}
+
+impl<A: DAMType> Rope<A>
+where
+    Rope<A>: Context,
+{
+    pub fn new(
+        in_stream: Receiver<Array1<A>>,
+        out_stream: Sender<Array1<A>>,
+        base: f64,
+        latency: u64,       // pipeline depth
+        init_inverval: u64, // initiation interval
+        seq_len: u64,
+        loop_bound: u64,
+    ) -> Self {
+        let rope = Rope {
+            in_stream,
+            out_stream,
+            base,
+            latency,
+            init_inverval,
+            seq_len,
+            loop_bound,
+            op_counter: Default::default(),
+            positions: vec![],
+            context_info: Default::default(),
+        };
+        (rope.in_stream).attach_receiver(&rope);
+        (rope.out_stream).attach_sender(&rope);
+
+        rope
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Explicit positions, e.g. the key stream of a mask (see mask::AttnMask::key_stream)
+    pub fn with_positions(mut self, positions: Vec<u64>) -> Self {
+        self.loop_bound = positions.len() as u64;
+        self.positions = positions;
+        self
+    }
+}
+
+impl<A> Context for Rope<A>
+where
+    A: DAMType + num::Float,
+    Array1<A>: DAMType,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        for t in 0..self.loop_bound {
+            let position = self
+                .positions
+                .get(t as usize)
+                .copied()
+                .unwrap_or(t % self.seq_len);
+            let in_deq = self.in_stream.dequeue(&self.time);
+            match in_deq {
+                Ok(in_elem) => {
+                    let x = in_elem.data;
+                    let head_dim = x.len();
+                    assert!(head_dim % 2 == 0);
+                    let mut rotated = x.clone();
+                    for m in 0..(head_dim / 2) {
+                        let angle = rope_angle(self.base, head_dim, m, position);
+                        let (sin, cos) = (
+                            <A as num::NumCast>::from(angle.sin()).unwrap(),
+                            <A as num::NumCast>::from(angle.cos()).unwrap(),
+                        );
+                        let (x0, x1) = (x[2 * m], x[2 * m + 1]);
+                        rotated[2 * m] = x0 * cos - x1 * sin;
+                        rotated[2 * m + 1] = x0 * sin + x1 * cos;
+                    }
+                    self.op_counter.add(OpKind::Mul, 2 * head_dim as u64);
+                    self.op_counter.add(OpKind::Add, head_dim as u64);
+
+                    let curr_time = self.time.tick();
+                    self.out_stream
+                        .enqueue(
+                            &self.time,
+                            ChannelElement::new(curr_time + self.latency, rotated),
+                        )
+                        .unwrap();
+                    self.time.incr_cycles(self.init_inverval);
+                    // initiation interval
+                }
+                _ => {
+                    panic!("Reached unhandled case");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::analysis::{cost::OpKind, resource::UnitKind};
+
+#[derive(Clone)]
+pub enum ScoreScaleType {
+    Identity,                          // exp(q*k), the unscaled scores of the original graphs
+    Scale { factor: f64 },             // s * factor, 1/sqrt(d) or 1/temperature
+    SoftCap { factor: f64, cap: f64 }, // cap * tanh(s * factor / cap), logit soft-capping as in Gemma 2
+}
+
+// Hardware model of the stage between q*k and the exp unit.
+// 'latency' is added on top of the pipeline depth of the node that instantiates it.
+#[derive(Clone)]
+pub struct ScoreScale {
+    pub unit_type: ScoreScaleType,
+    pub latency: u64, // pipeline depth
+}
+
+impl Default for ScoreScale {
+    fn default() -> Self {
+        ScoreScale {
+            unit_type: ScoreScaleType::Identity,
+            latency: 0,
+        }
+    }
+}
+
+impl ScoreScale {
+    pub fn scale(factor: f64, latency: u64) -> Self {
+        ScoreScale {
+            unit_type: ScoreScaleType::Scale { factor },
+            latency,
+        }
+    }
+
+    // Standard scaled dot-product attention
+    pub fn inv_sqrt_d(head_dim: u64, latency: u64) -> Self {
+        ScoreScale::scale(1_f64 / (head_dim as f64).sqrt(), latency)
+    }
+
+    pub fn temperature(temperature: f64, latency: u64) -> Self {
+        ScoreScale::scale(1_f64 / temperature, latency)
+    }
+
+    pub fn soft_cap(factor: f64, cap: f64, latency: u64) -> Self {
+        ScoreScale {
+            unit_type: ScoreScaleType::SoftCap { factor, cap },
+            latency,
+        }
+    }
+
+    pub fn eval<A: num::Float>(&self, x: A) -> A {
+        let cast = |v: f64| A::from(v).unwrap();
+        match self.unit_type {
+            ScoreScaleType::Identity => x,
+            ScoreScaleType::Scale { factor } => x * cast(factor),
+            ScoreScaleType::SoftCap { factor, cap } => cast(cap) * (x * cast(factor / cap)).tanh(),
+        }
+    }
+
+    // d eval(x) / dx, for the backward pass
+    pub fn grad<A: num::Float>(&self, x: A) -> A {
+        let cast = |v: f64| A::from(v).unwrap();
+        match self.unit_type {
+            ScoreScaleType::Identity => A::one(),
+            ScoreScaleType::Scale { factor } => cast(factor),
+            ScoreScaleType::SoftCap { factor, cap } => {
+                let t = (x * cast(factor / cap)).tanh();
+                cast(factor) * (A::one() - t * t)
+            }
+        }
+    }
+
+    // Operations per gradient on top of 'ops', the soft-cap reuses its tanh
+    pub fn grad_ops(&self) -> Vec<(OpKind, u64)> {
+        match self.unit_type {
+            ScoreScaleType::Identity | ScoreScaleType::Scale { .. } => vec![],
+            ScoreScaleType::SoftCap { .. } => vec![(OpKind::Mul, 2), (OpKind::Add, 1)],
+        }
+    }
+
+    // Operations per score: factor / cap is a constant,
+    // tanh(y) = (e^2y - 1) / (e^2y + 1) takes an exp, two adds and a divide
+    pub fn ops(&self) -> Vec<(OpKind, u64)> {
+        match self.unit_type {
+            ScoreScaleType::Identity => vec![],
+            ScoreScaleType::Scale { .. } => vec![(OpKind::Mul, 1)],
+            ScoreScaleType::SoftCap { .. } => vec![
+                (OpKind::Mul, 2),
+                (OpKind::Exp, 1),
+                (OpKind::Add, 2),
+                (OpKind::Div, 1),
+            ],
+        }
+    }
+
+    pub fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        match self.unit_type {
+            ScoreScaleType::Identity => vec![],
+            ScoreScaleType::Scale { .. } => vec![(UnitKind::Multiplier, 1)],
+            ScoreScaleType::SoftCap { .. } => vec![
+                (UnitKind::Multiplier, 2),
+                (UnitKind::ExpUnit, 1),
+                (UnitKind::Adder, 2),
+                (UnitKind::Divider, 1),
+            ],
+        }
+    }
+}
@@ -0,0 +1,807 @@
+use dam::context_tools::*;
+use ndarray::Array1;
+
+use super::{
+    exp_unit::ExpUnit, flashattn_running_op::Recurrence, score_scale::ScoreScale,
+    streamattn_binary::BinaryOpType, streamattn_reduce::MinMax, tree_reduce::tree_levels,
+};
+use crate::analysis::cost::{OpCounter, OpKind};
+
+// Vectorized variants of the elementwise and running ops: every token is a packed Array1 of
+// 'lanes' (W) consecutive elements, and a node consumes and produces one token per II.
+// Per-row scalars (q, the running max rescale, the row sum) stay scalar tokens.
+// Loop bounds count elements, so rows are 'inner_loop_bound / lanes' tokens long.
+// The builder options match the scalar nodes; row lengths must be multiples of W.
+
+fn check_packed_lengths(row_lengths: &[u64], lanes: u64) {
+    assert!(row_lengths.iter().all(|l| l.is_multiple_of(lanes)));
+}
+
+// Tokens of row 'i'
+fn row_tokens(row_lengths: &[u64], default: u64, i: u64, lanes: u64) -> u64 {
+    row_lengths.get(i as usize).copied().unwrap_or(default) / lanes
+}
+
+// Depth of the W-input adder (or comparator) tree in front of the loop-carried update,
+// built from the recurrence adders when it is modeled
+pub(crate) fn adder_tree_latency(lanes: u64, recurrence: Option<Recurrence>) -> u64 {
+    tree_levels(lanes) * recurrence.map_or(1, |r| r.add_latency)
+}
+
+#[context_macro]
+pub struct SimdQKTExp<A: Clone> {
+    pub q: Receiver<A>,                   // operand 1: one scalar per row
+    pub kt: Receiver<Array1<A>>,          // operand 2: W keys per token
+    pub out_fifo: Vec<Sender<Array1<A>>>, // list of output FIFOs, W scores per token
+    pub latency: u64,                     // pipeline depth
+    pub init_inverval: u64,               // initiation interval
+    pub lanes: u64,
+    pub seq_len: u64,
+    pub outer_loop_bound: u64,
+    pub exp_unit: ExpUnit,
+    pub op_counter: OpCounter,
+    pub row_lengths: Vec<u64>,             // overrides seq_len when set
+    pub score_scale: ScoreScale,           // applied to q*k before the exp, one per lane
+    pub bias: Option<Receiver<Array1<A>>>, // W additive score biases per token
+    pub bias_latency: u64,
+}
+
+impl<A: DAMType> SimdQKTExp<A>
+where
+    SimdQKTExp<A>: Context,
+{
+    pub fn new(
+        q: Receiver<A>,
+        kt: Receiver<Array1<A>>,
+        out_fifo: Vec<Sender<Array1<A>>>,
+        latency: u64,
+        init_inverval: u64,
+        lanes: u64,
+        seq_len: u64,
+        outer_loop_bound: u64,
+    ) -> Self {
+        assert!(seq_len.is_multiple_of(lanes));
+        let qkt_exp = SimdQKTExp {
+            q,
+            kt,
+            out_fifo,
+            latency,
+            init_inverval,
+            lanes,
+            seq_len,
+            outer_loop_bound,
+            exp_unit: Default::default(),
+            row_lengths: vec![],
+            score_scale: Default::default(),
+            bias: None,
+            bias_latency: 0,
+            op_counter: Default::default(),
+            context_info: Default::default(),
+        };
+        (qkt_exp.q).attach_receiver(&qkt_exp);
+        (qkt_exp.kt).attach_receiver(&qkt_exp);
+        for i in qkt_exp.out_fifo.iter() {
+            i.attach_sender(&qkt_exp);
+        }
+
+        qkt_exp
+    }
+
+    pub fn with_outer_loop_bound(mut self, outer_loop_bound: u64) -> Self {
+        self.outer_loop_bound = outer_loop_bound;
+        self
+    }
+
+    pub fn with_exp_unit(mut self, exp_unit: ExpUnit) -> Self {
+        self.exp_unit = exp_unit;
+        self
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Rows of different lengths, e.g. decode steps over a growing KV cache
+    pub fn with_row_lengths(mut self, row_lengths: Vec<u64>) -> Self {
+        check_packed_lengths(&row_lengths, self.lanes);
+        self.outer_loop_bound = row_lengths.len() as u64;
+        self.row_lengths = row_lengths;
+        self
+    }
+
+    pub fn with_score_scale(mut self, score_scale: ScoreScale) -> Self {
+        self.score_scale = score_scale;
+        self
+    }
+
+    // Adds a packed bias stream to q*k before the exp, costing an adder stage of 'bias_latency'
+    pub fn with_bias(mut self, bias: Receiver<Array1<A>>, bias_latency: u64) -> Self {
+        bias.attach_receiver(&self);
+        self.bias = Some(bias);
+        self.bias_latency = bias_latency;
+        self
+    }
+}
+
+impl<A> Context for SimdQKTExp<A>
+where
+    A: DAMType + num::Float,
+    Array1<A>: DAMType,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        let latency =
+            self.latency + self.exp_unit.latency + self.score_scale.latency + self.bias_latency;
+        let init_inverval = self.init_inverval.max(self.exp_unit.init_inverval);
+        for i in 0..self.outer_loop_bound {
+            let num_tokens = row_tokens(&self.row_lengths, self.seq_len, i, self.lanes);
+            let q_deq = self.q.dequeue(&self.time);
+            match q_deq {
+                Ok(q) => {
+                    for _j in 0..num_tokens {
+                        let kt_deq = self.kt.dequeue(&self.time);
+                        match kt_deq {
+                            Ok(kt) => {
+                                let mut score = kt.data.mapv(|k| self.score_scale.eval(q.data * k));
+                                for (kind, n) in self.score_scale.ops() {
+                                    self.op_counter.add(kind, n * self.lanes);
+                                }
+                                if let Some(bias) = &self.bias {
+                                    match bias.dequeue(&self.time) {
+                                        Ok(b) => score = score + b.data,
+                                        _ => {
+                                            panic!("Reached unhandled case");
+                                        }
+                                    }
+                                    self.op_counter.add(OpKind::Add, self.lanes);
+                                }
+                                let res = score.mapv(|s| self.exp_unit.eval(s));
+                                self.op_counter.add(OpKind::Mul, self.lanes);
+                                self.op_counter.add(OpKind::Exp, self.lanes);
+                                let curr_time = self.time.tick();
+
+                                for k in self.out_fifo.iter() {
+                                    let _ = k.wait_until_available(&self.time);
+                                }
+                                for k in self.out_fifo.iter() {
+                                    k.enqueue(
+                                        &self.time,
+                                        ChannelElement::new(curr_time + latency, res.clone()),
+                                    )
+                                    .unwrap();
+                                }
+
+                                self.time.incr_cycles(init_inverval);
+                                // initiation interval
+                            }
+                            _ => {
+                                panic!("Reached unhandled case");
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    panic!("Reached unhandled case");
+                }
+            }
+        }
+    }
+}
+
+#[context_macro]
+pub struct SimdIncrMax<A: Clone> {
+    pub in_stream: Receiver<Array1<A>>,
+    pub delta_out_stream: Vec<Sender<A>>, // one exp(m_old - m_new) per token
+    pub curr_out_stream: Vec<Sender<Array1<A>>>, // exp(s - m_new) for the W elements of a token
+    pub latency: u64,
+    pub init_inverval: u64,
+    pub lanes: u64,
+    pub inner_loop_bound: u64,
+    pub outer_loop_bound: u64,
+    pub exp_unit: ExpUnit,
+    pub op_counter: OpCounter,
+    pub row_lengths: Vec<u64>, // overrides inner_loop_bound when set
+}
+
+impl<A: Clone> SimdIncrMax<A> {
+    // Latency from a token to its outputs, including the log2(W) comparator tree of the max
+    pub fn total_latency(&self) -> u64 {
+        self.latency + self.exp_unit.latency + adder_tree_latency(self.lanes, None)
+    }
+}
+
+impl<A: DAMType> SimdIncrMax<A>
+where
+    SimdIncrMax<A>: Context,
+{
+    pub fn new(
+        in_stream: Receiver<Array1<A>>,
+        delta_out_stream: Vec<Sender<A>>,
+        curr_out_stream: Vec<Sender<Array1<A>>>,
+        latency: u64,
+        init_inverval: u64,
+        lanes: u64,
+        inner_loop_bound: u64,
+        outer_loop_bound: u64,
+    ) -> Self {
+        assert!(inner_loop_bound.is_multiple_of(lanes));
+        let incr_max = SimdIncrMax {
+            in_stream,
+            delta_out_stream,
+            curr_out_stream,
+            latency,
+            init_inverval,
+            lanes,
+            inner_loop_bound,
+            outer_loop_bound,
+            exp_unit: Default::default(),
+            row_lengths: vec![],
+            op_counter: Default::default(),
+            context_info: Default::default(),
+        };
+        (incr_max.in_stream).attach_receiver(&incr_max);
+        for i in incr_max.delta_out_stream.iter() {
+            i.attach_sender(&incr_max);
+        }
+        for i in incr_max.curr_out_stream.iter() {
+            i.attach_sender(&incr_max);
+        }
+
+        incr_max
+    }
+
+    pub fn with_exp_unit(mut self, exp_unit: ExpUnit) -> Self {
+        self.exp_unit = exp_unit;
+        self
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Rows of different lengths, e.g. decode steps over a growing KV cache
+    pub fn with_row_lengths(mut self, row_lengths: Vec<u64>) -> Self {
+        check_packed_lengths(&row_lengths, self.lanes);
+        self.outer_loop_bound = row_lengths.len() as u64;
+        self.row_lengths = row_lengths;
+        self
+    }
+}
+
+impl<A> Context for SimdIncrMax<A>
+where
+    A: DAMType + num::Float + MinMax + Copy,
+    Array1<A>: DAMType,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        let latency = self.total_latency();
+        let init_inverval = self.init_inverval.max(self.exp_unit.init_inverval);
+        for i in 0..self.outer_loop_bound {
+            let num_tokens = row_tokens(&self.row_lengths, self.inner_loop_bound, i, self.lanes);
+            let mut temp_res = A::get_min_val();
+            for _j in 0..num_tokens {
+                let in_deq = self.in_stream.dequeue(&self.time);
+                match in_deq {
+                    Ok(in_elem) => {
+                        let new_max = in_elem.data.fold(temp_res, |acc, x| acc.get_max(*x));
+                        let delta = self.exp_unit.eval(temp_res - new_max);
+                        let curr = in_elem.data.mapv(|x| self.exp_unit.eval(x - new_max));
+                        // W-way max, delta and one subtraction and exponential per lane
+                        self.op_counter.add(OpKind::Add, 2 * self.lanes + 1);
+                        self.op_counter.add(OpKind::Exp, self.lanes + 1);
+                        temp_res = new_max;
+
+                        let curr_time = self.time.tick();
+                        for k in self.delta_out_stream.iter() {
+                            k.enqueue(&self.time, ChannelElement::new(curr_time + latency, delta))
+                                .unwrap();
+                        }
+                        for k in self.curr_out_stream.iter() {
+                            k.enqueue(
+                                &self.time,
+                                ChannelElement::new(curr_time + latency, curr.clone()),
+                            )
+                            .unwrap();
+                        }
+
+                        self.time.incr_cycles(init_inverval);
+                        // initiation interval
+                    }
+                    _ => {
+                        panic!("Reached unhandled case");
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[context_macro]
+pub struct SimdIncrSum<A: Clone> {
+    pub in_delta_stream: Receiver<A>,
+    pub in_curr_stream: Receiver<Array1<A>>,
+    pub out_stream: Sender<A>,
+    pub latency: u64, // loop-carried update, the adder tree comes on top
+    pub init_inverval: u64,
+    pub lanes: u64,
+    pub inner_loop_bound: u64,
+    pub outer_loop_bound: u64,
+    pub op_counter: OpCounter,
+    pub row_lengths: Vec<u64>, // overrides inner_loop_bound when set
+    pub recurrence: Option<Recurrence>, // derives the II when set, see 'achieved_ii'
+}
+
+impl<A: Clone> SimdIncrSum<A> {
+    // Latency from the last token of a row to its sum, including the log2(W) adder tree
+    pub fn total_latency(&self) -> u64 {
+        self.latency + adder_tree_latency(self.lanes, self.recurrence)
+    }
+
+    // II the recurrence allows, 'init_inverval' when it is not modeled
+    pub fn achieved_ii(&self) -> u64 {
+        match self.recurrence {
+            Some(recurrence) => self.init_inverval.max(recurrence.min_ii(1)),
+            None => self.init_inverval,
+        }
+    }
+}
+
+impl<A: DAMType> SimdIncrSum<A>
+where
+    SimdIncrSum<A>: Context,
+{
+    pub fn new(
+        in_delta_stream: Receiver<A>,
+        in_curr_stream: Receiver<Array1<A>>,
+        out_stream: Sender<A>,
+        latency: u64,
+        init_inverval: u64,
+        lanes: u64,
+        inner_loop_bound: u64,
+        outer_loop_bound: u64,
+    ) -> Self {
+        assert!(inner_loop_bound.is_multiple_of(lanes));
+        let incr_sum = SimdIncrSum {
+            in_delta_stream,
+            in_curr_stream,
+            out_stream,
+            latency,
+            init_inverval,
+            lanes,
+            inner_loop_bound,
+            outer_loop_bound,
+            row_lengths: vec![],
+            recurrence: None,
+            op_counter: Default::default(),
+            context_info: Default::default(),
+        };
+        (incr_sum.in_delta_stream).attach_receiver(&incr_sum);
+        (incr_sum.in_curr_stream).attach_receiver(&incr_sum);
+        (incr_sum.out_stream).attach_sender(&incr_sum);
+
+        incr_sum
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Rows of different lengths, e.g. decode steps over a growing KV cache
+    pub fn with_row_lengths(mut self, row_lengths: Vec<u64>) -> Self {
+        check_packed_lengths(&row_lengths, self.lanes);
+        self.outer_loop_bound = row_lengths.len() as u64;
+        self.row_lengths = row_lengths;
+        self
+    }
+
+    // Derive the II from the multiply and add latencies instead of trusting 'init_inverval'
+    pub fn with_recurrence(mut self, recurrence: Recurrence) -> Self {
+        self.recurrence = Some(recurrence);
+        self
+    }
+}
+
+impl<A> Context for SimdIncrSum<A>
+where
+    A: DAMType + num::Num + MinMax + Copy,
+    Array1<A>: DAMType,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        let latency = self.total_latency();
+        let init_inverval = self.achieved_ii();
+        for i in 0..self.outer_loop_bound {
+            let num_tokens = row_tokens(&self.row_lengths, self.inner_loop_bound, i, self.lanes);
+            let mut temp_res = A::get_zero();
+            for j in 0..num_tokens {
+                let _ = self.in_delta_stream.peek_next(&self.time);
+                let _ = self.in_curr_stream.peek_next(&self.time);
+                let in_delta_deq = self.in_delta_stream.dequeue(&self.time);
+                let in_curr_deq = self.in_curr_stream.dequeue(&self.time);
+                match (in_delta_deq, in_curr_deq) {
+                    (Ok(in_delta), Ok(in_curr)) => {
+                        // W-input adder tree, then the loop-carried rescale
+                        let curr_sum = in_curr.data.fold(A::get_zero(), |acc, x| acc + *x);
+                        temp_res = temp_res * in_delta.data + curr_sum;
+                        self.op_counter.add(OpKind::Mul, 1);
+                        self.op_counter.add(OpKind::Add, self.lanes);
+
+                        if j == num_tokens - 1 {
+                            let curr_time = self.time.tick();
+                            self.out_stream
+                                .enqueue(
+                                    &self.time,
+                                    ChannelElement::new(curr_time + latency, temp_res),
+                                )
+                                .unwrap();
+                        }
+
+                        self.time.incr_cycles(init_inverval);
+                        // initiation interval
+                    }
+                    (_, _) => {
+                        panic!("Reached unhandled case");
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[context_macro]
+pub struct SimdIncrOutP<A: Clone> {
+    pub in_delta_stream: Receiver<A>,
+    pub in_curr_stream: Receiver<Array1<A>>,
+    pub in_v_stream: Receiver<Array1<A>>, // W values per token, d = 1
+    pub out_stream: Sender<A>,
+    pub latency: u64, // loop-carried update, the adder tree comes on top
+    pub init_inverval: u64,
+    pub lanes: u64,
+    pub inner_loop_bound: u64,
+    pub outer_loop_bound: u64,
+    pub op_counter: OpCounter,
+    pub row_lengths: Vec<u64>, // overrides inner_loop_bound when set
+    pub recurrence: Option<Recurrence>, // derives the II when set, see 'achieved_ii'
+}
+
+impl<A: Clone> SimdIncrOutP<A> {
+    // Latency from the last token of a row to its output, including the log2(W) adder tree
+    pub fn total_latency(&self) -> u64 {
+        self.latency + adder_tree_latency(self.lanes, self.recurrence)
+    }
+
+    // II the recurrence allows, 'init_inverval' when it is not modeled
+    pub fn achieved_ii(&self) -> u64 {
+        match self.recurrence {
+            Some(recurrence) => self.init_inverval.max(recurrence.min_ii(1)),
+            None => self.init_inverval,
+        }
+    }
+}
+
+impl<A: DAMType> SimdIncrOutP<A>
+where
+    SimdIncrOutP<A>: Context,
+{
+    pub fn new(
+        in_delta_stream: Receiver<A>,
+        in_curr_stream: Receiver<Array1<A>>,
+        in_v_stream: Receiver<Array1<A>>,
+        out_stream: Sender<A>,
+        latency: u64,
+        init_inverval: u64,
+        lanes: u64,
+        inner_loop_bound: u64,
+        outer_loop_bound: u64,
+    ) -> Self {
+        assert!(inner_loop_bound.is_multiple_of(lanes));
+        let incr_outer_p = SimdIncrOutP {
+            in_delta_stream,
+            in_curr_stream,
+            in_v_stream,
+            out_stream,
+            latency,
+            init_inverval,
+            lanes,
+            inner_loop_bound,
+            outer_loop_bound,
+            row_lengths: vec![],
+            recurrence: None,
+            op_counter: Default::default(),
+            context_info: Default::default(),
+        };
+        (incr_outer_p.in_delta_stream).attach_receiver(&incr_outer_p);
+        (incr_outer_p.in_curr_stream).attach_receiver(&incr_outer_p);
+        (incr_outer_p.in_v_stream).attach_receiver(&incr_outer_p);
+        (incr_outer_p.out_stream).attach_sender(&incr_outer_p);
+
+        incr_outer_p
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Rows of different lengths, e.g. decode steps over a growing KV cache
+    pub fn with_row_lengths(mut self, row_lengths: Vec<u64>) -> Self {
+        check_packed_lengths(&row_lengths, self.lanes);
+        self.outer_loop_bound = row_lengths.len() as u64;
+        self.row_lengths = row_lengths;
+        self
+    }
+
+    // Derive the II from the multiply and add latencies instead of trusting 'init_inverval'
+    pub fn with_recurrence(mut self, recurrence: Recurrence) -> Self {
+        self.recurrence = Some(recurrence);
+        self
+    }
+}
+
+impl<A> Context for SimdIncrOutP<A>
+where
+    A: DAMType + num::Num + MinMax + Copy,
+    Array1<A>: DAMType,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        let latency = self.total_latency();
+        let init_inverval = self.achieved_ii();
+        for i in 0..self.outer_loop_bound {
+            let num_tokens = row_tokens(&self.row_lengths, self.inner_loop_bound, i, self.lanes);
+            let mut temp_res = A::get_zero();
+            for j in 0..num_tokens {
+                let _ = self.in_delta_stream.peek_next(&self.time);
+                let _ = self.in_curr_stream.peek_next(&self.time);
+                let _ = self.in_v_stream.peek_next(&self.time);
+                let in_delta_deq = self.in_delta_stream.dequeue(&self.time);
+                let in_curr_deq = self.in_curr_stream.dequeue(&self.time);
+                let in_v_deq = self.in_v_stream.dequeue(&self.time);
+                match (in_delta_deq, in_curr_deq, in_v_deq) {
+                    (Ok(in_delta), Ok(in_curr), Ok(in_v)) => {
+                        // W products and their adder tree, then the loop-carried rescale
+                        let dot = in_curr
+                            .data
+                            .iter()
+                            .zip(in_v.data.iter())
+                            .fold(A::get_zero(), |acc, (c, v)| acc + *c * *v);
+                        temp_res = temp_res * in_delta.data + dot;
+                        self.op_counter.add(OpKind::Mul, self.lanes + 1);
+                        self.op_counter.add(OpKind::Add, self.lanes);
+
+                        if j == num_tokens - 1 {
+                            let curr_time = self.time.tick();
+                            self.out_stream
+                                .enqueue(
+                                    &self.time,
+                                    ChannelElement::new(curr_time + latency, temp_res),
+                                )
+                                .unwrap();
+                        }
+
+                        self.time.incr_cycles(init_inverval);
+                        // initiation interval
+                    }
+                    (_, _, _) => {
+                        panic!("Reached unhandled case");
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[context_macro]
+pub struct SimdBinaryOp<A: Clone> {
+    // Elementwise A @ B on two packed streams
+    pub in1_stream: Receiver<Array1<A>>,
+    pub in2_stream: Receiver<Array1<A>>,
+    pub out_stream: Sender<Array1<A>>,
+    pub latency: u64,       // pipeline depth
+    pub init_inverval: u64, // initiation interval
+    pub lanes: u64,
+    pub loop_bound: u64, // elements
+    pub op: BinaryOpType,
+    pub op_counter: OpCounter,
+}
+
+impl<A: DAMType> SimdBinaryOp<A>
+where
+    SimdBinaryOp<A>: Context,
+{
+    pub fn new(
+        in1_stream: Receiver<Array1<A>>,
+        in2_stream: Receiver<Array1<A>>,
+        out_stream: Sender<Array1<A>>,
+        latency: u64,
+        init_inverval: u64,
+        lanes: u64,
+        loop_bound: u64,
+        op: BinaryOpType,
+    ) -> Self {
+        assert!(loop_bound.is_multiple_of(lanes));
+        let binary_op = SimdBinaryOp {
+            in1_stream,
+            in2_stream,
+            out_stream,
+            latency,
+            init_inverval,
+            lanes,
+            loop_bound,
+            op,
+            op_counter: Default::default(),
+            context_info: Default::default(),
+        };
+        (binary_op.in1_stream).attach_receiver(&binary_op);
+        (binary_op.in2_stream).attach_receiver(&binary_op);
+        (binary_op.out_stream).attach_sender(&binary_op);
+
+        binary_op
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Rows of different lengths streamed back to back, each filling whole tokens
+    pub fn with_row_lengths(mut self, row_lengths: Vec<u64>) -> Self {
+        check_packed_lengths(&row_lengths, self.lanes);
+        self.loop_bound = row_lengths.iter().sum();
+        self
+    }
+}
+
+impl<A> Context for SimdBinaryOp<A>
+where
+    A: DAMType + num::Num + Copy,
+    Array1<A>: DAMType,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        for _i in 0..(self.loop_bound / self.lanes) {
+            let in1_deq = self.in1_stream.dequeue(&self.time);
+            let in2_deq = self.in2_stream.dequeue(&self.time);
+            match (in1_deq, in2_deq) {
+                (Ok(in1), Ok(in2)) => {
+                    let out_data: Array1<A> = in1
+                        .data
+                        .iter()
+                        .zip(in2.data.iter())
+                        .map(|(a, b)| self.op.eval(*a, *b))
+                        .collect();
+                    self.op_counter.add(self.op.op_kind(), self.lanes);
+                    let curr_time = self.time.tick();
+                    self.out_stream
+                        .enqueue(
+                            &self.time,
+                            ChannelElement::new(curr_time + self.latency, out_data),
+                        )
+                        .unwrap();
+                }
+                (_, _) => {
+                    panic!("Reached unhandled case");
+                }
+            }
+            self.time.incr_cycles(self.init_inverval);
+        }
+    }
+}
+
+#[context_macro]
+pub struct SimdBinary<A: Clone> {
+    // Packed counterpart of streamattn_binary::Binary: every element of a row @ one scalar per row
+    pub in1_stream: Receiver<Array1<A>>,
+    pub in2_stream: Receiver<A>,
+    pub out_stream: Sender<Array1<A>>,
+    pub latency: u64,       // pipeline depth
+    pub init_inverval: u64, // initiation interval
+    pub lanes: u64,
+    pub inner_loop_bound: u64,
+    pub outer_loop_bound: u64,
+    pub op: BinaryOpType,
+    pub op_counter: OpCounter,
+    pub row_lengths: Vec<u64>, // overrides inner_loop_bound when set
+}
+
+impl<A: DAMType> SimdBinary<A>
+where
+    SimdBinary<A>: Context,
+{
+    pub fn new(
+        in1_stream: Receiver<Array1<A>>,
+        in2_stream: Receiver<A>,
+        out_stream: Sender<Array1<A>>,
+        latency: u64,
+        init_inverval: u64,
+        lanes: u64,
+        inner_loop_bound: u64,
+        outer_loop_bound: u64,
+        op: BinaryOpType,
+    ) -> Self {
+        assert!(inner_loop_bound.is_multiple_of(lanes));
+        let binary = SimdBinary {
+            in1_stream,
+            in2_stream,
+            out_stream,
+            latency,
+            init_inverval,
+            lanes,
+            inner_loop_bound,
+            outer_loop_bound,
+            op,
+            row_lengths: vec![],
+            op_counter: Default::default(),
+            context_info: Default::default(),
+        };
+        (binary.in1_stream).attach_receiver(&binary);
+        (binary.in2_stream).attach_receiver(&binary);
+        (binary.out_stream).attach_sender(&binary);
+
+        binary
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Rows of different lengths, e.g. a sliding window or block-sparse mask
+    pub fn with_row_lengths(mut self, row_lengths: Vec<u64>) -> Self {
+        check_packed_lengths(&row_lengths, self.lanes);
+        self.outer_loop_bound = row_lengths.len() as u64;
+        self.row_lengths = row_lengths;
+        self
+    }
+}
+
+impl<A> Context for SimdBinary<A>
+where
+    A: DAMType + num::Num + Copy,
+    Array1<A>: DAMType,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        for i in 0..self.outer_loop_bound {
+            let num_tokens = row_tokens(&self.row_lengths, self.inner_loop_bound, i, self.lanes);
+            let in2_deq = self.in2_stream.dequeue(&self.time);
+            match in2_deq {
+                Ok(in2) => {
+                    for _j in 0..num_tokens {
+                        let in1_deq = self.in1_stream.dequeue(&self.time);
+                        match in1_deq {
+                            Ok(in1) => {
+                                let out_data = in1.data.mapv(|a| self.op.eval(a, in2.data));
+                                self.op_counter.add(self.op.op_kind(), self.lanes);
+                                let curr_time = self.time.tick();
+                                self.out_stream
+                                    .enqueue(
+                                        &self.time,
+                                        ChannelElement::new(curr_time + self.latency, out_data),
+                                    )
+                                    .unwrap();
+                            }
+                            _ => {
+                                panic!("Reached unhandled case");
+                            }
+                        }
+                        self.time.incr_cycles(self.init_inverval);
+                    }
+                }
+                _ => {
+                    panic!("Reached unhandled case");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use dam::context_tools::*;
+
+use super::{exp_unit::ExpUnit, streamattn_reduce::MinMax};
+use crate::analysis::cost::{OpCounter, OpKind};
+
+#[context_macro]
+pub struct SoftmaxMerge<A: Clone> {
+    // Combines the partial online-softmax states of P key partitions of the same row.
+    // The partials are folded in one per II with the same rescaling as the running ops:
+    // m = max(m, m_p), l = l exp(m_old - m) + l_p exp(m_p - m), o likewise, and out = o / l.
+    pub max_streams: Vec<Receiver<A>>, // m_p from IncrMax, one per partition
+    pub sum_streams: Vec<Receiver<A>>, // l_p from IncrSum
+    pub out_p_streams: Vec<Receiver<A>>, // o_p from IncrOutP
+    pub out_stream: Sender<A>,
+    pub latency: u64,       // pipeline depth, including the final division
+    pub init_inverval: u64, // per partition
+    pub loop_bound: u64,    // rows
+    pub exp_unit: ExpUnit,
+    pub op_counter: OpCounter,
+}
+
+impl<A: DAMType> SoftmaxMerge<A>
+where
+    SoftmaxMerge<A>: Context,
+{
+    pub fn new(
+        max_streams: Vec<Receiver<A>>,
+        sum_streams: Vec<Receiver<A>>,
+        out_p_streams: Vec<Receiver<A>>,
+        out_stream: Sender<A>,
+        latency: u64,
+        init_inverval: u64,
+        loop_bound: u64,
+    ) -> Self {
+        assert!(max_streams.len() == sum_streams.len());
+        assert!(max_streams.len() == out_p_streams.len());
+        let merge = SoftmaxMerge {
+            max_streams,
+            sum_streams,
+            out_p_streams,
+            out_stream,
+            latency,
+            init_inverval,
+            loop_bound,
+            exp_unit: Default::default(),
+            op_counter: Default::default(),
+            context_info: Default::default(),
+        };
+        for i in merge.max_streams.iter() {
+            i.attach_receiver(&merge);
+        }
+        for i in merge.sum_streams.iter() {
+            i.attach_receiver(&merge);
+        }
+        for i in merge.out_p_streams.iter() {
+            i.attach_receiver(&merge);
+        }
+        (merge.out_stream).attach_sender(&merge);
+
+        merge
+    }
+
+    pub fn with_exp_unit(mut self, exp_unit: ExpUnit) -> Self {
+        self.exp_unit = exp_unit;
+        self
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+}
+
+impl<A> Context for SoftmaxMerge<A>
+where
+    A: DAMType + num::Float + MinMax + Copy,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        let latency = self.latency + self.exp_unit.latency;
+        let init_inverval = self.init_inverval.max(self.exp_unit.init_inverval);
+        for _i in 0..self.loop_bound {
+            let mut max = A::get_min_val();
+            let mut sum = A::get_zero();
+            let mut out = A::get_zero();
+            for p in 0..self.max_streams.len() {
+                let max_deq = self.max_streams[p].dequeue(&self.time);
+                let sum_deq = self.sum_streams[p].dequeue(&self.time);
+                let out_deq = self.out_p_streams[p].dequeue(&self.time);
+                match (max_deq, sum_deq, out_deq) {
+                    (Ok(max_p), Ok(sum_p), Ok(out_p)) => {
+                        let new_max = max.get_max(max_p.data);
+                        let delta = self.exp_unit.eval(max - new_max);
+                        let delta_p = self.exp_unit.eval(max_p.data - new_max);
+                        sum = sum * delta + sum_p.data * delta_p;
+                        out = out * delta + out_p.data * delta_p;
+                        max = new_max;
+                        // max and two subtractions, two rescaled accumulations
+                        self.op_counter.add(OpKind::Add, 5);
+                        self.op_counter.add(OpKind::Mul, 4);
+                        self.op_counter.add(OpKind::Exp, 2);
+                    }
+                    (_, _, _) => {
+                        panic!("Reached unhandled case");
+                    }
+                }
+                self.time.incr_cycles(init_inverval);
+            }
+            self.op_counter.add(OpKind::Div, 1);
+
+            let curr_time = self.time.tick();
+            self.out_stream
+                .enqueue(
+                    &self.time,
+                    ChannelElement::new(curr_time + latency, out / sum),
+                )
+                .unwrap();
+        }
+    }
+}
@@ -1,5 +1,10 @@
 use dam::context_tools::*;
 
+use crate::analysis::{
+    cost::{OpCounter, OpKind},
+    resource::UnitKind,
+};
+
 pub enum BinaryOpType {
     Add,
     Sub,
@@ -7,6 +12,33 @@ pub enum BinaryOpType {
     Mul,
 }
 
+impl BinaryOpType {
+    pub fn op_kind(&self) -> OpKind {
+        match self {
+            BinaryOpType::Add | BinaryOpType::Sub => OpKind::Add,
+            BinaryOpType::Div => OpKind::Div,
+            BinaryOpType::Mul => OpKind::Mul,
+        }
+    }
+
+    pub fn eval<A: num::Num>(&self, lhs: A, rhs: A) -> A {
+        match self {
+            BinaryOpType::Add => lhs + rhs,
+            BinaryOpType::Sub => lhs - rhs,
+            BinaryOpType::Div => lhs / rhs,
+            BinaryOpType::Mul => lhs * rhs,
+        }
+    }
+
+    pub fn unit_kind(&self) -> UnitKind {
+        match self {
+            BinaryOpType::Add | BinaryOpType::Sub => UnitKind::Adder,
+            BinaryOpType::Div => UnitKind::Divider,
+            BinaryOpType::Mul => UnitKind::Multiplier,
+        }
+    }
+}
+
 #[context_macro]
 pub struct Binary<A: Clone> {
     in1_stream: Receiver<A>,     // operand 1: A
@@ -16,7 +48,9 @@ pub struct Binary<A: Clone> {
     pub init_inverval: u64, // initiation interval
     pub inner_loop_bound: u64,
     pub outer_loop_bound: u64,
-    op: BinaryOpType,
+    pub(crate) op: BinaryOpType,
+    pub op_counter: OpCounter,
+    pub row_lengths: Vec<u64>, // overrides inner_loop_bound when set
 }
 
 impl<A: DAMType> Binary<A>
@@ -42,6 +76,8 @@ where
             inner_loop_bound,
             outer_loop_bound,
             op,
+            row_lengths: vec![],
+            op_counter: Default::default(),
             context_info: Default::default(),
         };
         ctx.in1_stream.attach_receiver(&ctx);
@@ -50,12 +86,29 @@ where
 
         ctx
     }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Rows of different lengths, e.g. a sliding window or block-sparse mask
+    pub fn with_row_lengths(mut self, row_lengths: Vec<u64>) -> Self {
+        self.outer_loop_bound = row_lengths.len() as u64;
+        self.row_lengths = row_lengths;
+        self
+    }
 }
 
 impl<A: DAMType + num::Num> Context for Binary<A> {
     fn run(&mut self) {
         //self.time.incr_cycles(4);
-        for _i in 0..self.outer_loop_bound {
+        for row in 0..self.outer_loop_bound {
+            let inner_loop_bound = self
+                .row_lengths
+                .get(row as usize)
+                .copied()
+                .unwrap_or(self.inner_loop_bound);
             //self.time.incr_cycles(4);
             let _ = self.in1_stream.peek_next(&self.time);
             let _ = self.in2_stream.peek_next(&self.time);
@@ -81,6 +134,7 @@ impl<A: DAMType + num::Num> Context for Binary<A> {
                             out_data = in1_data - in2_data.clone();
                         }
                     }
+                    self.op_counter.add(self.op.op_kind(), 1);
                     let curr_time = self.time.tick();
                     self.out1_stream
                         .enqueue(
@@ -91,7 +145,7 @@ impl<A: DAMType + num::Num> Context for Binary<A> {
 
                     self.time.incr_cycles(self.init_inverval);
 
-                    for _i in 1..self.inner_loop_bound {
+                    for _i in 1..inner_loop_bound {
                         let in1_deq = self.in1_stream.dequeue(&self.time);
 
                         match in1_deq {
@@ -112,6 +166,7 @@ impl<A: DAMType + num::Num> Context for Binary<A> {
                                         out_data = in1_data - in2_data.clone();
                                     }
                                 }
+                                self.op_counter.add(self.op.op_kind(), 1);
                                 let curr_time = self.time.tick();
                                 self.out1_stream
                                     .enqueue(
@@ -1,5 +1,7 @@
 use dam::context_tools::*;
 
+use crate::analysis::cost::{OpCounter, OpKind};
+
 #[context_macro]
 
 pub struct MatVecProd<A: Clone> {
@@ -10,6 +12,8 @@ pub struct MatVecProd<A: Clone> {
     pub init_inverval: u64, // initiation interval
     pub inner_loop_bound: u64,
     pub outer_loop_bound: u64,
+    pub op_counter: OpCounter,
+    pub row_lengths: Vec<u64>, // overrides inner_loop_bound when set
 }
 
 impl<A: DAMType> MatVecProd<A>
@@ -33,6 +37,8 @@ where
             init_inverval,
             inner_loop_bound,
             outer_loop_bound,
+            row_lengths: vec![],
+            op_counter: Default::default(),
             context_info: Default::default(),
         };
         (matmul_outer.in1_stream).attach_receiver(&matmul_outer);
@@ -41,6 +47,18 @@ where
 
         matmul_outer
     }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Rows of different lengths, e.g. a sliding window or block-sparse mask
+    pub fn with_row_lengths(mut self, row_lengths: Vec<u64>) -> Self {
+        self.outer_loop_bound = row_lengths.len() as u64;
+        self.row_lengths = row_lengths;
+        self
+    }
 }
 
 impl<A> Context for MatVecProd<A>
@@ -50,7 +68,12 @@ where
     fn init(&mut self) {}
     fn run(&mut self) -> () {
         //self.time.incr_cycles(4);
-        for _i in 0..self.outer_loop_bound {
+        for row in 0..self.outer_loop_bound {
+            let inner_loop_bound = self
+                .row_lengths
+                .get(row as usize)
+                .copied()
+                .unwrap_or(self.inner_loop_bound);
             //self.time.incr_cycles(4);
             let s_deq = self.in1_stream.dequeue(&self.time);
             let v_deq = self.in2_stream.dequeue(&self.time);
@@ -60,10 +83,20 @@ where
                     let s_data = s_elem.data;
                     let v_data = v_elem.data;
                     let mut accum_sum = s_data * v_data;
+                    self.op_counter.add(OpKind::Mul, 1);
+                    if inner_loop_bound == 1 {
+                        let curr_time = self.time.tick();
+                        self.out1_stream
+                            .enqueue(
+                                &self.time,
+                                ChannelElement::new(curr_time + self.latency, accum_sum),
+                            )
+                            .unwrap();
+                    }
 
                     self.time.incr_cycles(self.init_inverval);
 
-                    for i in 1..self.inner_loop_bound {
+                    for i in 1..inner_loop_bound {
                         let s_deq = self.in1_stream.dequeue(&self.time);
                         let v_deq = self.in2_stream.dequeue(&self.time);
 
@@ -72,12 +105,14 @@ where
                                 let s_data = s_elem.data;
                                 let v_data = v_elem.data;
                                 accum_sum = accum_sum + s_data * v_data;
+                                self.op_counter.add(OpKind::Mul, 1);
+                                self.op_counter.add(OpKind::Add, 1);
                             }
                             (_, _) => {
                                 panic!("Reached unhandled case");
                             }
                         }
-                        if i == self.inner_loop_bound - 1 {
+                        if i == inner_loop_bound - 1 {
                             let curr_time = self.time.tick();
                             self.out1_stream
                                 .enqueue(
@@ -1,5 +1,7 @@
 use dam::context_tools::*;
 
+use super::{exp_unit::ExpUnit, score_scale::ScoreScale};
+use crate::analysis::cost::{OpCounter, OpKind};
 use ndarray::{ArrayBase, Dim, OwnedRepr};
 
 #[context_macro]
@@ -10,6 +12,13 @@ pub struct QKTExp<A: Clone> {
     pub latency: u64,             // pipeline depth
     pub init_inverval: u64,       // initiation interval
     pub seq_len: u64,
+    pub outer_loop_bound: u64, // number of query rows (defaults to seq_len)
+    pub exp_unit: ExpUnit,
+    pub op_counter: OpCounter,
+    pub row_lengths: Vec<u64>,     // overrides seq_len when set
+    pub score_scale: ScoreScale,   // applied to q*k before the exp
+    pub bias: Option<Receiver<A>>, // one additive score bias per q*k, e.g. ALiBi
+    pub bias_latency: u64,
 }
 
 impl<A: DAMType> QKTExp<A>
@@ -32,6 +41,13 @@ where
             latency,
             init_inverval,
             seq_len,
+            outer_loop_bound: seq_len,
+            exp_unit: Default::default(),
+            row_lengths: vec![],
+            score_scale: Default::default(),
+            bias: None,
+            bias_latency: 0,
+            op_counter: Default::default(),
             context_info: Default::default(),
         };
         (qkt_exp.q).attach_receiver(&qkt_exp);
@@ -42,6 +58,41 @@ where
 
         qkt_exp
     }
+
+    pub fn with_outer_loop_bound(mut self, outer_loop_bound: u64) -> Self {
+        self.outer_loop_bound = outer_loop_bound;
+        self
+    }
+
+    pub fn with_exp_unit(mut self, exp_unit: ExpUnit) -> Self {
+        self.exp_unit = exp_unit;
+        self
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Rows of different lengths, e.g. decode steps over a growing KV cache
+    pub fn with_row_lengths(mut self, row_lengths: Vec<u64>) -> Self {
+        self.outer_loop_bound = row_lengths.len() as u64;
+        self.row_lengths = row_lengths;
+        self
+    }
+
+    pub fn with_score_scale(mut self, score_scale: ScoreScale) -> Self {
+        self.score_scale = score_scale;
+        self
+    }
+
+    // Adds a bias stream to q*k before the exp, costing an adder stage of 'bias_latency'
+    pub fn with_bias(mut self, bias: Receiver<A>, bias_latency: u64) -> Self {
+        bias.attach_receiver(&self);
+        self.bias = Some(bias);
+        self.bias_latency = bias_latency;
+        self
+    }
 }
 
 impl<A> Context for QKTExp<A>
@@ -51,8 +102,16 @@ where
     fn init(&mut self) {}
 
     fn run(&mut self) -> () {
+        let latency =
+            self.latency + self.exp_unit.latency + self.score_scale.latency + self.bias_latency;
+        let init_inverval = self.init_inverval.max(self.exp_unit.init_inverval);
         //self.time.incr_cycles(4);
-        for _i in 0..self.seq_len {
+        for i in 0..self.outer_loop_bound {
+            let seq_len = self
+                .row_lengths
+                .get(i as usize)
+                .copied()
+                .unwrap_or(self.seq_len);
             let _ = self.q.peek_next(&self.time);
             let _ = self.kt.peek_next(&self.time);
 
@@ -60,11 +119,26 @@ where
             match q_deq {
                 Ok(q) => {
                     //self.time.incr_cycles(4);
-                    for _i in 0..self.seq_len {
+                    for _i in 0..seq_len {
                         let kt_deq = self.kt.dequeue(&self.time);
                         match kt_deq {
                             Ok(kt) => {
-                                let qkt_exp_res = (q.data * kt.data).exp();
+                                let mut score = self.score_scale.eval(q.data * kt.data);
+                                for (kind, n) in self.score_scale.ops() {
+                                    self.op_counter.add(kind, n);
+                                }
+                                if let Some(bias) = &self.bias {
+                                    match bias.dequeue(&self.time) {
+                                        Ok(b) => score = score + b.data,
+                                        _ => {
+                                            panic!("Reached unhandled case");
+                                        }
+                                    }
+                                    self.op_counter.add(OpKind::Add, 1);
+                                }
+                                let qkt_exp_res = self.exp_unit.eval(score);
+                                self.op_counter.add(OpKind::Mul, 1);
+                                self.op_counter.add(OpKind::Exp, 1);
                                 let curr_time = self.time.tick();
 
                                 for k in self.out_fifo.iter() {
@@ -75,14 +149,14 @@ where
                                     k.enqueue(
                                         &self.time,
                                         ChannelElement::new(
-                                            curr_time + self.latency,
+                                            curr_time + latency,
                                             qkt_exp_res.clone(),
                                         ),
                                     )
                                     .unwrap();
                                 }
 
-                                self.time.incr_cycles(self.init_inverval);
+                                self.time.incr_cycles(init_inverval);
                                 // initiation interval
                             }
                             _ => {
@@ -98,3 +172,136 @@ where
         }
     }
 }
+
+#[context_macro]
+pub struct QKTExpMultiRow<A: Clone> {
+    // Holds 'out_fifo.len()' (Br) query rows and feeds every streamed K element to all of them,
+    // so K is streamed once per block of Br rows instead of once per row.
+    pub q: Receiver<A>,                // operand 1: Br consecutive rows per block
+    pub kt: Receiver<A>,               // operand 2: Vector, streamed once per block
+    pub out_fifo: Vec<Vec<Sender<A>>>, // per lane: list of output scalar FIFOs
+    pub latency: u64,                  // pipeline depth
+    pub init_inverval: u64,            // initiation interval
+    pub seq_len: u64,
+    pub outer_loop_bound: u64, // number of query rows, a multiple of Br
+    pub exp_unit: ExpUnit,
+    pub op_counter: OpCounter,
+    pub score_scale: ScoreScale, // one per lane
+}
+
+impl<A: DAMType> QKTExpMultiRow<A>
+where
+    QKTExpMultiRow<A>: Context,
+{
+    pub fn new(
+        q: Receiver<A>,
+        kt: Receiver<A>,
+        out_fifo: Vec<Vec<Sender<A>>>,
+        latency: u64,
+        init_inverval: u64,
+        seq_len: u64,
+        outer_loop_bound: u64,
+    ) -> Self {
+        assert!(outer_loop_bound.is_multiple_of(out_fifo.len() as u64));
+        let qkt_exp = QKTExpMultiRow {
+            q,
+            kt,
+            out_fifo,
+            latency,
+            init_inverval,
+            seq_len,
+            outer_loop_bound,
+            exp_unit: Default::default(),
+            op_counter: Default::default(),
+            score_scale: Default::default(),
+            context_info: Default::default(),
+        };
+        (qkt_exp.q).attach_receiver(&qkt_exp);
+        (qkt_exp.kt).attach_receiver(&qkt_exp);
+        for lane in qkt_exp.out_fifo.iter() {
+            for i in lane.iter() {
+                i.attach_sender(&qkt_exp);
+            }
+        }
+
+        qkt_exp
+    }
+
+    pub fn with_exp_unit(mut self, exp_unit: ExpUnit) -> Self {
+        self.exp_unit = exp_unit;
+        self
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    pub fn with_score_scale(mut self, score_scale: ScoreScale) -> Self {
+        self.score_scale = score_scale;
+        self
+    }
+}
+
+impl<A> Context for QKTExpMultiRow<A>
+where
+    A: DAMType + num::Float,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        let latency = self.latency + self.exp_unit.latency + self.score_scale.latency;
+        let init_inverval = self.init_inverval.max(self.exp_unit.init_inverval);
+        let num_lanes = self.out_fifo.len() as u64;
+        for _i in 0..(self.outer_loop_bound / num_lanes) {
+            let mut q_rows = vec![];
+            for _r in 0..num_lanes {
+                match self.q.dequeue(&self.time) {
+                    Ok(q) => q_rows.push(q.data),
+                    _ => {
+                        panic!("Reached unhandled case");
+                    }
+                }
+            }
+            for _j in 0..self.seq_len {
+                let kt_deq = self.kt.dequeue(&self.time);
+                match kt_deq {
+                    Ok(kt) => {
+                        // Br parallel lanes consume the same K element in one cycle
+                        let qkt_exp_res: Vec<A> = q_rows
+                            .iter()
+                            .map(|q| self.exp_unit.eval(self.score_scale.eval(*q * kt.data)))
+                            .collect();
+                        self.op_counter.add(OpKind::Mul, num_lanes);
+                        self.op_counter.add(OpKind::Exp, num_lanes);
+                        for (kind, n) in self.score_scale.ops() {
+                            self.op_counter.add(kind, n * num_lanes);
+                        }
+                        let curr_time = self.time.tick();
+
+                        for lane in self.out_fifo.iter() {
+                            for k in lane.iter() {
+                                let _ = k.wait_until_available(&self.time);
+                            }
+                        }
+                        for (lane, res) in self.out_fifo.iter().zip(qkt_exp_res.iter()) {
+                            for k in lane.iter() {
+                                k.enqueue(
+                                    &self.time,
+                                    ChannelElement::new(curr_time + latency, *res),
+                                )
+                                .unwrap();
+                            }
+                        }
+
+                        self.time.incr_cycles(init_inverval);
+                        // initiation interval
+                    }
+                    _ => {
+                        panic!("Reached unhandled case");
+                    }
+                }
+            }
+        }
+    }
+}
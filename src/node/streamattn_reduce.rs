@@ -1,9 +1,31 @@
+use std::sync::Arc;
+
 use dam::context_tools::*;
 
+use crate::analysis::{
+    cost::{OpCounter, OpKind},
+    resource::UnitKind,
+};
+
 pub trait MinMax {
     fn get_max(self, rhs: Self) -> Self;
     fn get_min_val() -> Self;
     fn get_zero() -> Self;
+    fn get_min(self, rhs: Self) -> Self;
+    fn get_max_val() -> Self;
+    // Identities of max and min, infinite for floats
+    fn get_max_identity() -> Self
+    where
+        Self: Sized,
+    {
+        Self::get_min_val()
+    }
+    fn get_min_identity() -> Self
+    where
+        Self: Sized,
+    {
+        Self::get_max_val()
+    }
 }
 impl MinMax for u8 {
     fn get_max(self, rhs: u8) -> u8 {
@@ -17,6 +39,12 @@ impl MinMax for u8 {
     fn get_zero() -> u8 {
         0u8
     }
+    fn get_min(self, rhs: u8) -> u8 {
+        self.min(rhs)
+    }
+    fn get_max_val() -> u8 {
+        u8::MAX
+    }
 }
 impl MinMax for u16 {
     fn get_max(self, rhs: u16) -> u16 {
@@ -28,6 +56,12 @@ impl MinMax for u16 {
     fn get_zero() -> u16 {
         0u16
     }
+    fn get_min(self, rhs: u16) -> u16 {
+        self.min(rhs)
+    }
+    fn get_max_val() -> u16 {
+        u16::MAX
+    }
 }
 impl MinMax for u32 {
     fn get_max(self, rhs: u32) -> u32 {
@@ -39,6 +73,12 @@ impl MinMax for u32 {
     fn get_zero() -> u32 {
         0u32
     }
+    fn get_min(self, rhs: u32) -> u32 {
+        self.min(rhs)
+    }
+    fn get_max_val() -> u32 {
+        u32::MAX
+    }
 }
 impl MinMax for u64 {
     fn get_max(self, rhs: u64) -> u64 {
@@ -50,6 +90,12 @@ impl MinMax for u64 {
     fn get_zero() -> u64 {
         0u64
     }
+    fn get_min(self, rhs: u64) -> u64 {
+        self.min(rhs)
+    }
+    fn get_max_val() -> u64 {
+        u64::MAX
+    }
 }
 impl MinMax for i8 {
     fn get_max(self, rhs: i8) -> i8 {
@@ -61,6 +107,12 @@ impl MinMax for i8 {
     fn get_zero() -> i8 {
         0i8
     }
+    fn get_min(self, rhs: i8) -> i8 {
+        self.min(rhs)
+    }
+    fn get_max_val() -> i8 {
+        i8::MAX
+    }
 }
 impl MinMax for i16 {
     fn get_max(self, rhs: i16) -> i16 {
@@ -72,6 +124,12 @@ impl MinMax for i16 {
     fn get_zero() -> i16 {
         0i16
     }
+    fn get_min(self, rhs: i16) -> i16 {
+        self.min(rhs)
+    }
+    fn get_max_val() -> i16 {
+        i16::MAX
+    }
 }
 impl MinMax for i32 {
     fn get_max(self, rhs: i32) -> i32 {
@@ -83,6 +141,12 @@ impl MinMax for i32 {
     fn get_zero() -> i32 {
         0i32
     }
+    fn get_min(self, rhs: i32) -> i32 {
+        self.min(rhs)
+    }
+    fn get_max_val() -> i32 {
+        i32::MAX
+    }
 }
 impl MinMax for i64 {
     fn get_max(self, rhs: i64) -> i64 {
@@ -94,6 +158,12 @@ impl MinMax for i64 {
     fn get_zero() -> i64 {
         0i64
     }
+    fn get_min(self, rhs: i64) -> i64 {
+        self.min(rhs)
+    }
+    fn get_max_val() -> i64 {
+        i64::MAX
+    }
 }
 impl MinMax for f32 {
     fn get_max(self, rhs: f32) -> f32 {
@@ -105,6 +175,18 @@ impl MinMax for f32 {
     fn get_zero() -> f32 {
         0_f32
     }
+    fn get_min(self, rhs: f32) -> f32 {
+        self.min(rhs)
+    }
+    fn get_max_val() -> f32 {
+        f32::MAX
+    }
+    fn get_max_identity() -> f32 {
+        f32::NEG_INFINITY
+    }
+    fn get_min_identity() -> f32 {
+        f32::INFINITY
+    }
 }
 impl MinMax for f64 {
     fn get_max(self, rhs: f64) -> f64 {
@@ -116,11 +198,109 @@ impl MinMax for f64 {
     fn get_zero() -> f64 {
         0_f64
     }
+    fn get_min(self, rhs: f64) -> f64 {
+        self.min(rhs)
+    }
+    fn get_max_val() -> f64 {
+        f64::MAX
+    }
+    fn get_max_identity() -> f64 {
+        f64::NEG_INFINITY
+    }
+    fn get_min_identity() -> f64 {
+        f64::INFINITY
+    }
 }
 
-pub enum ReduceOpType {
+pub enum ReduceOpType<A> {
     Max,
+    Min,
     Sum,
+    Prod,
+    ArgMax, // value on out_stream, index within the row on the index stream
+    Custom {
+        combine: Arc<dyn Fn(A, A) -> A + Send + Sync>,
+        identity: A,             // emitted for empty rows
+        ops: Vec<(OpKind, u64)>, // cost of one combine
+    },
+}
+
+impl<A: num::Num + MinMax + Copy> ReduceOpType<A> {
+    // 'ops' is the cost of one combine
+    pub fn custom<F>(combine: F, identity: A, ops: Vec<(OpKind, u64)>) -> Self
+    where
+        F: Fn(A, A) -> A + Send + Sync + 'static,
+    {
+        ReduceOpType::Custom {
+            combine: Arc::new(combine),
+            identity,
+            ops,
+        }
+    }
+
+    // Result of reducing an empty row
+    pub fn identity(&self) -> A {
+        match self {
+            ReduceOpType::Max | ReduceOpType::ArgMax => A::get_max_identity(),
+            ReduceOpType::Min => A::get_min_identity(),
+            ReduceOpType::Sum => A::zero(),
+            ReduceOpType::Prod => A::one(),
+            ReduceOpType::Custom { identity, .. } => *identity,
+        }
+    }
+
+    pub fn combine(&self, lhs: A, rhs: A) -> A {
+        match self {
+            ReduceOpType::Max | ReduceOpType::ArgMax => lhs.get_max(rhs),
+            ReduceOpType::Min => lhs.get_min(rhs),
+            ReduceOpType::Sum => lhs + rhs,
+            ReduceOpType::Prod => lhs * rhs,
+            ReduceOpType::Custom { combine, .. } => combine(lhs, rhs),
+        }
+    }
+}
+
+impl<A> ReduceOpType<A> {
+    // Operations of one combine
+    pub fn ops(&self) -> Vec<(OpKind, u64)> {
+        match self {
+            ReduceOpType::Prod => vec![(OpKind::Mul, 1)],
+            ReduceOpType::Custom { ops, .. } => ops.clone(),
+            _ => vec![(OpKind::Add, 1)],
+        }
+    }
+
+    pub fn functional_units(&self) -> Vec<(UnitKind, u64)> {
+        self.ops()
+            .into_iter()
+            .filter_map(|(kind, n)| match kind {
+                OpKind::Mul => Some((UnitKind::Multiplier, n)),
+                OpKind::Add => Some((UnitKind::Adder, n)),
+                OpKind::Div => Some((UnitKind::Divider, n)),
+                OpKind::Exp => Some((UnitKind::ExpUnit, n)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl<A: num::Float + MinMax + Send + Sync + 'static> ReduceOpType<A> {
+    // ln(sum(exp(x))), combined pairwise as max + ln(1 + exp(min - max)) so it never overflows
+    pub fn log_sum_exp() -> Self {
+        ReduceOpType::custom(
+            |a: A, b: A| {
+                let (hi, lo) = (a.max(b), a.min(b));
+                if lo == A::neg_infinity() {
+                    hi
+                } else {
+                    hi + (lo - hi).exp().ln_1p()
+                }
+            },
+            A::neg_infinity(),
+            // max, min - max and 1 + ..., the exp and the ln
+            vec![(OpKind::Add, 3), (OpKind::Exp, 2)],
+        )
+    }
 }
 
 #[context_macro]
@@ -131,7 +311,10 @@ pub struct ReduceOp<A: Clone> {
     pub init_inverval: u64,     // initiation interval
     pub inner_loop_bound: u64, // As this is a reduction, we need a inner loop bound to specify how many elements are reduce
     pub outer_loop_bound: u64,
-    op: ReduceOpType,
+    pub(crate) op: ReduceOpType<A>,
+    pub op_counter: OpCounter,
+    pub row_lengths: Vec<u64>, // overrides inner_loop_bound when set
+    pub index_stream: Option<Sender<u64>>, // ArgMax index output
 }
 
 impl<A: DAMType> ReduceOp<A>
@@ -145,7 +328,7 @@ where
         init_inverval: u64,
         inner_loop_bound: u64,
         outer_loop_bound: u64,
-        op: ReduceOpType,
+        op: ReduceOpType<A>,
     ) -> Self {
         let reduce = ReduceOp {
             in_stream,
@@ -155,6 +338,9 @@ where
             inner_loop_bound,
             outer_loop_bound,
             op,
+            row_lengths: vec![],
+            index_stream: None,
+            op_counter: Default::default(),
             context_info: Default::default(),
         };
         (reduce.in_stream).attach_receiver(&reduce);
@@ -162,6 +348,26 @@ where
 
         reduce
     }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Rows of different lengths, e.g. a sliding window or block-sparse mask
+    pub fn with_row_lengths(mut self, row_lengths: Vec<u64>) -> Self {
+        self.outer_loop_bound = row_lengths.len() as u64;
+        self.row_lengths = row_lengths;
+        self
+    }
+
+    // Where ArgMax emits the index of the max within each row
+    pub fn with_index_out(mut self, index_stream: Sender<u64>) -> Self {
+        assert!(matches!(self.op, ReduceOpType::ArgMax));
+        index_stream.attach_sender(&self);
+        self.index_stream = Some(index_stream);
+        self
+    }
 }
 
 impl<A> Context for ReduceOp<A>
@@ -172,39 +378,49 @@ where
 
     fn run(&mut self) -> () {
         //self.time.incr_cycles(4);
-        for _i in 0..self.outer_loop_bound {
+        for row in 0..self.outer_loop_bound {
+            let inner_loop_bound = self
+                .row_lengths
+                .get(row as usize)
+                .copied()
+                .unwrap_or(self.inner_loop_bound);
+            if inner_loop_bound == 0 {
+                self.emit(self.op.identity(), 0);
+                continue;
+            }
             //self.time.incr_cycles(4);
             let first_peek = self.in_stream.dequeue(&self.time);
             match first_peek {
                 Ok(first_elem) => {
                     let mut temp_res = first_elem.data;
+                    let mut arg = 0;
+                    if inner_loop_bound == 1 {
+                        self.emit(temp_res, arg);
+                    }
                     self.time.incr_cycles(self.init_inverval);
-                    for i in 1..self.inner_loop_bound {
+                    for i in 1..inner_loop_bound {
                         let in_deq = self.in_stream.dequeue(&self.time);
                         match in_deq {
                             Ok(in_elem) => {
                                 let in_data = in_elem.data;
-                                match self.op {
-                                    ReduceOpType::Max => {
-                                        temp_res = temp_res.get_max(in_data);
-                                    }
-                                    ReduceOpType::Sum => {
-                                        temp_res = temp_res + in_data;
+                                for (kind, n) in self.op.ops() {
+                                    self.op_counter.add(kind, n);
+                                }
+                                let new_res = self.op.combine(temp_res, in_data);
+                                if let ReduceOpType::ArgMax = self.op {
+                                    // Ties keep the first index
+                                    if new_res != temp_res {
+                                        arg = i;
                                     }
                                 }
+                                temp_res = new_res;
                             }
                             _ => {
                                 panic!("Reached unhandled case");
                             }
                         }
-                        if i == self.inner_loop_bound - 1 {
-                            let curr_time = self.time.tick();
-                            self.out_stream
-                                .enqueue(
-                                    &self.time,
-                                    ChannelElement::new(curr_time + self.latency, temp_res),
-                                )
-                                .unwrap();
+                        if i == inner_loop_bound - 1 {
+                            self.emit(temp_res, arg);
                         }
                         self.time.incr_cycles(self.init_inverval);
                     }
@@ -216,3 +432,29 @@ where
         }
     }
 }
+
+impl<A> ReduceOp<A>
+where
+    A: DAMType + num::Num + MinMax + Copy,
+{
+    // Result of a row, and its ArgMax index if requested
+    fn emit(&self, res: A, arg: u64) {
+        emit_row(&self.time, &self.out_stream, self.latency, res);
+        if let Some(index_stream) = &self.index_stream {
+            emit_row(&self.time, index_stream, self.latency, arg);
+        }
+    }
+}
+
+// Enqueue the result of one row 'latency' cycles from now
+pub(crate) fn emit_row<T: DAMType>(
+    time: &TimeManager,
+    out_stream: &Sender<T>,
+    latency: u64,
+    res: T,
+) {
+    let curr_time = time.tick();
+    out_stream
+        .enqueue(time, ChannelElement::new(curr_time + latency, res))
+        .unwrap();
+}
@@ -0,0 +1,284 @@
+use dam::context_tools::*;
+
+use super::streamattn_reduce::{emit_row, MinMax, ReduceOpType};
+use crate::analysis::cost::OpCounter;
+
+// Levels of a binary tree combining 'n' values
+pub fn tree_levels(n: u64) -> u64 {
+    (n.max(1) as f64).log2().ceil() as u64
+}
+
+#[context_macro]
+pub struct TreeReduce<A: Clone> {
+    // Adder tree over W = in_lanes.len() lanes: element j of a row arrives on lane j % W.
+    // Each chunk of W elements goes through log2(W) levels, the chunk results are combined
+    // by an accumulator whose 'add_latency' loop-carried dependence sets the II per chunk.
+    pub in_lanes: Vec<Receiver<A>>,
+    pub out_stream: Sender<A>,
+    pub add_latency: u64,   // pipeline depth of one combine
+    pub init_inverval: u64, // initiation interval
+    pub inner_loop_bound: u64,
+    pub outer_loop_bound: u64,
+    pub(crate) op: ReduceOpType<A>,
+    pub op_counter: OpCounter,
+    pub row_lengths: Vec<u64>, // overrides inner_loop_bound when set
+}
+
+impl<A: DAMType> TreeReduce<A>
+where
+    TreeReduce<A>: Context,
+{
+    pub fn new(
+        in_lanes: Vec<Receiver<A>>,
+        out_stream: Sender<A>,
+        add_latency: u64,
+        init_inverval: u64,
+        inner_loop_bound: u64,
+        outer_loop_bound: u64,
+        op: ReduceOpType<A>,
+    ) -> Self {
+        assert!(!in_lanes.is_empty());
+        let tree = TreeReduce {
+            in_lanes,
+            out_stream,
+            add_latency,
+            init_inverval,
+            inner_loop_bound,
+            outer_loop_bound,
+            op,
+            op_counter: Default::default(),
+            row_lengths: vec![],
+            context_info: Default::default(),
+        };
+        for i in tree.in_lanes.iter() {
+            i.attach_receiver(&tree);
+        }
+        (tree.out_stream).attach_sender(&tree);
+
+        tree
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Rows of different lengths, e.g. a sliding window or block-sparse mask
+    pub fn with_row_lengths(mut self, row_lengths: Vec<u64>) -> Self {
+        self.outer_loop_bound = row_lengths.len() as u64;
+        self.row_lengths = row_lengths;
+        self
+    }
+}
+
+impl<A> Context for TreeReduce<A>
+where
+    A: DAMType + num::Num + MinMax + Copy,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        let num_lanes = self.in_lanes.len() as u64;
+        let tree_latency = tree_levels(num_lanes) * self.add_latency;
+        // The accumulator can only take a new chunk once the previous combine is done
+        let init_inverval = self.init_inverval.max(self.add_latency);
+        for row in 0..self.outer_loop_bound {
+            let inner_loop_bound = self
+                .row_lengths
+                .get(row as usize)
+                .copied()
+                .unwrap_or(self.inner_loop_bound);
+            // An empty row reduces to the identity without touching the datapath
+            if inner_loop_bound == 0 {
+                emit_row(
+                    &self.time,
+                    &self.out_stream,
+                    self.add_latency,
+                    self.op.identity(),
+                );
+                continue;
+            }
+            let mut acc: Option<A> = None;
+            let num_chunks = inner_loop_bound.div_ceil(num_lanes);
+            for c in 0..num_chunks {
+                let chunk_len = (inner_loop_bound - c * num_lanes).min(num_lanes);
+                let mut chunk: Option<A> = None;
+                for lane in self.in_lanes.iter().take(chunk_len as usize) {
+                    let _ = lane.peek_next(&self.time);
+                }
+                for lane in self.in_lanes.iter().take(chunk_len as usize) {
+                    match lane.dequeue(&self.time) {
+                        Ok(in_elem) => {
+                            chunk = Some(match chunk {
+                                Some(res) => self.op.combine(res, in_elem.data),
+                                None => in_elem.data,
+                            });
+                        }
+                        _ => {
+                            panic!("Reached unhandled case");
+                        }
+                    }
+                }
+                let chunk = chunk.unwrap();
+                acc = Some(match acc {
+                    Some(res) => self.op.combine(res, chunk),
+                    None => chunk,
+                });
+                // chunk_len - 1 combines in the tree and one in the accumulator
+                let combines = chunk_len - 1 + if c > 0 { 1 } else { 0 };
+                for (kind, n) in self.op.ops() {
+                    self.op_counter.add(kind, n * combines);
+                }
+
+                if c == num_chunks - 1 {
+                    emit_row(
+                        &self.time,
+                        &self.out_stream,
+                        tree_latency + self.add_latency,
+                        acc.unwrap(),
+                    );
+                }
+                self.time.incr_cycles(init_inverval);
+            }
+        }
+    }
+}
+
+#[context_macro]
+pub struct PartialSumAccum<A: Clone> {
+    // Pipelined accumulator with P partial results: element j of a row updates partial j % P,
+    // so each partial sees a new element every P issues and an adder of 'add_latency' stages
+    // sustains II = ceil(add_latency / P). The partials are merged by a tree at the end of each row.
+    pub in_stream: Receiver<A>,
+    pub out_stream: Sender<A>,
+    pub add_latency: u64,   // pipeline depth of one combine
+    pub init_inverval: u64, // initiation interval
+    pub num_partials: u64,
+    pub inner_loop_bound: u64,
+    pub outer_loop_bound: u64,
+    pub(crate) op: ReduceOpType<A>,
+    pub op_counter: OpCounter,
+    pub row_lengths: Vec<u64>, // overrides inner_loop_bound when set
+}
+
+impl<A: DAMType> PartialSumAccum<A>
+where
+    PartialSumAccum<A>: Context,
+{
+    pub fn new(
+        in_stream: Receiver<A>,
+        out_stream: Sender<A>,
+        add_latency: u64,
+        init_inverval: u64,
+        num_partials: u64,
+        inner_loop_bound: u64,
+        outer_loop_bound: u64,
+        op: ReduceOpType<A>,
+    ) -> Self {
+        assert!(num_partials > 0);
+        let accum = PartialSumAccum {
+            in_stream,
+            out_stream,
+            add_latency,
+            init_inverval,
+            num_partials,
+            inner_loop_bound,
+            outer_loop_bound,
+            op,
+            op_counter: Default::default(),
+            row_lengths: vec![],
+            context_info: Default::default(),
+        };
+        (accum.in_stream).attach_receiver(&accum);
+        (accum.out_stream).attach_sender(&accum);
+
+        accum
+    }
+
+    pub fn with_op_counter(mut self, op_counter: OpCounter) -> Self {
+        self.op_counter = op_counter;
+        self
+    }
+
+    // Rows of different lengths, e.g. a sliding window or block-sparse mask
+    pub fn with_row_lengths(mut self, row_lengths: Vec<u64>) -> Self {
+        self.outer_loop_bound = row_lengths.len() as u64;
+        self.row_lengths = row_lengths;
+        self
+    }
+
+    // II the recurrence allows with 'num_partials' interleaved partial results
+    pub fn achieved_ii(&self) -> u64 {
+        let recurrence_ii = self.add_latency.div_ceil(self.num_partials);
+        self.init_inverval.max(recurrence_ii)
+    }
+}
+
+impl<A> Context for PartialSumAccum<A>
+where
+    A: DAMType + num::Num + MinMax + Copy,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) -> () {
+        let init_inverval = self.achieved_ii();
+        for row in 0..self.outer_loop_bound {
+            let inner_loop_bound = self
+                .row_lengths
+                .get(row as usize)
+                .copied()
+                .unwrap_or(self.inner_loop_bound);
+            // An empty row reduces to the identity without touching the datapath
+            if inner_loop_bound == 0 {
+                emit_row(
+                    &self.time,
+                    &self.out_stream,
+                    self.add_latency,
+                    self.op.identity(),
+                );
+                continue;
+            }
+            let mut partials: Vec<Option<A>> = vec![None; self.num_partials as usize];
+            for j in 0..inner_loop_bound {
+                match self.in_stream.dequeue(&self.time) {
+                    Ok(in_elem) => {
+                        let p = (j % self.num_partials) as usize;
+                        partials[p] = Some(match partials[p] {
+                            Some(res) => {
+                                for (kind, n) in self.op.ops() {
+                                    self.op_counter.add(kind, n);
+                                }
+                                self.op.combine(res, in_elem.data)
+                            }
+                            None => in_elem.data,
+                        });
+                    }
+                    _ => {
+                        panic!("Reached unhandled case");
+                    }
+                }
+
+                if j == inner_loop_bound - 1 {
+                    // Merge the used partials
+                    let used: Vec<A> = partials.iter().flatten().copied().collect();
+                    let merge_latency = tree_levels(used.len() as u64) * self.add_latency;
+                    let mut res = used[0];
+                    for x in used.iter().skip(1) {
+                        for (kind, n) in self.op.ops() {
+                            self.op_counter.add(kind, n);
+                        }
+                        res = self.op.combine(res, *x);
+                    }
+
+                    emit_row(
+                        &self.time,
+                        &self.out_stream,
+                        self.add_latency + merge_latency,
+                        res,
+                    );
+                }
+                self.time.incr_cycles(init_inverval);
+            }
+        }
+    }
+}
@@ -0,0 +1,230 @@
+#[cfg(test)]
+mod tests {
+    use dam::{
+        simulation::{DotConvertible, ProgramBuilder},
+        utility_contexts::{ApproxCheckerContext, GeneratorContext},
+    };
+
+    use crate::{
+        graph::{
+            flashattn::{flash_attn_lse, FlashAttnConfig},
+            flashattn_backward::{flash_attn_backward, FlashAttnBwdConfig},
+        },
+        node::score_scale::ScoreScale,
+    };
+
+    const SEQ_LEN: u64 = 64;
+
+    struct Reference {
+        o: Vec<f64>,
+        lse: Vec<f64>,
+        dq: Vec<f64>,
+        dk: Vec<f64>,
+        dv: Vec<f64>,
+    }
+
+    // Dense forward and backward pass with d = 1, on the scores s = exp(scale(q k)) of the flash pipeline
+    fn reference(
+        q: &[f64],
+        k: &[f64],
+        v: &[f64],
+        d_o: &[f64],
+        score_scale: &ScoreScale,
+    ) -> Reference {
+        let n = q.len();
+        let score = |i: usize, j: usize| score_scale.eval(q[i] * k[j]).exp();
+        let mut o = vec![0_f64; n];
+        let mut lse = vec![0_f64; n];
+        let mut dq = vec![0_f64; n];
+        let mut dk = vec![0_f64; n];
+        let mut dv = vec![0_f64; n];
+        for i in 0..n {
+            lse[i] = (0..n).map(|j| score(i, j).exp()).sum::<f64>().ln();
+            o[i] = (0..n).map(|j| (score(i, j) - lse[i]).exp() * v[j]).sum();
+        }
+        for i in 0..n {
+            let d = d_o[i] * o[i];
+            for j in 0..n {
+                let p = (score(i, j) - lse[i]).exp();
+                let ds = p * (d_o[i] * v[j] - d);
+                // ds / d(q k) = s scale'(q k)
+                let dx = ds * score(i, j) * score_scale.grad(q[i] * k[j]);
+                dv[j] += p * d_o[i];
+                dq[i] += dx * k[j];
+                dk[j] += dx * q[i];
+            }
+        }
+        Reference { o, lse, dq, dk, dv }
+    }
+
+    fn inputs() -> (Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>) {
+        let n = SEQ_LEN as usize;
+        let q: Vec<f64> = (0..n).map(|i| (i as f64) * 0.01_f64).collect();
+        let k: Vec<f64> = (0..n)
+            .map(|j| 0.1_f64 + 0.01_f64 * ((j % 7) as f64))
+            .collect();
+        let v: Vec<f64> = (0..n).map(|j| 1_f64 + (j as f64) * 0.01_f64).collect();
+        let d_o: Vec<f64> = (0..n).map(|i| 1_f64 - (i as f64) * 0.005_f64).collect();
+        (q, k, v, d_o)
+    }
+
+    fn score_scales() -> Vec<ScoreScale> {
+        vec![
+            ScoreScale::default(),
+            ScoreScale::inv_sqrt_d(4, 2),
+            ScoreScale::soft_cap(2_f64, 0.05_f64, 6),
+        ]
+    }
+
+    #[test]
+    fn reference_gradients() {
+        // The analytic gradients of L = sum_i dO_i O_i match central differences
+        for score_scale in score_scales() {
+            check_reference_gradients(&score_scale);
+        }
+    }
+
+    fn check_reference_gradients(score_scale: &ScoreScale) {
+        let (q, k, v, d_o) = inputs();
+        let r = reference(&q, &k, &v, &d_o, score_scale);
+        let loss = |q: &[f64], k: &[f64], v: &[f64]| -> f64 {
+            let o = reference(q, k, v, &d_o, score_scale).o;
+            o.iter().zip(d_o.iter()).map(|(o, d)| o * d).sum()
+        };
+        let h = 1e-6_f64;
+        let shifted = |x: &[f64], idx: usize, delta: f64| {
+            let mut x = x.to_vec();
+            x[idx] += delta;
+            x
+        };
+        for idx in [0, 5, 31, 63] {
+            let fd_q = (loss(&shifted(&q, idx, h), &k, &v) - loss(&shifted(&q, idx, -h), &k, &v))
+                / (2_f64 * h);
+            let fd_k = (loss(&q, &shifted(&k, idx, h), &v) - loss(&q, &shifted(&k, idx, -h), &v))
+                / (2_f64 * h);
+            let fd_v = (loss(&q, &k, &shifted(&v, idx, h)) - loss(&q, &k, &shifted(&v, idx, -h)))
+                / (2_f64 * h);
+            assert!((fd_q - r.dq[idx]).abs() < 1e-6);
+            assert!((fd_k - r.dk[idx]).abs() < 1e-6);
+            assert!((fd_v - r.dv[idx]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn flash_attn_backward_pass() {
+        let n = SEQ_LEN as usize;
+
+        let config = FlashAttnBwdConfig {
+            seq_len: SEQ_LEN,
+            ..Default::default()
+        };
+
+        let (q, k, v, d_o) = inputs();
+        let r = reference(&q, &k, &v, &d_o, &config.score_scale);
+
+        let mut ctx = ProgramBuilder::default();
+
+        let mut generator = |data: Vec<f64>| {
+            let (sender, receiver) = ctx.bounded::<f64>(config.chan_size);
+            ctx.add_child(GeneratorContext::new(move || data.into_iter(), sender));
+            receiver
+        };
+        // K and V are re-streamed once per row
+        let q_receiver = generator(q.clone());
+        let kt_receiver = generator(k.repeat(n));
+        let v_receiver = generator(v.repeat(n));
+        let o_receiver = generator(r.o.clone());
+        let do_receiver = generator(d_o.clone());
+        let lse_receiver = generator(r.lse.clone());
+
+        let (dq, dk, dv) = flash_attn_backward(
+            &mut ctx,
+            q_receiver,
+            kt_receiver,
+            v_receiver,
+            o_receiver,
+            do_receiver,
+            lse_receiver,
+            &config,
+        );
+        for (out, expected) in [(dq, r.dq), (dk, r.dk), (dv, r.dv)] {
+            ctx.add_child(ApproxCheckerContext::new(
+                move || expected.into_iter(),
+                out,
+                |a, b| (a - b).abs() < 1e-9 * b.abs().max(1_f64),
+            ));
+        }
+
+        let initialized = ctx.initialize(Default::default()).unwrap();
+        #[cfg(feature = "dot")]
+        println!("{}", initialized.to_dot_string());
+
+        let summary = initialized.run(Default::default());
+        dbg!(summary.elapsed_cycles());
+    }
+
+    #[test]
+    fn flash_attn_forward_backward() {
+        // O and lse come from the forward pipeline instead of the software reference
+        for score_scale in score_scales() {
+            forward_backward(score_scale);
+        }
+    }
+
+    fn forward_backward(score_scale: ScoreScale) {
+        let n = SEQ_LEN as usize;
+
+        let fwd_config = FlashAttnConfig {
+            seq_len: SEQ_LEN,
+            score_scale: score_scale.clone(),
+            ..Default::default()
+        };
+        let bwd_config = FlashAttnBwdConfig {
+            seq_len: SEQ_LEN,
+            score_scale,
+            ..Default::default()
+        };
+
+        let (q, k, v, d_o) = inputs();
+        let r = reference(&q, &k, &v, &d_o, &bwd_config.score_scale);
+
+        let mut ctx = ProgramBuilder::default();
+
+        let mut generator = |data: Vec<f64>| {
+            let (sender, receiver) = ctx.bounded::<f64>(bwd_config.chan_size);
+            ctx.add_child(GeneratorContext::new(move || data.into_iter(), sender));
+            receiver
+        };
+        // Both passes re-stream K and V once per row
+        let fwd_q = generator(q.clone());
+        let fwd_kt = generator(k.repeat(n));
+        let fwd_v = generator(v.repeat(n));
+        let bwd_q = generator(q.clone());
+        let bwd_kt = generator(k.repeat(n));
+        let bwd_v = generator(v.repeat(n));
+        let d_o_receiver = generator(d_o.clone());
+
+        let (o, lse) = flash_attn_lse(&mut ctx, fwd_q, fwd_kt, fwd_v, &fwd_config, SEQ_LEN);
+        let (dq, dk, dv) = flash_attn_backward(
+            &mut ctx,
+            bwd_q,
+            bwd_kt,
+            bwd_v,
+            o,
+            d_o_receiver,
+            lse,
+            &bwd_config,
+        );
+        for (out, expected) in [(dq, r.dq), (dk, r.dk), (dv, r.dv)] {
+            ctx.add_child(ApproxCheckerContext::new(
+                move || expected.into_iter(),
+                out,
+                |a, b| (a - b).abs() < 1e-9 * b.abs().max(1_f64),
+            ));
+        }
+
+        let initialized = ctx.initialize(Default::default()).unwrap();
+        let summary = initialized.run(Default::default());
+        dbg!(summary.elapsed_cycles());
+    }
+}
@@ -0,0 +1,165 @@
+#[cfg(test)]
+mod tests {
+    use dam::{
+        simulation::ProgramBuilder,
+        utility_contexts::{ApproxCheckerContext, GeneratorContext},
+    };
+
+    use crate::{
+        mask::{window::SlidingWindow, AttnMask},
+        node::{
+            bias::{alibi_slopes, AlibiBias},
+            streamattn_qkt::QKTExp,
+        },
+    };
+
+    const QKT_LATENCY: u64 = 11;
+    const BIAS_LATENCY: u64 = 3;
+    const INIT_INTERVAL: u64 = 1;
+    const SEQ_LEN: u64 = 64;
+
+    fn q_val(i: u64) -> f64 {
+        (i as f64) * 0.01_f64
+    }
+
+    fn k_val(j: u64) -> f64 {
+        0.1_f64 + 0.01_f64 * ((j % 5) as f64)
+    }
+
+    // Runs QKTExp over the (row, key) pairs of 'key_indices' with an ALiBi bias and checks every score
+    fn run_alibi(key_indices: Vec<Vec<u64>>, slope: f64) -> u64 {
+        let chan_size = 2; // FIFO Depth
+        let pairs: Vec<(u64, u64)> = key_indices
+            .iter()
+            .enumerate()
+            .flat_map(|(i, keys)| keys.iter().map(move |j| (i as u64, *j)))
+            .collect();
+        let keys: Vec<u64> = pairs.iter().map(|(_, j)| *j).collect();
+        let expected: Vec<f64> = pairs
+            .iter()
+            .map(|(i, j)| (q_val(*i) * k_val(*j) - slope * (i.abs_diff(*j) as f64)).exp())
+            .collect();
+
+        let mut ctx = ProgramBuilder::default();
+
+        let (q_sender, q_receiver) = ctx.bounded::<f64>(chan_size);
+        let (kt_sender, kt_receiver) = ctx.bounded::<f64>(chan_size);
+        let (bias_sender, bias_receiver) = ctx.bounded::<f64>(chan_size);
+        let num_rows = key_indices.len() as u64;
+        ctx.add_child(GeneratorContext::new(
+            move || (0..num_rows).map(q_val),
+            q_sender,
+        ));
+        ctx.add_child(GeneratorContext::new(
+            move || keys.into_iter().map(k_val),
+            kt_sender,
+        ));
+        let row_lengths = key_indices.iter().map(|k| k.len() as u64).collect();
+        ctx.add_child(
+            AlibiBias::new(bias_sender, slope, 1, INIT_INTERVAL, SEQ_LEN, SEQ_LEN)
+                .with_key_indices(key_indices),
+        );
+
+        let (out_sender, out_receiver) =
+            ctx.bounded::<f64>(chan_size + (QKT_LATENCY + BIAS_LATENCY - 1) as usize);
+        ctx.add_child(
+            QKTExp::new(
+                q_receiver,
+                kt_receiver,
+                vec![out_sender],
+                QKT_LATENCY,
+                INIT_INTERVAL,
+                SEQ_LEN,
+            )
+            .with_row_lengths(row_lengths)
+            .with_bias(bias_receiver, BIAS_LATENCY),
+        );
+
+        ctx.add_child(ApproxCheckerContext::new(
+            move || expected.into_iter(),
+            out_receiver,
+            |a, b| (a - b).abs() < 0.0001,
+        ));
+
+        let initialized = ctx.initialize(Default::default()).unwrap();
+        let summary = initialized.run(Default::default());
+        summary.elapsed_cycles().unwrap()
+    }
+
+    #[test]
+    fn alibi_bias() {
+        let slopes = alibi_slopes(8);
+        assert_eq!(slopes.len(), 8);
+        assert!((slopes[0] - 0.5_f64).abs() < 1e-12);
+        assert!((slopes[7] - 1_f64 / 256_f64).abs() < 1e-12);
+
+        let dense = (0..SEQ_LEN).map(|_i| (0..SEQ_LEN).collect()).collect();
+        let cycles = run_alibi(dense, slopes[0]);
+        // The bias adder only deepens the pipeline, the II is unchanged
+        assert!(cycles >= SEQ_LEN * SEQ_LEN + QKT_LATENCY + BIAS_LATENCY);
+        assert!(cycles < SEQ_LEN * SEQ_LEN + 4 * (QKT_LATENCY + BIAS_LATENCY));
+
+        // Distances follow the key indices of a masked stream
+        let mask = SlidingWindow::new(SEQ_LEN, 8);
+        let windowed = (0..SEQ_LEN).map(|i| mask.key_indices(i)).collect();
+        run_alibi(windowed, slopes[3]);
+    }
+
+    #[test]
+    fn relative_position_bias() {
+        // A learned bias table indexed by clipped relative distance, streamed from memory
+        const MAX_DISTANCE: u64 = 16;
+        let table: Vec<f64> = (0..=MAX_DISTANCE)
+            .map(|d| -0.05_f64 * (d as f64).sqrt())
+            .collect();
+        let bias_of = move |idx: u64| {
+            let (i, j) = (idx / SEQ_LEN, idx % SEQ_LEN);
+            table[i.abs_diff(j).min(MAX_DISTANCE) as usize]
+        };
+
+        let out_bias_of = bias_of.clone();
+
+        let chan_size = 2; // FIFO Depth
+        let mut ctx = ProgramBuilder::default();
+
+        let (q_sender, q_receiver) = ctx.bounded::<f64>(chan_size);
+        let (kt_sender, kt_receiver) = ctx.bounded::<f64>(chan_size);
+        let (bias_sender, bias_receiver) = ctx.bounded::<f64>(chan_size);
+        ctx.add_child(GeneratorContext::new(|| (0..SEQ_LEN).map(q_val), q_sender));
+        ctx.add_child(GeneratorContext::new(
+            || (0..(SEQ_LEN * SEQ_LEN)).map(|idx| k_val(idx % SEQ_LEN)),
+            kt_sender,
+        ));
+        ctx.add_child(GeneratorContext::new(
+            move || (0..(SEQ_LEN * SEQ_LEN)).map(bias_of),
+            bias_sender,
+        ));
+
+        let (out_sender, out_receiver) =
+            ctx.bounded::<f64>(chan_size + (QKT_LATENCY + BIAS_LATENCY - 1) as usize);
+        ctx.add_child(
+            QKTExp::new(
+                q_receiver,
+                kt_receiver,
+                vec![out_sender],
+                QKT_LATENCY,
+                INIT_INTERVAL,
+                SEQ_LEN,
+            )
+            .with_bias(bias_receiver, BIAS_LATENCY),
+        );
+
+        let out_iter = move || {
+            (0..(SEQ_LEN * SEQ_LEN)).map(move |idx| {
+                (q_val(idx / SEQ_LEN) * k_val(idx % SEQ_LEN) + out_bias_of(idx)).exp()
+            })
+        };
+        ctx.add_child(ApproxCheckerContext::new(out_iter, out_receiver, |a, b| {
+            (a - b).abs() < 0.0001
+        }));
+
+        let initialized = ctx.initialize(Default::default()).unwrap();
+        let summary = initialized.run(Default::default());
+        assert!(summary.elapsed_cycles().unwrap() >= SEQ_LEN * SEQ_LEN);
+    }
+}
@@ -0,0 +1,57 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        analysis::traffic::Architecture,
+        graph::masked::run_masked_attn,
+        mask::{block_sparse::BlockSparse, AttnMask, Dense},
+    };
+
+    #[test]
+    fn block_sparse_attn() {
+        const SEQ_LEN: u64 = 256;
+        const BLOCK_SIZE: u64 = 32;
+
+        let mask = BlockSparse::new(SEQ_LEN, BLOCK_SIZE)
+            .with_global(1)
+            .with_local(1)
+            .with_random(1, 7);
+        let num_blocks = mask.num_blocks();
+        for r in 0..num_blocks {
+            assert!(mask.is_present(r, r));
+            assert!(mask.is_present(r, 0));
+            assert!(mask.is_present(0, r));
+        }
+        assert!(mask.density() < 0.75);
+        // Only present blocks are streamed
+        assert_eq!(
+            mask.num_keys(),
+            ((mask.density() * ((num_blocks * num_blocks) as f64)).round() as u64)
+                * BLOCK_SIZE
+                * BLOCK_SIZE
+        );
+
+        for arch in [Architecture::Streamed, Architecture::Flash] {
+            let dense = run_masked_attn(arch, &Dense::new(SEQ_LEN));
+            let sparse = run_masked_attn(arch, &mask);
+            println!("{}", dense);
+            println!("{}", sparse);
+
+            assert!(dense.error.max_abs_err < 0.0001);
+            assert!(sparse.mask_error.max_abs_err < 0.0001);
+            // Skipped blocks cost no cycles
+            let dense_cycles = dense.elapsed_cycles as f64;
+            assert!((sparse.elapsed_cycles as f64) < dense_cycles * (mask.density() + 0.1));
+            // Dropping blocks perturbs the output without breaking it
+            assert!(sparse.error.max_abs_err > dense.error.max_abs_err);
+            assert!(sparse.error.max_abs_err < 0.5);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn block_sparse_needs_a_block_per_row() {
+        // Without any blocks no query row attends to a key
+        let mask = BlockSparse::new(64, 16);
+        run_masked_attn(Architecture::Flash, &mask);
+    }
+}
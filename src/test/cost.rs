@@ -0,0 +1,55 @@
+#[cfg(test)]
+mod tests {
+    use crate::analysis::{
+        cost::{run_attn_cost, EnergyModel, OpKind, Roofline},
+        traffic::Architecture,
+    };
+
+    #[test]
+    fn attn_energy_roofline() {
+        const SEQ_LEN: u64 = 64;
+        const N2: u64 = SEQ_LEN * SEQ_LEN;
+
+        let roofline = || Roofline {
+            peak_ops_per_cycle: 4_f64,
+            dram_bytes_per_cycle: 16_f64,
+        };
+        let streamed = run_attn_cost(
+            Architecture::Streamed,
+            SEQ_LEN,
+            EnergyModel::default(),
+            roofline(),
+            1_f64,
+        );
+        let flash = run_attn_cost(
+            Architecture::Flash,
+            SEQ_LEN,
+            EnergyModel::default(),
+            roofline(),
+            1_f64,
+        );
+        println!("{}", streamed);
+        println!("{}", flash);
+
+        // Streamed: QK^T + exp, row sum, normalization, PV
+        assert_eq!(streamed.count(OpKind::Mul), 2 * N2);
+        assert_eq!(streamed.count(OpKind::Add), 2 * (N2 - SEQ_LEN));
+        assert_eq!(streamed.count(OpKind::Exp), N2);
+        assert_eq!(streamed.count(OpKind::Div), N2);
+
+        // Flash: running max/sum/output rescale every element, one division per row
+        assert_eq!(flash.count(OpKind::Mul), 4 * N2);
+        assert_eq!(flash.count(OpKind::Add), 5 * N2);
+        assert_eq!(flash.count(OpKind::Exp), 3 * N2);
+        assert_eq!(flash.count(OpKind::Div), SEQ_LEN);
+
+        // Q once, K and V once per row
+        for report in [&streamed, &flash] {
+            assert_eq!(report.count(OpKind::DramByte), (2 * N2 + SEQ_LEN) * 8);
+            assert!(report.memory_bound());
+            assert!(report.power_mw() > 0_f64);
+        }
+        // DRAM dominates both designs
+        assert!(streamed.energy_pj(OpKind::DramByte) > 0.5 * streamed.total_energy_pj());
+    }
+}
@@ -0,0 +1,55 @@
+#[cfg(test)]
+mod tests {
+    use crate::graph::{
+        decode::{run_decode_attn, DecodeConfig},
+        multihead::{synthetic_k, synthetic_q, synthetic_v},
+    };
+
+    // Step t attends over the prompt and the keys appended by steps 0..=t
+    fn decode_reference(prompt_len: u64, num_steps: u64) -> Vec<f64> {
+        (0..num_steps)
+            .map(|t| {
+                let q = synthetic_q(t);
+                let keys = 0..(prompt_len + t + 1);
+                let weights: Vec<f64> = keys
+                    .clone()
+                    .map(|key| (q * synthetic_k(key)).exp().exp())
+                    .collect();
+                let out: f64 = weights
+                    .iter()
+                    .zip(keys)
+                    .map(|(w, key)| w * synthetic_v(key))
+                    .sum();
+                out / weights.iter().sum::<f64>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn kv_cache_decode() {
+        const PROMPT_LEN: u64 = 128;
+        const NUM_STEPS: u64 = 8;
+
+        let config = DecodeConfig {
+            num_steps: NUM_STEPS,
+            ..Default::default()
+        };
+        let report = run_decode_attn(PROMPT_LEN, &config);
+        println!("{}", report);
+
+        assert_eq!(report.token_latencies.len(), NUM_STEPS as usize);
+        for (out, expected) in report
+            .outputs
+            .iter()
+            .zip(decode_reference(PROMPT_LEN, NUM_STEPS))
+        {
+            assert!((out - expected).abs() < 0.0001);
+        }
+        // Every step streams one more cached key through the II-bound running ops
+        for w in report.token_latencies.windows(2) {
+            assert!(w[1] > w[0]);
+        }
+        // The cache is streamed once per token, so latency scales with its length
+        assert!(report.token_latencies[0] >= config.attn.muticycle_ii * PROMPT_LEN);
+    }
+}
@@ -0,0 +1,190 @@
+#[cfg(test)]
+mod tests {
+    use dam::{
+        simulation::{DotConvertible, ProgramBuilder},
+        utility_contexts::GeneratorContext,
+    };
+
+    use crate::{
+        analysis::error_report::{ErrorAnalysisContext, ErrorMetric, ErrorReport},
+        node::{
+            exp_unit::ExpUnit,
+            streamattn_binary::{Binary, BinaryOpType},
+            streamattn_matvec::MatVecProd,
+            streamattn_qkt::QKTExp,
+            streamattn_reduce::{ReduceOp, ReduceOpType},
+        },
+    };
+
+    const SEQ_LEN: u64 = 64;
+
+    fn q_val(i: u64) -> f64 {
+        (i as f64) * 0.01_f64
+    }
+
+    fn k_val(j: u64) -> f64 {
+        if j == 0 {
+            0.11_f64
+        } else {
+            0.1_f64
+        }
+    }
+
+    fn v_val(j: u64) -> f64 {
+        1_f64 + ((j % 4) as f64) * 0.5_f64
+    }
+
+    fn reference_attn() -> Vec<f64> {
+        (0..SEQ_LEN)
+            .map(|i| {
+                let p: Vec<f64> = (0..SEQ_LEN).map(|j| (q_val(i) * k_val(j)).exp()).collect();
+                let rowsum: f64 = p.iter().sum();
+                (0..SEQ_LEN)
+                    .map(|j| p[j as usize] / rowsum * v_val(j))
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn streamed_attn_error(exp_unit: ExpUnit) -> ErrorReport {
+        const QKT_LATENCY: u64 = 11;
+        const REDUCE_LATENCY: u64 = 2;
+        const BINARY_LATENCY: u64 = 8;
+        const MATVEC_LATENCY: u64 = 12;
+        const INIT_INTERVAL: u64 = 1;
+
+        let chan_size_long = (SEQ_LEN as usize) + 2;
+
+        let chan_size = 2; // FIFO Depth
+
+        let mut ctx = ProgramBuilder::default();
+
+        // Generators
+        let (q_sender, q_receiver) = ctx.bounded::<f64>(chan_size);
+        let (kt_sender, kt_receiver) = ctx.bounded::<f64>(chan_size);
+        let (v_sender, v_receiver) = ctx.bounded::<f64>(chan_size);
+        let q_iter = || (0..(SEQ_LEN)).map(q_val);
+        let kt_iter = || (0..(SEQ_LEN * SEQ_LEN)).map(|i| k_val(i % SEQ_LEN));
+        let v_iter = || (0..(SEQ_LEN * SEQ_LEN)).map(|i| v_val(i % SEQ_LEN));
+        ctx.add_child(GeneratorContext::new(q_iter, q_sender)); // Q : [1,D] shaped vectors
+        ctx.add_child(GeneratorContext::new(kt_iter, kt_sender)); // KT: [D,1] shaped vectors
+        ctx.add_child(GeneratorContext::new(v_iter, v_sender)); // V: [D,1] shaped vectors
+
+        // QKT & Exp block
+        let (qkt_exp_short_sender, qkt_exp_short_receiver) =
+            ctx.bounded::<f64>(chan_size + (QKT_LATENCY as usize));
+        let (qkt_exp_long_sender, qkt_exp_long_receiver) = ctx.bounded::<f64>(chan_size_long);
+        ctx.add_child(
+            QKTExp::new(
+                q_receiver,
+                kt_receiver,
+                vec![qkt_exp_short_sender, qkt_exp_long_sender],
+                QKT_LATENCY,
+                INIT_INTERVAL,
+                SEQ_LEN,
+            )
+            .with_exp_unit(exp_unit),
+        );
+
+        // Reduce
+        let (rowsum_sender, rowsum_receiver) =
+            ctx.bounded::<f64>(chan_size + (REDUCE_LATENCY as usize));
+        ctx.add_child(ReduceOp::new(
+            qkt_exp_short_receiver,
+            rowsum_sender,
+            REDUCE_LATENCY,
+            INIT_INTERVAL,
+            SEQ_LEN,
+            SEQ_LEN,
+            ReduceOpType::Sum,
+        ));
+
+        // Div
+        let (div_sender, div_receiver) = ctx.bounded::<f64>(chan_size + (BINARY_LATENCY as usize));
+        ctx.add_child(Binary::<f64>::new(
+            qkt_exp_long_receiver,
+            rowsum_receiver,
+            div_sender,
+            BINARY_LATENCY,
+            INIT_INTERVAL,
+            SEQ_LEN,
+            SEQ_LEN,
+            BinaryOpType::Div,
+        ));
+
+        // Multiply with V
+        let (out_sender, out_receiver) = ctx.bounded::<f64>(chan_size);
+        ctx.add_child(MatVecProd::new(
+            div_receiver,
+            v_receiver,
+            out_sender,
+            MATVEC_LATENCY,
+            INIT_INTERVAL,
+            SEQ_LEN,
+            SEQ_LEN,
+        ));
+
+        // Error analysis
+        let analysis = ErrorAnalysisContext::new(reference_attn, out_receiver);
+        let report = analysis.report();
+        ctx.add_child(analysis);
+
+        let initialized = ctx.initialize(Default::default()).unwrap();
+        #[cfg(feature = "dot")]
+        println!("{}", initialized.to_dot_string());
+
+        let summary = initialized.run(Default::default());
+        dbg!(summary.elapsed_cycles());
+
+        let report = report.lock().unwrap().clone();
+        println!("{}", report);
+        report
+    }
+
+    #[test]
+    fn ulp_distance() {
+        assert_eq!(1_f64.ulp_distance(1_f64), 0);
+        assert_eq!(1_f64.ulp_distance(1_f64 + f64::EPSILON), 1);
+        assert_eq!(0_f64.ulp_distance(-0_f64), 0);
+        assert_eq!(
+            (-f64::MIN_POSITIVE).ulp_distance(f64::MIN_POSITIVE),
+            1 << 53
+        );
+        assert_eq!(1_f32.ulp_distance(1_f32 + f32::EPSILON), 1);
+    }
+
+    #[test]
+    fn non_finite_errors() {
+        let mut report = ErrorReport::default();
+        report.record(1_f64, 1_f64);
+        report.record(f64::INFINITY, f64::INFINITY);
+        report.record(f64::INFINITY, 1_f64);
+        assert_eq!(report.exact_matches, 2);
+        assert_eq!(report.non_finite, 1);
+        assert_eq!(report.max_abs_err, f64::INFINITY);
+
+        // A NaN is counted and poisons the max instead of landing in bucket 0
+        report.record(f64::NAN, 1_f64);
+        assert_eq!(report.count, 4);
+        assert_eq!(report.non_finite, 2);
+        assert!(report.max_abs_err.is_nan());
+        assert_eq!(report.abs_err_histogram.iter().sum::<u64>(), 0);
+        assert_eq!(report.ulp_histogram.iter().sum::<u64>(), 2);
+        report.record(1_f64 + f64::EPSILON, 1_f64);
+        assert!(report.max_abs_err.is_nan());
+    }
+
+    #[test]
+    fn streamed_attn_exp_error() {
+        let exact = streamed_attn_error(ExpUnit::exact(0, 1));
+        let pwl = streamed_attn_error(ExpUnit::piecewise_linear(8, 2, 1));
+        let shift = streamed_attn_error(ExpUnit::exp2_shift(1, 1));
+
+        assert_eq!(exact.count, SEQ_LEN);
+        assert!(exact.max_rel_err < 1e-12);
+        assert!(pwl.max_rel_err < 1e-2);
+        assert!(shift.max_rel_err < 1e-1);
+        assert!(exact.max_abs_err <= pwl.max_abs_err);
+        assert!(exact.max_ulp <= shift.max_ulp);
+    }
+}
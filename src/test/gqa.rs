@@ -0,0 +1,32 @@
+#[cfg(test)]
+mod tests {
+    use crate::graph::{flashattn::FlashAttnConfig, gqa::run_grouped_query_attn};
+
+    #[test]
+    fn grouped_query_attn() {
+        const NUM_Q_HEADS: u64 = 4;
+
+        let config = FlashAttnConfig {
+            seq_len: 64,
+            ..Default::default()
+        };
+
+        let mha = run_grouped_query_attn(NUM_Q_HEADS, NUM_Q_HEADS, &config);
+        let gqa = run_grouped_query_attn(NUM_Q_HEADS, 2, &config);
+        let mqa = run_grouped_query_attn(NUM_Q_HEADS, 1, &config);
+        println!("{}", mha);
+        println!("{}", gqa);
+        println!("{}", mqa);
+
+        // Each K/V group is streamed once, N^2 elements each of K and V
+        for report in [&mha, &gqa, &mqa] {
+            assert_eq!(
+                report.kv_elements_read,
+                2 * report.num_kv_heads * config.seq_len * config.seq_len
+            );
+        }
+        assert_eq!(mha.kv_bandwidth_saving(), 0_f64);
+        assert_eq!(gqa.kv_bandwidth_saving(), 0.5_f64);
+        assert_eq!(mqa.kv_bandwidth_saving(), 0.75_f64);
+    }
+}
@@ -0,0 +1,49 @@
+#[cfg(test)]
+mod tests {
+    use dam::{
+        simulation::{DotConvertible, ProgramBuilder},
+        utility_contexts::ApproxCheckerContext,
+    };
+
+    use crate::graph::{
+        flashattn::{flash_attn_lse, FlashAttnConfig},
+        multihead::{add_checker, add_generators, flash_reference, synthetic_k, synthetic_q},
+    };
+
+    #[test]
+    fn flash_attn_logsumexp() {
+        const SEQ_LEN: u64 = 64;
+
+        let config = FlashAttnConfig {
+            seq_len: SEQ_LEN,
+            ..Default::default()
+        };
+
+        let mut ctx = ProgramBuilder::default();
+
+        let (q, kt, v) = add_generators(&mut ctx, SEQ_LEN, SEQ_LEN, config.chan_size);
+        let (out, lse) = flash_attn_lse(&mut ctx, q, kt, v, &config, SEQ_LEN);
+        add_checker(&mut ctx, out, flash_reference(0..SEQ_LEN, SEQ_LEN));
+
+        // The running ops see the exponentiated scores of QKTExp, lse_i = ln(sum_j exp(s_ij))
+        let lse_iter = || {
+            (0..SEQ_LEN).map(|i| {
+                let q = synthetic_q(i);
+                (0..SEQ_LEN)
+                    .map(|j| (q * synthetic_k(j)).exp().exp())
+                    .sum::<f64>()
+                    .ln()
+            })
+        };
+        ctx.add_child(ApproxCheckerContext::new(lse_iter, lse, |a, b| {
+            (a - b).abs() < 1e-9 * b.abs().max(1_f64)
+        }));
+
+        let initialized = ctx.initialize(Default::default()).unwrap();
+        #[cfg(feature = "dot")]
+        println!("{}", initialized.to_dot_string());
+
+        let summary = initialized.run(Default::default());
+        dbg!(summary.elapsed_cycles());
+    }
+}
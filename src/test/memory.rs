@@ -0,0 +1,182 @@
+#[cfg(test)]
+mod tests {
+    use dam::{
+        simulation::{DotConvertible, ProgramBuilder},
+        utility_contexts::CheckerContext,
+    };
+
+    use crate::{
+        graph::{
+            flashattn::{flash_attn, FlashAttnConfig},
+            multihead::{add_checker, flash_reference, synthetic_k, synthetic_q, synthetic_v},
+        },
+        memory::{
+            dram::{DramConfig, DramRead},
+            sram::{SramConfig, SramRead},
+            AccessPattern, MemoryStats,
+        },
+    };
+
+    // Q is read once from DRAM, K and V are re-read once per query row
+    // either from an on-chip SRAM or from DRAM.
+    // Returns (elapsed cycles, K stats)
+    fn run_flash_attn_from_memory(
+        config: &FlashAttnConfig,
+        kv_on_chip: bool,
+    ) -> (u64, MemoryStats) {
+        let seq_len = config.seq_len;
+        let q_data: Vec<f64> = (0..seq_len).map(synthetic_q).collect();
+        let kt_data: Vec<f64> = (0..seq_len).map(synthetic_k).collect();
+        let v_data: Vec<f64> = (0..seq_len).map(synthetic_v).collect();
+
+        let mut ctx = ProgramBuilder::default();
+
+        let (q_sender, q_receiver) = ctx.bounded::<f64>(config.chan_size);
+        let (kt_sender, kt_receiver) = ctx.bounded::<f64>(config.chan_size);
+        let (v_sender, v_receiver) = ctx.bounded::<f64>(config.chan_size);
+
+        ctx.add_child(DramRead::new(
+            q_data,
+            AccessPattern::sequential(seq_len),
+            q_sender,
+            DramConfig {
+                bytes_per_cycle: 16,
+                burst_bytes: 64,
+                latency: 100,
+            },
+        ));
+
+        let kt_stats = if kv_on_chip {
+            let sram_config = || SramConfig {
+                capacity: seq_len as usize,
+                num_banks: 4,
+                ports_per_bank: 1,
+                latency: 2,
+            };
+            let kt_sram = SramRead::new(
+                kt_data,
+                AccessPattern::repeated(seq_len, seq_len),
+                kt_sender,
+                sram_config(),
+            );
+            let kt_stats = kt_sram.stats();
+            ctx.add_child(kt_sram);
+            ctx.add_child(SramRead::new(
+                v_data,
+                AccessPattern::repeated(seq_len, seq_len),
+                v_sender,
+                sram_config(),
+            ));
+            kt_stats
+        } else {
+            let dram_config = || DramConfig {
+                bytes_per_cycle: 2,
+                burst_bytes: 64,
+                latency: 100,
+            };
+            let kt_dram = DramRead::new(
+                kt_data,
+                AccessPattern::repeated(seq_len, seq_len),
+                kt_sender,
+                dram_config(),
+            );
+            let kt_stats = kt_dram.stats();
+            ctx.add_child(kt_dram);
+            ctx.add_child(DramRead::new(
+                v_data,
+                AccessPattern::repeated(seq_len, seq_len),
+                v_sender,
+                dram_config(),
+            ));
+            kt_stats
+        };
+
+        let out = flash_attn(
+            &mut ctx,
+            q_receiver,
+            kt_receiver,
+            v_receiver,
+            config,
+            seq_len,
+        );
+        add_checker(&mut ctx, out, flash_reference(0..seq_len, seq_len));
+
+        let initialized = ctx.initialize(Default::default()).unwrap();
+        #[cfg(feature = "dot")]
+        println!("{}", initialized.to_dot_string());
+
+        let summary = initialized.run(Default::default());
+        dbg!(kv_on_chip, summary.elapsed_cycles());
+        let stats = kt_stats.lock().unwrap().clone();
+        (summary.elapsed_cycles().unwrap(), stats)
+    }
+
+    #[test]
+    fn sram_dram_flash_attn() {
+        const SEQ_LEN: u64 = 64;
+
+        let config = FlashAttnConfig {
+            seq_len: SEQ_LEN,
+            ..Default::default()
+        };
+
+        let (sram_cycles, sram_stats) = run_flash_attn_from_memory(&config, true);
+        let (dram_cycles, dram_stats) = run_flash_attn_from_memory(&config, false);
+        println!("SRAM K: {}", sram_stats);
+        println!("DRAM K: {}", dram_stats);
+
+        // Every K re-read is served by the memory
+        assert_eq!(sram_stats.reads, SEQ_LEN * SEQ_LEN);
+        assert_eq!(dram_stats.reads, SEQ_LEN * SEQ_LEN);
+        assert_eq!(dram_stats.bytes_read, SEQ_LEN * SEQ_LEN * 8);
+        assert_eq!(sram_stats.stall_cycles, 0);
+
+        // At 2 B/cycle DRAM delivers one f64 every 4 cycles, slower than the II = 2 pipeline
+        assert!(dram_cycles > sram_cycles);
+        assert!(dram_cycles >= 4 * SEQ_LEN * SEQ_LEN);
+    }
+
+    // Streams 16 words from a 4-bank, single-port SRAM. Returns the SRAM stats
+    fn run_sram_pattern(pattern: AccessPattern) -> MemoryStats {
+        let data: Vec<u64> = (0..16).collect();
+        let expected: Vec<u64> = (0..pattern.num_accesses)
+            .map(|i| data[(pattern.address)(i)])
+            .collect();
+
+        let mut ctx = ProgramBuilder::default();
+        let (sender, receiver) = ctx.bounded::<u64>(32);
+        let sram = SramRead::new(
+            data,
+            pattern,
+            sender,
+            SramConfig {
+                capacity: 16,
+                num_banks: 4,
+                ports_per_bank: 1,
+                latency: 2,
+            },
+        );
+        let stats = sram.stats();
+        ctx.add_child(sram);
+        ctx.add_child(CheckerContext::new(move || expected.into_iter(), receiver));
+        ctx.initialize(Default::default())
+            .unwrap()
+            .run(Default::default());
+
+        let stats = stats.lock().unwrap().clone();
+        stats
+    }
+
+    #[test]
+    fn sram_bank_conflicts_stall() {
+        // Consecutive words hit different banks, four words per cycle
+        let sequential = run_sram_pattern(AccessPattern::sequential(16));
+        assert_eq!(sequential.busy_cycles, 4);
+        assert_eq!(sequential.stall_cycles, 0);
+
+        // A stride of num_banks keeps hitting bank 0 while the other banks idle
+        let strided = run_sram_pattern(AccessPattern::new(16, |i| ((i * 4) % 16) as usize));
+        assert_eq!(strided.busy_cycles, 16);
+        assert_eq!(strided.stall_cycles, 15);
+    }
+}
@@ -1,4 +1,29 @@
+pub mod backward;
+pub mod bias;
+pub mod block_sparse;
+pub mod cost;
+pub mod decode;
+pub mod error_report;
+pub mod exp_unit;
 pub mod flashattn;
+pub mod gqa;
 pub mod incremental_unit_test;
+pub mod lse;
+pub mod memory;
+pub mod multihead;
+pub mod normalizer;
+pub mod quant;
+pub mod recurrence;
+pub mod reduce;
+pub mod resource;
+pub mod rope;
+pub mod row_tiled;
+pub mod score_scale;
+pub mod simd;
+pub mod split_k;
 pub mod streamattn;
+pub mod tiled_flashattn;
+pub mod traffic;
+pub mod tree_reduce;
 pub mod unit_tests;
+pub mod window;
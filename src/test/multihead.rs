@@ -0,0 +1,28 @@
+#[cfg(test)]
+mod tests {
+    use crate::graph::{
+        flashattn::FlashAttnConfig,
+        multihead::{run_multi_head_attn, HeadMapping},
+    };
+
+    #[test]
+    fn spatial_vs_temporal_heads() {
+        const NUM_HEADS: u64 = 4;
+        const BATCH_SIZE: u64 = 2;
+
+        let config = FlashAttnConfig {
+            seq_len: 64,
+            ..Default::default()
+        };
+
+        let spatial = run_multi_head_attn(HeadMapping::Spatial, NUM_HEADS, BATCH_SIZE, &config);
+        let temporal = run_multi_head_attn(HeadMapping::Temporal, NUM_HEADS, BATCH_SIZE, &config);
+        println!("{}", spatial);
+        println!("{}", temporal);
+
+        // Time-multiplexing serializes the heads, replication runs them side by side
+        assert_eq!(spatial.num_pipelines, NUM_HEADS * BATCH_SIZE);
+        assert_eq!(temporal.num_pipelines, 1);
+        assert!(temporal.elapsed_cycles > spatial.elapsed_cycles * (NUM_HEADS * BATCH_SIZE - 1));
+    }
+}
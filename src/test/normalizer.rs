@@ -0,0 +1,140 @@
+#[cfg(test)]
+mod tests {
+    use dam::{
+        simulation::ProgramBuilder,
+        utility_contexts::{ApproxCheckerContext, GeneratorContext},
+    };
+
+    use crate::{
+        graph::{
+            normalizer::{linear_attn, run_normalizer_attn, Normalizer, NORMALIZERS},
+            streamattn::StreamAttnConfig,
+        },
+        node::{normalizer::linear_features, score_scale::ScoreScale},
+    };
+
+    #[test]
+    fn alternative_normalizers() {
+        const SEQ_LEN: u64 = 256;
+
+        let reports: Vec<_> = NORMALIZERS
+            .iter()
+            .map(|n| run_normalizer_attn(*n, SEQ_LEN, ScoreScale::default()))
+            .collect();
+        for report in reports.iter() {
+            println!("{}", report);
+            assert!(report.error.max_abs_err < 0.0001);
+        }
+        let report = |n: Normalizer| reports.iter().find(|r| r.normalizer == n).unwrap();
+        let softmax = report(Normalizer::Softmax);
+        let sigmoid = report(Normalizer::Sigmoid);
+        let relu2 = report(Normalizer::ReluSquared);
+        let linear = report(Normalizer::Linear);
+
+        // Elementwise normalizers drop the O(N) long FIFO but still stream K/V per row
+        for r in [sigmoid, relu2] {
+            assert!(r.resources.fifo_bits() + (SEQ_LEN * 64) <= softmax.resources.fifo_bits());
+            assert!(r.elapsed_cycles >= SEQ_LEN * SEQ_LEN);
+            assert!(r.elapsed_cycles <= softmax.elapsed_cycles);
+        }
+        // The running KV state makes linear attention O(N) in time and O(1) in storage
+        assert!(linear.elapsed_cycles < 2 * SEQ_LEN);
+        assert!(
+            linear.resources.storage_bits() + (SEQ_LEN * 64) <= softmax.resources.storage_bits()
+        );
+    }
+
+    #[test]
+    fn scaled_normalizers() {
+        const SEQ_LEN: u64 = 64;
+        const SCALE_LATENCY: u64 = 3;
+
+        for n in NORMALIZERS {
+            let plain = run_normalizer_attn(n, SEQ_LEN, ScoreScale::default());
+            let scaled =
+                run_normalizer_attn(n, SEQ_LEN, ScoreScale::temperature(2_f64, SCALE_LATENCY));
+            println!("{}", scaled);
+
+            // The scale reaches every normalizer, including phi(q) of linear attention,
+            // and deepens its pipeline
+            assert!(scaled.error.max_abs_err < 0.0001);
+            assert!(scaled.elapsed_cycles >= plain.elapsed_cycles + SCALE_LATENCY);
+            assert!(
+                scaled.resources.fifo_bits() >= plain.resources.fifo_bits() + SCALE_LATENCY * 64
+            );
+        }
+    }
+
+    #[test]
+    fn linear_attn_depends_on_q() {
+        const NUM_TOKENS: u64 = 64;
+
+        // K and Q of both signs, so the two features weight the keys differently
+        fn q_val(i: u64) -> f64 {
+            if i.is_multiple_of(3) {
+                -1_f64
+            } else {
+                1_f64
+            }
+        }
+        fn k_val(j: u64) -> f64 {
+            if j.is_multiple_of(2) {
+                -1_f64
+            } else {
+                1_f64
+            }
+        }
+        fn v_val(j: u64) -> f64 {
+            1_f64 + 0.1_f64 * ((j % 13) as f64)
+        }
+        let reference = |q: fn(u64) -> f64| -> Vec<f64> {
+            (0..NUM_TOKENS)
+                .map(|i| {
+                    let phi_q = linear_features(q(i));
+                    let (mut num, mut den) = (0_f64, 0_f64);
+                    for j in 0..=i {
+                        let phi_k = linear_features(k_val(j));
+                        for (a, b) in phi_q.iter().zip(phi_k.iter()) {
+                            num += a * b * v_val(j);
+                            den += a * b;
+                        }
+                    }
+                    num / den
+                })
+                .collect()
+        };
+        let expected = reference(q_val);
+        let flipped = reference(|i| -q_val(i));
+        assert!(expected
+            .iter()
+            .zip(flipped.iter())
+            .any(|(a, b)| (a - b).abs() > 0.01));
+
+        let config = StreamAttnConfig::default();
+        let mut ctx = ProgramBuilder::default();
+        let (q_sender, q) = ctx.bounded::<f64>(config.chan_size);
+        let (k_sender, k) = ctx.bounded::<f64>(config.chan_size);
+        let (v_sender, v) = ctx.bounded::<f64>(config.chan_size);
+        ctx.add_child(GeneratorContext::new(
+            move || (0..NUM_TOKENS).map(q_val),
+            q_sender,
+        ));
+        ctx.add_child(GeneratorContext::new(
+            move || (0..NUM_TOKENS).map(k_val),
+            k_sender,
+        ));
+        ctx.add_child(GeneratorContext::new(
+            move || (0..NUM_TOKENS).map(v_val),
+            v_sender,
+        ));
+        let out = linear_attn(&mut ctx, q, k, v, &config, NUM_TOKENS);
+        ctx.add_child(ApproxCheckerContext::new(
+            move || expected.into_iter(),
+            out,
+            |a, b| (a - b).abs() < 0.0001,
+        ));
+        ctx.initialize(Default::default())
+            .unwrap()
+            .run(Default::default());
+    }
+}
@@ -0,0 +1,205 @@
+#[cfg(test)]
+mod tests {
+    use dam::{
+        simulation::{DotConvertible, ProgramBuilder},
+        utility_contexts::{ApproxCheckerContext, CheckerContext, GeneratorContext},
+    };
+
+    use crate::node::{
+        quant_convert::{Dequantize, Quantize, Requantize},
+        quant_qkt::{FixedExp, QKTExpFixed},
+        streamattn_binary::{Binary, BinaryOpType},
+        streamattn_matvec::MatVecProd,
+        streamattn_reduce::{ReduceOp, ReduceOpType},
+    };
+
+    #[test]
+    fn fixed_exp_accuracy() {
+        const FRAC_BITS: u32 = 12;
+        let one = (1 << FRAC_BITS) as f64;
+        let poly = FixedExp::polynomial(FRAC_BITS);
+        let lut = FixedExp::lut(FRAC_BITS, 6);
+        for i in -80..40 {
+            let x = (i as f64) * 0.1;
+            let x_fixed = (x * one).round() as i64;
+            let expected = x.exp();
+            let poly_res = (poly.eval(x_fixed) as f64) / one;
+            let lut_res = (lut.eval(x_fixed) as f64) / one;
+            // Relative error of the 2^f approximation plus one LSB of quantization
+            assert!((poly_res - expected).abs() <= expected * 0.005 + 2.0 / one);
+            assert!((lut_res - expected).abs() <= expected * 0.01 + 2.0 / one);
+        }
+    }
+
+    #[test]
+    fn stream_int8_attn() {
+        const QUANT_LATENCY: u64 = 1;
+        const QKT_LATENCY: u64 = 11;
+        const REDUCE_LATENCY: u64 = 2;
+        const BINARY_LATENCY: u64 = 8;
+        const MATVEC_LATENCY: u64 = 12;
+        const INIT_INTERVAL: u64 = 1;
+
+        const SEQ_LEN: u64 = 64;
+        const FRAC_BITS: u32 = 12;
+        const Q_SCALE: f64 = 0.01;
+        const KT_SCALE: f64 = 0.001;
+        let chan_size_long = (SEQ_LEN + REDUCE_LATENCY + QKT_LATENCY) as usize + 2;
+
+        let chan_size = 2; // FIFO Depth
+
+        let mut ctx = ProgramBuilder::default();
+
+        // Generators
+        let (q_sender, q_receiver) = ctx.bounded::<f64>(chan_size);
+        let (kt_sender, kt_receiver) = ctx.bounded::<f64>(chan_size);
+        let (v_sender, v_receiver) = ctx.bounded::<i32>(chan_size);
+        let q_iter = || (0..(SEQ_LEN)).map(|i| (i as f64) * 0.01_f64);
+        let kt_iter =
+            || (0..(SEQ_LEN * SEQ_LEN)).map(|i| if i % SEQ_LEN == 0 { 0.11_f64 } else { 0.1_f64 });
+        let v_iter = || (0..(SEQ_LEN * SEQ_LEN)).map(|_i| 1_i32);
+        ctx.add_child(GeneratorContext::new(q_iter, q_sender)); // Q : [1,D] shaped vectors
+        ctx.add_child(GeneratorContext::new(kt_iter, kt_sender)); // KT: [D,1] shaped vectors
+        ctx.add_child(GeneratorContext::new(v_iter, v_sender)); // V: integer, scale 1
+
+        // Quantize Q and K to int8
+        let (q_i8_sender, q_i8_receiver) = ctx.bounded::<i8>(chan_size);
+        let (kt_i8_sender, kt_i8_receiver) = ctx.bounded::<i8>(chan_size);
+        ctx.add_child(Quantize::<f64, i8>::new(
+            q_receiver,
+            q_i8_sender,
+            Q_SCALE,
+            QUANT_LATENCY,
+            INIT_INTERVAL,
+            SEQ_LEN,
+        ));
+        ctx.add_child(Quantize::<f64, i8>::new(
+            kt_receiver,
+            kt_i8_sender,
+            KT_SCALE,
+            QUANT_LATENCY,
+            INIT_INTERVAL,
+            SEQ_LEN * SEQ_LEN,
+        ));
+
+        // QKT & fixed-point Exp block (Q.12 output)
+        let (qkt_exp_short_sender, qkt_exp_short_receiver) =
+            ctx.bounded::<i32>(chan_size + (QKT_LATENCY as usize));
+        let (qkt_exp_long_sender, qkt_exp_long_receiver) = ctx.bounded::<i32>(chan_size_long);
+        ctx.add_child(QKTExpFixed::new(
+            q_i8_receiver,
+            kt_i8_receiver,
+            vec![qkt_exp_short_sender, qkt_exp_long_sender],
+            Q_SCALE * KT_SCALE,
+            FixedExp::polynomial(FRAC_BITS),
+            QKT_LATENCY,
+            INIT_INTERVAL,
+            SEQ_LEN,
+            1,
+        ));
+
+        // Reduce (Q.12)
+        let (rowsum_sender, rowsum_receiver) =
+            ctx.bounded::<i32>(chan_size + (REDUCE_LATENCY as usize));
+        ctx.add_child(ReduceOp::new(
+            qkt_exp_short_receiver,
+            rowsum_sender,
+            REDUCE_LATENCY,
+            INIT_INTERVAL,
+            SEQ_LEN,
+            SEQ_LEN,
+            ReduceOpType::Sum,
+        ));
+
+        // Align the numerator so the integer division keeps FRAC_BITS fractional bits (Q.24)
+        let (aligned_sender, aligned_receiver) = ctx.bounded::<i32>(chan_size_long);
+        ctx.add_child(Requantize::<i32, i32>::new(
+            qkt_exp_long_receiver,
+            aligned_sender,
+            (1 << FRAC_BITS) as f64,
+            QUANT_LATENCY,
+            INIT_INTERVAL,
+            SEQ_LEN * SEQ_LEN,
+        ));
+
+        // Div (Q.24 / Q.12 -> Q.12)
+        let (div_sender, div_receiver) = ctx.bounded::<i32>(chan_size + (BINARY_LATENCY as usize));
+        ctx.add_child(Binary::<i32>::new(
+            aligned_receiver,
+            rowsum_receiver,
+            div_sender,
+            BINARY_LATENCY,
+            INIT_INTERVAL,
+            SEQ_LEN,
+            SEQ_LEN,
+            BinaryOpType::Div,
+        ));
+
+        // Multiply with V (Q.12)
+        let (matvec_sender, matvec_receiver) = ctx.bounded::<i32>(chan_size);
+        ctx.add_child(MatVecProd::new(
+            div_receiver,
+            v_receiver,
+            matvec_sender,
+            MATVEC_LATENCY,
+            INIT_INTERVAL,
+            SEQ_LEN,
+            SEQ_LEN,
+        ));
+
+        // Back to floating point for checking
+        let (out_sender, out_receiver) = ctx.bounded::<f64>(chan_size);
+        ctx.add_child(Dequantize::<i32, f64>::new(
+            matvec_receiver,
+            out_sender,
+            1.0 / ((1 << FRAC_BITS) as f64),
+            QUANT_LATENCY,
+            INIT_INTERVAL,
+            SEQ_LEN,
+        ));
+
+        // Checkers
+        // Flooring in the integer division loses less than one LSB per element of the row
+        let out_iter = || (0..(SEQ_LEN)).map(|_i| (1_f64));
+        ctx.add_child(ApproxCheckerContext::new(out_iter, out_receiver, |a, b| {
+            (a - b).abs() < 0.02
+        }));
+
+        let initialized = ctx.initialize(Default::default()).unwrap();
+        #[cfg(feature = "dot")]
+        println!("{}", initialized.to_dot_string());
+
+        let summary = initialized.run(Default::default());
+        dbg!(summary.elapsed_cycles());
+        #[cfg(feature = "dot")]
+        {
+            println!("{}", summary.to_dot_string());
+        }
+    }
+
+    #[test]
+    fn requantize_saturates() {
+        let chan_size = 2;
+
+        let mut ctx = ProgramBuilder::default();
+
+        // x * 4 overflows i64 in Q30 for the large accumulators
+        let in_iter = || [i64::MAX, i64::MIN, 1 << 40, -3, 5].into_iter();
+        let out_iter = || [i32::MAX, i32::MIN, i32::MAX, -12, 20].into_iter();
+        let (in_sender, in_receiver) = ctx.bounded::<i64>(chan_size);
+        let (out_sender, out_receiver) = ctx.bounded::<i32>(chan_size);
+        ctx.add_child(GeneratorContext::new(in_iter, in_sender));
+        ctx.add_child(Requantize::<i64, i32>::new(
+            in_receiver,
+            out_sender,
+            4.0,
+            1,
+            1,
+            5,
+        ));
+        ctx.add_child(CheckerContext::new(out_iter, out_receiver));
+
+        let initialized = ctx.initialize(Default::default()).unwrap();
+        initialized.run(Default::default());
+    }
+}
@@ -0,0 +1,141 @@
+#[cfg(test)]
+mod tests {
+    use dam::{
+        simulation::ProgramBuilder,
+        utility_contexts::{ApproxCheckerContext, CheckerContext, GeneratorContext},
+    };
+
+    use crate::{
+        analysis::cost::{OpCounter, OpKind},
+        node::streamattn_reduce::{ReduceOp, ReduceOpType},
+    };
+
+    const REDUCE_LATENCY: u64 = 2;
+    const INIT_INTERVAL: u64 = 1;
+
+    fn rows() -> Vec<Vec<f64>> {
+        vec![
+            vec![0.5, -1.25, 3.0, 3.0, 0.75],
+            vec![-2.0],
+            vec![],
+            vec![1.5, -4.0, 2.0, 0.25],
+        ]
+    }
+
+    // Reduces every row of 'rows()' and checks the results, and the ArgMax indices if given
+    fn check_reduce(
+        op: ReduceOpType<f64>,
+        expected: Vec<f64>,
+        expected_index: Option<Vec<u64>>,
+    ) -> OpCounter {
+        let chan_size = 2; // FIFO Depth
+        let data = rows();
+        let row_lengths = data.iter().map(|r| r.len() as u64).collect();
+        let op_counter = OpCounter::default();
+
+        let mut ctx = ProgramBuilder::default();
+
+        let (in_sender, in_receiver) = ctx.bounded::<f64>(chan_size);
+        let (out_sender, out_receiver) = ctx.bounded::<f64>(chan_size);
+        ctx.add_child(GeneratorContext::new(
+            move || data.into_iter().flatten(),
+            in_sender,
+        ));
+        let mut reduce = ReduceOp::new(
+            in_receiver,
+            out_sender,
+            REDUCE_LATENCY,
+            INIT_INTERVAL,
+            0,
+            0,
+            op,
+        )
+        .with_row_lengths(row_lengths)
+        .with_op_counter(op_counter.clone());
+        if let Some(expected_index) = expected_index {
+            let (index_sender, index_receiver) = ctx.bounded::<u64>(chan_size);
+            reduce = reduce.with_index_out(index_sender);
+            ctx.add_child(CheckerContext::new(
+                move || expected_index.into_iter(),
+                index_receiver,
+            ));
+        }
+        ctx.add_child(reduce);
+        ctx.add_child(ApproxCheckerContext::new(
+            move || expected.into_iter(),
+            out_receiver,
+            |a, b| (a - b).abs() < 0.0001 || a == b,
+        ));
+
+        let initialized = ctx.initialize(Default::default()).unwrap();
+        initialized.run(Default::default());
+        op_counter
+    }
+
+    #[test]
+    fn reduce_min_prod() {
+        check_reduce(
+            ReduceOpType::Min,
+            vec![-1.25, -2.0, f64::INFINITY, -4.0],
+            None,
+        );
+        let ops = check_reduce(ReduceOpType::Prod, vec![-4.21875, -2.0, 1.0, -3.0], None);
+        // One multiply per combine, rows of n elements combine n - 1 times: 4 + 0 + 0 + 3
+        assert_eq!(ops.get(OpKind::Mul), 7);
+        assert_eq!(ops.get(OpKind::Add), 0);
+    }
+
+    #[test]
+    fn reduce_argmax() {
+        // Ties keep the first index, an empty row reports index 0
+        check_reduce(
+            ReduceOpType::ArgMax,
+            vec![3.0, -2.0, f64::NEG_INFINITY, 2.0],
+            Some(vec![2, 0, 0, 2]),
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_out_needs_argmax() {
+        let mut ctx = ProgramBuilder::default();
+        let (_in_sender, in_receiver) = ctx.bounded::<f64>(2);
+        let (out_sender, _out_receiver) = ctx.bounded::<f64>(2);
+        let (index_sender, _index_receiver) = ctx.bounded::<u64>(2);
+        let _ = ReduceOp::new(
+            in_receiver,
+            out_sender,
+            REDUCE_LATENCY,
+            INIT_INTERVAL,
+            4,
+            1,
+            ReduceOpType::Max,
+        )
+        .with_index_out(index_sender);
+    }
+
+    #[test]
+    fn reduce_log_sum_exp() {
+        let expected = rows()
+            .iter()
+            .map(|r| match r.len() {
+                0 => f64::NEG_INFINITY,
+                _ => r.iter().map(|x| x.exp()).sum::<f64>().ln(),
+            })
+            .collect();
+        let ops = check_reduce(ReduceOpType::log_sum_exp(), expected, None);
+        assert_eq!(ops.get(OpKind::Exp), 2 * 7);
+    }
+
+    #[test]
+    fn reduce_custom() {
+        // Largest magnitude with its sign, e.g. to pick the dominant score of a row
+        let op = ReduceOpType::custom(
+            |a: f64, b: f64| if b.abs() > a.abs() { b } else { a },
+            0_f64,
+            vec![(OpKind::Add, 2)],
+        );
+        let ops = check_reduce(op, vec![3.0, -2.0, 0.0, -4.0], None);
+        assert_eq!(ops.get(OpKind::Add), 2 * 7);
+    }
+}
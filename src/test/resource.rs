@@ -0,0 +1,70 @@
+#[cfg(test)]
+mod tests {
+    use dam::simulation::ProgramBuilder;
+
+    use crate::{
+        analysis::{
+            resource::{attn_resources, ResourceTable, UnitKind},
+            traffic::Architecture,
+        },
+        graph::{
+            flashattn::{tiled_flash_attn, FlashAttnConfig},
+            multihead::{add_checker, add_generators, flash_reference},
+        },
+    };
+
+    #[test]
+    fn attn_resource_table() {
+        let streamed_512 = attn_resources(Architecture::Streamed, 512);
+        let streamed_2048 = attn_resources(Architecture::Streamed, 2048);
+        let flash_512 = attn_resources(Architecture::Flash, 512);
+        let flash_2048 = attn_resources(Architecture::Flash, 2048);
+        println!("{}", streamed_2048);
+        println!("{}", flash_2048);
+
+        assert_eq!(streamed_512.units(UnitKind::Multiplier), 2);
+        assert_eq!(streamed_512.units(UnitKind::Adder), 2);
+        assert_eq!(streamed_512.units(UnitKind::Divider), 1);
+        assert_eq!(streamed_512.units(UnitKind::ExpUnit), 1);
+        assert_eq!(flash_512.units(UnitKind::Multiplier), 4);
+        assert_eq!(flash_512.units(UnitKind::Adder), 5);
+        assert_eq!(flash_512.units(UnitKind::Divider), 1);
+        assert_eq!(flash_512.units(UnitKind::ExpUnit), 3);
+
+        // The long FIFO of the streamed design grows with N, flash buffering does not
+        assert_eq!(
+            streamed_2048.fifo_bits() - streamed_512.fifo_bits(),
+            (2048 - 512) * 64
+        );
+        assert_eq!(flash_2048.storage_bits(), flash_512.storage_bits());
+        assert!(flash_2048.storage_bits() < streamed_2048.storage_bits());
+    }
+
+    #[test]
+    fn tiled_flash_attn_resources() {
+        const SEQ_LEN: u64 = 64;
+        let resources = ResourceTable::default();
+        let config = FlashAttnConfig {
+            seq_len: SEQ_LEN,
+            resources: resources.clone(),
+            ..Default::default()
+        };
+
+        let mut ctx = ProgramBuilder::default();
+        let (q, kt, v) = add_generators(&mut ctx, SEQ_LEN, SEQ_LEN, config.chan_size);
+        let out = tiled_flash_attn(&mut ctx, q, kt, v, &config, SEQ_LEN, 8);
+        add_checker(&mut ctx, out, flash_reference(0..SEQ_LEN, SEQ_LEN));
+
+        let tiled = resources.summary();
+        let flash = attn_resources(Architecture::Flash, SEQ_LEN);
+        println!("{}", tiled);
+
+        // Same nodes and FIFOs as the untiled pipeline
+        assert_eq!(tiled.nodes.len(), 5);
+        assert_eq!(tiled.fifos.len(), flash.fifos.len());
+        assert!(tiled.nodes.iter().any(|n| n.name == "TiledIncrOutP"));
+        assert_eq!(tiled.units(UnitKind::Divider), 1);
+        // The tile buffer of TiledIncrMax adds pipeline register bits
+        assert!(tiled.pipeline_reg_bits() > flash.pipeline_reg_bits());
+    }
+}
@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod tests {
+    use dam::{
+        simulation::ProgramBuilder,
+        utility_contexts::{ApproxCheckerContext, GeneratorContext},
+    };
+    use ndarray::Array1;
+
+    use crate::{
+        mask::{window::SlidingWindow, AttnMask},
+        node::rope::{rope_angle, Rope},
+    };
+
+    const ROPE_LATENCY: u64 = 6;
+    const INIT_INTERVAL: u64 = 1;
+    const SEQ_LEN: u64 = 32;
+    const HEAD_DIM: usize = 8;
+    const BASE: f64 = 10000_f64;
+
+    fn vector(t: u64) -> Array1<f64> {
+        Array1::from_iter(
+            (0..HEAD_DIM).map(|e| 0.1_f64 * (((t as usize + e) % 5) as f64) + 0.05_f64),
+        )
+    }
+
+    fn rotate(x: &Array1<f64>, position: u64) -> Array1<f64> {
+        let mut out = x.clone();
+        for m in 0..(HEAD_DIM / 2) {
+            let angle = rope_angle(BASE, HEAD_DIM, m, position);
+            out[2 * m] = x[2 * m] * angle.cos() - x[2 * m + 1] * angle.sin();
+            out[2 * m + 1] = x[2 * m] * angle.sin() + x[2 * m + 1] * angle.cos();
+        }
+        out
+    }
+
+    #[test]
+    fn rope_q_k() {
+        let chan_size = 2; // FIFO Depth
+        let mask = SlidingWindow::new(SEQ_LEN, 4);
+        let key_positions = mask.key_stream();
+        let num_keys = key_positions.len() as u64;
+
+        let mut ctx = ProgramBuilder::default();
+
+        let (q_sender, q_receiver) = ctx.bounded::<Array1<f64>>(chan_size);
+        let (k_sender, k_receiver) = ctx.bounded::<Array1<f64>>(chan_size);
+        ctx.add_child(GeneratorContext::new(|| (0..SEQ_LEN).map(vector), q_sender));
+        let k_keys = key_positions.clone();
+        ctx.add_child(GeneratorContext::new(
+            move || k_keys.into_iter().map(vector),
+            k_sender,
+        ));
+
+        // Q rows sit at their row index, K follows the positions of the masked key stream
+        let rope_depth = chan_size + (ROPE_LATENCY - 1) as usize;
+        let (q_rot_sender, q_rot_receiver) = ctx.bounded::<Array1<f64>>(rope_depth);
+        let (k_rot_sender, k_rot_receiver) = ctx.bounded::<Array1<f64>>(rope_depth);
+        ctx.add_child(Rope::new(
+            q_receiver,
+            q_rot_sender,
+            BASE,
+            ROPE_LATENCY,
+            INIT_INTERVAL,
+            SEQ_LEN,
+            SEQ_LEN,
+        ));
+        ctx.add_child(
+            Rope::new(
+                k_receiver,
+                k_rot_sender,
+                BASE,
+                ROPE_LATENCY,
+                INIT_INTERVAL,
+                SEQ_LEN,
+                num_keys,
+            )
+            .with_positions(key_positions.clone()),
+        );
+
+        let close = |a: &Array1<f64>, b: &Array1<f64>| {
+            a.iter().zip(b.iter()).all(|(x, y)| (x - y).abs() < 0.0001)
+        };
+        ctx.add_child(ApproxCheckerContext::new(
+            || (0..SEQ_LEN).map(|t| rotate(&vector(t), t)),
+            q_rot_receiver,
+            close,
+        ));
+        ctx.add_child(ApproxCheckerContext::new(
+            move || key_positions.into_iter().map(|p| rotate(&vector(p), p)),
+            k_rot_receiver,
+            close,
+        ));
+
+        let initialized = ctx.initialize(Default::default()).unwrap();
+        let summary = initialized.run(Default::default());
+        // One vector per II (the last one issues at num_keys - 1), the rotation only adds pipeline depth
+        let cycles = summary.elapsed_cycles().unwrap();
+        assert!(cycles >= (num_keys - 1) + ROPE_LATENCY);
+        assert!(cycles < num_keys + 4 * ROPE_LATENCY);
+
+        // Rotated scores only depend on the relative position
+        let q = vector(3);
+        let k = vector(7);
+        let score = |i: u64, j: u64| rotate(&q, i).dot(&rotate(&k, j));
+        assert!((score(10, 4) - score(20, 14)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    };
+
+    use dam::{
+        simulation::{DotConvertible, ProgramBuilder},
+        utility_contexts::GeneratorContext,
+    };
+
+    use crate::{
+        graph::{
+            flashattn::FlashAttnConfig,
+            multihead::{add_checker, add_q_generator, flash_reference, synthetic_k, synthetic_v},
+            row_tiled::row_tiled_flash_attn,
+        },
+        node::flashattn_running_op::Recurrence,
+    };
+
+    // Returns (elapsed cycles, K elements pulled from the K generator)
+    fn run_row_tiled_flash_attn(config: &FlashAttnConfig, rows_per_tile: u64) -> (u64, u64) {
+        let seq_len = config.seq_len;
+        let k_passes = seq_len / rows_per_tile;
+
+        let mut ctx = ProgramBuilder::default();
+
+        let q = add_q_generator(&mut ctx, seq_len, seq_len, config.chan_size);
+        let k_reads = Arc::new(AtomicU64::new(0));
+        let (kt_sender, kt) = ctx.bounded::<f64>(config.chan_size);
+        let (v_sender, v) = ctx.bounded::<f64>(config.chan_size);
+        let counter = k_reads.clone();
+        ctx.add_child(GeneratorContext::new(
+            move || {
+                (0..(k_passes * seq_len)).map(move |i| {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    synthetic_k(i % seq_len)
+                })
+            },
+            kt_sender,
+        ));
+        ctx.add_child(GeneratorContext::new(
+            move || (0..(k_passes * seq_len)).map(move |i| synthetic_v(i % seq_len)),
+            v_sender,
+        ));
+        let outputs = row_tiled_flash_attn(&mut ctx, q, kt, v, config, seq_len, rows_per_tile);
+        // Lane r computes rows r, Br + r, 2 Br + r, ...
+        for (r, out) in outputs.into_iter().enumerate() {
+            let rows = (0..k_passes).map(move |t| t * rows_per_tile + r as u64);
+            add_checker(&mut ctx, out, flash_reference(rows, seq_len));
+        }
+
+        let initialized = ctx.initialize(Default::default()).unwrap();
+        #[cfg(feature = "dot")]
+        println!("{}", initialized.to_dot_string());
+
+        let summary = initialized.run(Default::default());
+        dbg!(rows_per_tile, summary.elapsed_cycles());
+        (
+            summary.elapsed_cycles().unwrap(),
+            k_reads.load(Ordering::Relaxed),
+        )
+    }
+
+    #[test]
+    fn row_tiled_attn() {
+        const SEQ_LEN: u64 = 128;
+
+        let config = FlashAttnConfig {
+            seq_len: SEQ_LEN,
+            ..Default::default()
+        };
+
+        let (single_cycles, single_k_reads) = run_row_tiled_flash_attn(&config, 1);
+        let (tiled_cycles, tiled_k_reads) = run_row_tiled_flash_attn(&config, 4);
+
+        assert_eq!(single_k_reads, 16384);
+        assert_eq!(tiled_k_reads, 4096);
+        assert!(tiled_cycles * 2 < single_cycles);
+
+        // The recurrence replaces muticycle_ii in every lane
+        let derived = FlashAttnConfig {
+            seq_len: SEQ_LEN,
+            muticycle_ii: 7,
+            recurrence: Some(Recurrence::new(1, 1)),
+            ..Default::default()
+        };
+        assert_eq!(run_row_tiled_flash_attn(&derived, 4).0, tiled_cycles);
+    }
+}
@@ -0,0 +1,97 @@
+#[cfg(test)]
+mod tests {
+    use dam::{
+        simulation::ProgramBuilder,
+        utility_contexts::{ApproxCheckerContext, GeneratorContext},
+    };
+
+    use crate::{
+        analysis::cost::OpKind,
+        graph::{
+            multihead::{add_checker, add_generators, scaled_streamed_reference},
+            streamattn::{streamed_attn, StreamAttnConfig},
+        },
+        node::{score_scale::ScoreScale, streamattn_qkt::QKTExp},
+    };
+
+    const SEQ_LEN: u64 = 64;
+
+    fn run_qkt(score_scale: ScoreScale, expected: fn(f64) -> f64) {
+        let chan_size = 2; // FIFO Depth
+        let q_val = |i: u64| (i as f64) * 0.5_f64;
+        let kt_val = |j: u64| 0.1_f64 * ((j % 9) as f64);
+
+        let mut ctx = ProgramBuilder::default();
+
+        let (q_sender, q_receiver) = ctx.bounded::<f64>(chan_size);
+        let (kt_sender, kt_receiver) = ctx.bounded::<f64>(chan_size);
+        ctx.add_child(GeneratorContext::new(
+            move || (0..SEQ_LEN).map(q_val),
+            q_sender,
+        ));
+        ctx.add_child(GeneratorContext::new(
+            move || (0..(SEQ_LEN * SEQ_LEN)).map(move |idx| kt_val(idx % SEQ_LEN)),
+            kt_sender,
+        ));
+
+        let (out_sender, out_receiver) = ctx.bounded::<f64>(chan_size);
+        ctx.add_child(
+            QKTExp::new(q_receiver, kt_receiver, vec![out_sender], 11, 1, SEQ_LEN)
+                .with_score_scale(score_scale),
+        );
+
+        let out_iter = move || {
+            (0..(SEQ_LEN * SEQ_LEN))
+                .map(move |idx| expected(q_val(idx / SEQ_LEN) * kt_val(idx % SEQ_LEN)).exp())
+        };
+        ctx.add_child(ApproxCheckerContext::new(out_iter, out_receiver, |a, b| {
+            ((a - b) / b).abs() < 0.0001
+        }));
+
+        let initialized = ctx.initialize(Default::default()).unwrap();
+        initialized.run(Default::default());
+    }
+
+    #[test]
+    fn scaled_scores() {
+        run_qkt(ScoreScale::inv_sqrt_d(64, 2), |s| s / 8_f64);
+        run_qkt(ScoreScale::temperature(2_f64, 2), |s| s / 2_f64);
+        // Soft-capping bounds the largest scores (up to 25 here) to the cap
+        run_qkt(ScoreScale::soft_cap(1_f64, 5_f64, 6), |s| {
+            5_f64 * (s / 5_f64).tanh()
+        });
+    }
+
+    #[test]
+    fn scaled_streamed_attn() {
+        const SCALE_LATENCY: u64 = 3;
+
+        let run = |score_scale: ScoreScale| {
+            let expected = scaled_streamed_reference(0..SEQ_LEN, SEQ_LEN, &score_scale);
+            let config = StreamAttnConfig {
+                seq_len: SEQ_LEN,
+                score_scale,
+                ..Default::default()
+            };
+            let mut ctx = ProgramBuilder::default();
+            let (q, kt, v) = add_generators(&mut ctx, SEQ_LEN, SEQ_LEN, config.chan_size);
+            let out = streamed_attn(&mut ctx, q, kt, v, &config, SEQ_LEN);
+            add_checker(&mut ctx, out, expected);
+            let initialized = ctx.initialize(Default::default()).unwrap();
+            let summary = initialized.run(Default::default());
+            (summary.elapsed_cycles().unwrap(), config.op_counter)
+        };
+
+        let (plain_cycles, plain_ops) = run(ScoreScale::default());
+        let (scaled_cycles, scaled_ops) = run(ScoreScale::inv_sqrt_d(64, SCALE_LATENCY));
+
+        // The scale stage deepens the QKT pipeline without lowering its throughput
+        assert!(scaled_cycles >= plain_cycles + SCALE_LATENCY);
+        assert!(scaled_cycles < plain_cycles + 4 * SCALE_LATENCY);
+        // One extra multiply per score
+        assert_eq!(
+            scaled_ops.get(OpKind::Mul) - plain_ops.get(OpKind::Mul),
+            SEQ_LEN * SEQ_LEN
+        );
+    }
+}
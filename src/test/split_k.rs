@@ -0,0 +1,44 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        graph::{flashattn::FlashAttnConfig, split_k::run_split_k_attn},
+        node::flashattn_running_op::Recurrence,
+    };
+
+    #[test]
+    fn split_k_decode() {
+        const SEQ_LEN: u64 = 1024;
+        const NUM_ROWS: u64 = 4;
+        const MERGE_LATENCY: u64 = 30;
+
+        let config = FlashAttnConfig {
+            seq_len: SEQ_LEN,
+            ..Default::default()
+        };
+
+        let single = run_split_k_attn(1, NUM_ROWS, MERGE_LATENCY, &config);
+        let split = run_split_k_attn(4, NUM_ROWS, MERGE_LATENCY, &config);
+        println!("{}", single);
+        println!("{}", split);
+
+        // The merged partial states match dense attention over all keys
+        for report in [&single, &split] {
+            assert_eq!(report.error.count, NUM_ROWS);
+            assert!(report.error.max_abs_err < 0.0001);
+        }
+        // Each partition streams a quarter of the keys, the merge only adds P cycles per row
+        let speedup = (single.elapsed_cycles as f64) / (split.elapsed_cycles as f64);
+        assert!(speedup > 3_f64);
+        assert!(split.elapsed_cycles > NUM_ROWS * SEQ_LEN / 4 * config.running_ii(1));
+
+        // The recurrence replaces muticycle_ii in every partition
+        let derived = FlashAttnConfig {
+            seq_len: SEQ_LEN,
+            muticycle_ii: 7,
+            recurrence: Some(Recurrence::new(1, 1)),
+            ..Default::default()
+        };
+        let derived = run_split_k_attn(4, NUM_ROWS, MERGE_LATENCY, &derived);
+        assert_eq!(derived.elapsed_cycles, split.elapsed_cycles);
+    }
+}
@@ -0,0 +1,84 @@
+#[cfg(test)]
+mod tests {
+    use dam::simulation::{DotConvertible, ProgramBuilder};
+
+    use crate::{
+        graph::{
+            flashattn::{tiled_flash_attn, FlashAttnConfig},
+            multihead::{add_checker, add_generators, flash_reference},
+        },
+        node::flashattn_running_op::Recurrence,
+    };
+
+    fn run_tiled_flash_attn(config: &FlashAttnConfig, tile_size: u64) -> u64 {
+        let mut ctx = ProgramBuilder::default();
+
+        let (q, kt, v) = add_generators(&mut ctx, config.seq_len, config.seq_len, config.chan_size);
+        let out = tiled_flash_attn(&mut ctx, q, kt, v, config, config.seq_len, tile_size);
+        add_checker(
+            &mut ctx,
+            out,
+            flash_reference(0..config.seq_len, config.seq_len),
+        );
+
+        let initialized = ctx.initialize(Default::default()).unwrap();
+        #[cfg(feature = "dot")]
+        println!("{}", initialized.to_dot_string());
+
+        let summary = initialized.run(Default::default());
+        dbg!(tile_size, summary.elapsed_cycles());
+        summary.elapsed_cycles().unwrap()
+    }
+
+    #[test]
+    fn tiled_seq_agnostic_attn() {
+        const SEQ_LEN: u64 = 256;
+
+        let config = FlashAttnConfig {
+            seq_len: SEQ_LEN,
+            ..Default::default()
+        };
+
+        // With Bc = 1 every key pays the multi-cycle rescale, larger tiles amortize it
+        let untiled = run_tiled_flash_attn(&config, 1);
+        let tiled = run_tiled_flash_attn(&config, 16);
+        assert!(untiled >= SEQ_LEN * SEQ_LEN * config.muticycle_ii);
+        assert!(tiled < untiled);
+        assert!(tiled < SEQ_LEN * SEQ_LEN * config.muticycle_ii);
+    }
+
+    #[test]
+    fn tiled_derived_rescale_ii() {
+        const SEQ_LEN: u64 = 64;
+
+        for tile_size in [1, 16] {
+            // A 1-cycle multiply and a 1-cycle add are what MUTICYCLE_II = 2 stands for
+            let guessed = run_tiled_flash_attn(
+                &FlashAttnConfig {
+                    seq_len: SEQ_LEN,
+                    ..Default::default()
+                },
+                tile_size,
+            );
+            let derived = run_tiled_flash_attn(
+                &FlashAttnConfig {
+                    seq_len: SEQ_LEN,
+                    muticycle_ii: 7, // ignored once the recurrence is modeled
+                    recurrence: Some(Recurrence::new(1, 1)),
+                    ..Default::default()
+                },
+                tile_size,
+            );
+            assert_eq!(guessed, derived);
+        }
+
+        // A slow recurrence stalls every key with Bc = 1, a tile of 16 keys hides it
+        let slow = FlashAttnConfig {
+            seq_len: SEQ_LEN,
+            recurrence: Some(Recurrence::new(2, 3)),
+            ..Default::default()
+        };
+        assert!(run_tiled_flash_attn(&slow, 1) >= SEQ_LEN * SEQ_LEN * 5);
+        assert!(run_tiled_flash_attn(&slow, 16) < SEQ_LEN * SEQ_LEN * 2);
+    }
+}
@@ -0,0 +1,40 @@
+#[cfg(test)]
+mod tests {
+    use crate::analysis::traffic::{attention_ops, run_attn_traffic, Architecture, Direction};
+
+    #[test]
+    fn attn_traffic() {
+        const SEQ_LEN: u64 = 128;
+
+        let streamed = run_attn_traffic(Architecture::Streamed, SEQ_LEN);
+        let flash = run_attn_traffic(Architecture::Flash, SEQ_LEN);
+        println!("{}", streamed);
+        println!("{}", flash);
+
+        // Q is read and O written once per row, K and V are re-streamed once per query row
+        let expected = [
+            ("Q", Direction::Read, SEQ_LEN),
+            ("K", Direction::Read, SEQ_LEN * SEQ_LEN),
+            ("V", Direction::Read, SEQ_LEN * SEQ_LEN),
+            ("O", Direction::Write, SEQ_LEN),
+        ];
+        for arch in [&streamed, &flash] {
+            assert_eq!(arch.traffic.operands.len(), expected.len());
+            for (operand, direction, elements) in expected {
+                let traffic = arch.traffic.operand(operand).unwrap();
+                assert_eq!(traffic.direction, direction);
+                assert_eq!(traffic.elements, elements);
+                assert_eq!(traffic.bytes, elements * 8);
+            }
+            assert_eq!(
+                arch.traffic.bytes(Direction::Read),
+                (2 * SEQ_LEN + 1) * SEQ_LEN * 8
+            );
+            assert_eq!(arch.traffic.bytes(Direction::Write), SEQ_LEN * 8);
+        }
+        // Neither design keeps K/V on chip, so both sit at the same point below 1/4 op/byte
+        let expected = (attention_ops(SEQ_LEN) as f64) / (((2 * SEQ_LEN + 2) * SEQ_LEN * 8) as f64);
+        assert!((streamed.arithmetic_intensity() - expected).abs() < 1e-12);
+        assert!((flash.arithmetic_intensity() - expected).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,262 @@
+#[cfg(test)]
+mod tests {
+    use dam::{
+        context_tools::*,
+        simulation::ProgramBuilder,
+        utility_contexts::{ApproxCheckerContext, GeneratorContext},
+    };
+
+    use crate::node::{
+        streamattn_reduce::{ReduceOp, ReduceOpType},
+        tree_reduce::{PartialSumAccum, TreeReduce},
+    };
+
+    const ADD_LATENCY: u64 = 2; // what forces MUTICYCLE_II = 2 in the flash tests
+    const INIT_INTERVAL: u64 = 1;
+    const ROW_LEN: u64 = 64;
+    const NUM_ROWS: u64 = 16;
+
+    fn elem(idx: u64) -> f64 {
+        0.01_f64 * ((idx % 11) as f64)
+    }
+
+    fn row_sums() -> Vec<f64> {
+        (0..NUM_ROWS)
+            .map(|r| (0..ROW_LEN).map(|j| elem(r * ROW_LEN + j)).sum())
+            .collect()
+    }
+
+    fn finish<'a>(mut ctx: ProgramBuilder<'a>, out: Receiver<f64>) -> u64 {
+        ctx.add_child(ApproxCheckerContext::new(
+            || row_sums().into_iter(),
+            out,
+            |a, b| (a - b).abs() < 0.0001,
+        ));
+        let initialized = ctx.initialize(Default::default()).unwrap();
+        let summary = initialized.run(Default::default());
+        summary.elapsed_cycles().unwrap()
+    }
+
+    // One accumulator whose II has to cover the adder latency
+    fn sequential() -> u64 {
+        let chan_size = 2; // FIFO Depth
+        let mut ctx = ProgramBuilder::default();
+        let (in_sender, in_receiver) = ctx.bounded::<f64>(chan_size);
+        let (out_sender, out_receiver) = ctx.bounded::<f64>(chan_size);
+        ctx.add_child(GeneratorContext::new(
+            || (0..(NUM_ROWS * ROW_LEN)).map(elem),
+            in_sender,
+        ));
+        ctx.add_child(ReduceOp::new(
+            in_receiver,
+            out_sender,
+            ADD_LATENCY,
+            ADD_LATENCY,
+            ROW_LEN,
+            NUM_ROWS,
+            ReduceOpType::Sum,
+        ));
+        finish(ctx, out_receiver)
+    }
+
+    fn partial_sums(num_partials: u64) -> u64 {
+        let chan_size = 2; // FIFO Depth
+        let mut ctx = ProgramBuilder::default();
+        let (in_sender, in_receiver) = ctx.bounded::<f64>(chan_size);
+        let (out_sender, out_receiver) = ctx.bounded::<f64>(chan_size);
+        ctx.add_child(GeneratorContext::new(
+            || (0..(NUM_ROWS * ROW_LEN)).map(elem),
+            in_sender,
+        ));
+        ctx.add_child(PartialSumAccum::new(
+            in_receiver,
+            out_sender,
+            ADD_LATENCY,
+            INIT_INTERVAL,
+            num_partials,
+            ROW_LEN,
+            NUM_ROWS,
+            ReduceOpType::Sum,
+        ));
+        finish(ctx, out_receiver)
+    }
+
+    fn tree(num_lanes: u64) -> u64 {
+        let chan_size = 2; // FIFO Depth
+        let mut ctx = ProgramBuilder::default();
+        let mut lanes = vec![];
+        for l in 0..num_lanes {
+            let (lane_sender, lane_receiver) = ctx.bounded::<f64>(chan_size);
+            ctx.add_child(GeneratorContext::new(
+                move || {
+                    (0..(NUM_ROWS * ROW_LEN))
+                        .filter(move |idx| idx % num_lanes == l)
+                        .map(elem)
+                },
+                lane_sender,
+            ));
+            lanes.push(lane_receiver);
+        }
+        let (out_sender, out_receiver) = ctx.bounded::<f64>(chan_size);
+        ctx.add_child(TreeReduce::new(
+            lanes,
+            out_sender,
+            ADD_LATENCY,
+            INIT_INTERVAL,
+            ROW_LEN,
+            NUM_ROWS,
+            ReduceOpType::Sum,
+        ));
+        finish(ctx, out_receiver)
+    }
+
+    #[test]
+    fn reduction_hardware() {
+        let sequential_cycles = sequential();
+        let partial_cycles = partial_sums(ADD_LATENCY);
+        let tree_cycles = tree(4);
+        println!(
+            "sequential: {}, {} partial sums: {}, 4-lane tree: {}",
+            sequential_cycles, ADD_LATENCY, partial_cycles, tree_cycles
+        );
+
+        let num_elems = NUM_ROWS * ROW_LEN;
+        assert!(sequential_cycles >= ADD_LATENCY * num_elems);
+        // As many partials as adder stages hide the recurrence, II = 1
+        assert!(partial_cycles >= num_elems);
+        assert!(partial_cycles < num_elems + 4 * ROW_LEN);
+        assert!(partial_sums(1) >= ADD_LATENCY * num_elems);
+        // W lanes retire W elements per accumulator update
+        assert!(tree_cycles < ADD_LATENCY * num_elems / 4 + 4 * ROW_LEN);
+    }
+
+    #[test]
+    fn empty_rows() {
+        let chan_size = 2; // FIFO Depth
+        let mut ctx = ProgramBuilder::default();
+        let (in_sender, in_receiver) = ctx.bounded::<f64>(chan_size);
+        let (lane_sender, lane_receiver) = ctx.bounded::<f64>(chan_size);
+        let (partial_sender, partial_receiver) = ctx.bounded::<f64>(chan_size);
+        let (tree_sender, tree_receiver) = ctx.bounded::<f64>(chan_size);
+        ctx.add_child(GeneratorContext::new(std::iter::empty::<f64>, in_sender));
+        ctx.add_child(GeneratorContext::new(std::iter::empty::<f64>, lane_sender));
+        ctx.add_child(PartialSumAccum::new(
+            in_receiver,
+            partial_sender,
+            ADD_LATENCY,
+            INIT_INTERVAL,
+            ADD_LATENCY,
+            0,
+            NUM_ROWS,
+            ReduceOpType::Prod,
+        ));
+        ctx.add_child(TreeReduce::new(
+            vec![lane_receiver],
+            tree_sender,
+            ADD_LATENCY,
+            INIT_INTERVAL,
+            0,
+            NUM_ROWS,
+            ReduceOpType::Prod,
+        ));
+        // Every empty row still produces one result, the identity of the op
+        for out in [partial_receiver, tree_receiver] {
+            ctx.add_child(ApproxCheckerContext::new(
+                || (0..NUM_ROWS).map(|_i| 1_f64),
+                out,
+                |a, b| (a - b).abs() < 0.0001,
+            ));
+        }
+        let initialized = ctx.initialize(Default::default()).unwrap();
+        initialized.run(Default::default());
+    }
+
+    #[test]
+    fn ragged_rows() {
+        // Row r holds r elements, including an empty first row
+        const NUM_LANES: u64 = 4;
+        let row_lengths: Vec<u64> = (0..NUM_ROWS).collect();
+        let total: u64 = row_lengths.iter().sum();
+        let row_starts: Vec<u64> = row_lengths
+            .iter()
+            .scan(0, |start, len| {
+                let row_start = *start;
+                *start += len;
+                Some(row_start)
+            })
+            .collect();
+        let sums: Vec<f64> = row_lengths
+            .iter()
+            .zip(row_starts.iter())
+            .map(|(len, start)| (0..*len).map(|j| elem(start + j)).sum())
+            .collect();
+        // Element j of a row goes to lane j % NUM_LANES
+        let lane_elems: Vec<Vec<u64>> = (0..NUM_LANES)
+            .map(|l| {
+                row_lengths
+                    .iter()
+                    .zip(row_starts.iter())
+                    .flat_map(|(len, start)| {
+                        (0..*len)
+                            .filter(move |j| j % NUM_LANES == l)
+                            .map(move |j| start + j)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let chan_size = 2; // FIFO Depth
+        let mut ctx = ProgramBuilder::default();
+        let (in_sender, in_receiver) = ctx.bounded::<f64>(chan_size);
+        let (partial_sender, partial_receiver) = ctx.bounded::<f64>(chan_size);
+        let (tree_sender, tree_receiver) = ctx.bounded::<f64>(chan_size);
+        ctx.add_child(GeneratorContext::new(
+            move || (0..total).map(elem),
+            in_sender,
+        ));
+        let mut lanes = vec![];
+        for idxs in lane_elems {
+            let (lane_sender, lane_receiver) = ctx.bounded::<f64>(chan_size);
+            ctx.add_child(GeneratorContext::new(
+                move || idxs.clone().into_iter().map(elem),
+                lane_sender,
+            ));
+            lanes.push(lane_receiver);
+        }
+        ctx.add_child(
+            PartialSumAccum::new(
+                in_receiver,
+                partial_sender,
+                ADD_LATENCY,
+                INIT_INTERVAL,
+                ADD_LATENCY,
+                ROW_LEN,
+                NUM_ROWS,
+                ReduceOpType::Sum,
+            )
+            .with_row_lengths(row_lengths.clone()),
+        );
+        ctx.add_child(
+            TreeReduce::new(
+                lanes,
+                tree_sender,
+                ADD_LATENCY,
+                INIT_INTERVAL,
+                ROW_LEN,
+                NUM_ROWS,
+                ReduceOpType::Sum,
+            )
+            .with_row_lengths(row_lengths),
+        );
+        for out in [partial_receiver, tree_receiver] {
+            let sums = sums.clone();
+            ctx.add_child(ApproxCheckerContext::new(
+                move || sums.clone().into_iter(),
+                out,
+                |a, b| (a - b).abs() < 0.0001,
+            ));
+        }
+        let initialized = ctx.initialize(Default::default()).unwrap();
+        initialized.run(Default::default());
+    }
+}
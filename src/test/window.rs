@@ -0,0 +1,41 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        analysis::traffic::Architecture,
+        graph::masked::run_masked_attn,
+        mask::{window::SlidingWindow, AttnMask, Dense},
+    };
+
+    #[test]
+    fn sliding_window_attn() {
+        const SEQ_LEN: u64 = 256;
+        const WINDOW: u64 = 32;
+
+        let mask = SlidingWindow::new(SEQ_LEN, WINDOW);
+        // Rows near the start see fewer than W + 1 keys
+        assert_eq!(mask.row_lengths()[0], 1);
+        assert_eq!(mask.row_lengths()[(SEQ_LEN - 1) as usize], WINDOW + 1);
+        assert!(mask.num_keys() <= SEQ_LEN * (WINDOW + 1));
+
+        for arch in [Architecture::Streamed, Architecture::Flash] {
+            let dense = run_masked_attn(arch, &Dense::new(SEQ_LEN));
+            let window = run_masked_attn(arch, &mask);
+            println!("{}", dense);
+            println!("{}", window);
+
+            assert!(dense.error.max_abs_err < 0.0001);
+            assert!(window.mask_error.max_abs_err < 0.0001);
+            // Cycles scale with N * W instead of N^2
+            assert!(window.elapsed_cycles * 4 < dense.elapsed_cycles);
+            assert!(window.elapsed_cycles >= mask.num_keys());
+
+            // The long FIFO of the streamed design only holds one window
+            if arch == Architecture::Streamed {
+                assert_eq!(
+                    dense.resources.fifo_bits() - window.resources.fifo_bits(),
+                    (SEQ_LEN - (WINDOW + 1)) * 64
+                );
+            }
+        }
+    }
+}